@@ -1,6 +1,8 @@
+use std::time::Duration;
+
 use bson::oid::ObjectId;
 
-use crate::{constants, models::{Inventory, InventoryUpdate, OrderTransaction}, persistence::{InventoryRepository, OrderTransactionRepository}};
+use crate::{constants, models::{Basket, BasketSweepResult, Inventory, InventoryRelease, InventoryUpdate, OrderTransaction}, persistence::{BasketRepository, InventoryRepository, OrderTransactionRepository, PaymentRepository}};
 
 pub struct InventoryKeeperService {
     inventory_repository: InventoryRepository,
@@ -38,6 +40,85 @@ impl InventoryKeeperService {
     
 }
 
+pub struct BasketSweeperService {
+    basket_repository: BasketRepository,
+    payment_repository: PaymentRepository,
+    inventory_repository: InventoryRepository,
+}
+
+impl BasketSweeperService {
+
+    pub fn new(basket_repository: BasketRepository, payment_repository: PaymentRepository, inventory_repository: InventoryRepository) -> Self {
+        BasketSweeperService {
+            basket_repository,
+            payment_repository,
+            inventory_repository
+        }
+    }
+
+    /// Marks expired unpaid baskets as abandoned and gives their inventories back right away
+    /// instead of waiting for the reservations to lapse.
+    pub async fn sweep_abandoned_baskets(&self, retention: Duration) -> anyhow::Result<BasketSweepResult> {
+        if let Err(err) = self.basket_repository.ensure_abandoned_basket_retention(retention).await {
+            tracing::error!("Error ensuring abandoned basket retention: {:?}", err);
+        }
+
+        let now = chrono::Utc::now();
+        let mut result = BasketSweepResult::default();
+        let mut after_id = None;
+        loop {
+            let baskets = self.basket_repository.get_expired_baskets(now, after_id, constants::BASKET_SWEEP_PAGE_SIZE).await?;
+            let Some(last_basket) = baskets.last() else {
+                break;
+            };
+            after_id = Some(last_basket.id);
+            let basket_ids: Vec<ObjectId> = baskets.iter().map(|b| b.id).collect();
+
+            // A paid basket that was never purchased needs a person to look at it, not a sweep.
+            let paid_basket_ids = self.payment_repository.get_paid_basket_ids(basket_ids).await?;
+
+            result.expired += baskets.len();
+            for basket in baskets.iter() {
+                if paid_basket_ids.contains(&basket.id) {
+                    result.skipped_paid += 1;
+                    continue;
+                }
+                if !self.basket_repository.mark_abandoned(basket, now).await? {
+                    continue;
+                }
+                result.abandoned += 1;
+                result.released_inventories += self.inventory_repository.release_inventories(get_inventory_releases(basket)).await?;
+            }
+
+            if (baskets.len() as i64) < constants::BASKET_SWEEP_PAGE_SIZE {
+                break;
+            }
+        }
+
+        tracing::info!(
+            expired = result.expired,
+            skipped_paid = result.skipped_paid,
+            abandoned = result.abandoned,
+            released_inventories = result.released_inventories,
+            "basket sweep done"
+        );
+        Ok(result)
+    }
+}
+
+fn get_inventory_releases(basket: &Basket) -> Vec<InventoryRelease> {
+    basket.basket_items.iter()
+        .flat_map(|item| item.basketed_inventories.iter())
+        .filter_map(|basketed_inventory| match ObjectId::parse_str(&basketed_inventory.inventory_id) {
+            Ok(id) => Some(InventoryRelease { id, reserved_until: basketed_inventory.reserved_until }),
+            Err(_) => {
+                tracing::error!("Invalid inventory id {} in basket {}", basketed_inventory.inventory_id, basket.id);
+                None
+            }
+        })
+        .collect()
+}
+
 fn get_inventory_updates(inventories: Vec<crate::models::Inventory>, order_transactions: &Vec<OrderTransaction>) -> Vec<InventoryUpdate> {
     inventories.iter().map(|inventory|create_inventory_update(inventory, order_transactions)).collect()
 }
@@ -45,7 +126,7 @@ fn get_inventory_updates(inventories: Vec<crate::models::Inventory>, order_trans
 fn create_inventory_update(inventory: &Inventory, order_transactions: &Vec<OrderTransaction>) -> InventoryUpdate {
     let status:String;
 
-    if order_contains_inventory(order_transactions, &inventory.id) {
+    if is_inventory_sold(order_transactions, &inventory.id) {
         status = constants::INVENTORY_STATUS_SOLD.to_string();
     }
     else {
@@ -59,16 +140,21 @@ fn create_inventory_update(inventory: &Inventory, order_transactions: &Vec<Order
     }
 }
 
-fn order_contains_inventory(order_transactions: &Vec<OrderTransaction>, inventory_id: &ObjectId) -> bool {
-    order_transactions.iter().any(|order_transaction|
-        order_transaction.items.iter().any(|i| i.inventories.iter().any(|ii| &ii.inventory_id == inventory_id))
-    )
+/// A refunded inventory can be restocked and sold again, so it is sold while its sales outnumber its refunds.
+fn is_inventory_sold(order_transactions: &Vec<OrderTransaction>, inventory_id: &ObjectId) -> bool {
+    let count = |r#type: &str| order_transactions.iter()
+        .filter(|order_transaction| order_transaction.r#type == r#type)
+        .filter(|order_transaction|
+            order_transaction.items.iter().any(|i| i.inventories.iter().any(|ii| &ii.inventory_id == inventory_id))
+        )
+        .count();
+    count(constants::ORDER_TRANSACTION_TYPE_SALE) > count(constants::ORDER_TRANSACTION_TYPE_REFUND)
 }
 
 
 #[cfg(test)]
 mod tests {
-    use crate::{constants::INVENTORY_STATUS_RESERVED, models::{OrderTransactionItem, OrderTransactionItemInventory}};
+    use crate::{constants::INVENTORY_STATUS_RESERVED, models::{BasketItem, BasketedInventory, OrderTransactionItem, OrderTransactionItemInventory}};
 
     use super::*;
 
@@ -92,6 +178,7 @@ mod tests {
                         }],
                     ..Default::default()
                 }],
+            r#type: constants::ORDER_TRANSACTION_TYPE_SALE.to_string(),
             ..Default::default()
         };
 
@@ -121,6 +208,7 @@ mod tests {
                         }],
                     ..Default::default()
                 }],
+            r#type: constants::ORDER_TRANSACTION_TYPE_SALE.to_string(),
             ..Default::default()
         };
 
@@ -130,4 +218,67 @@ mod tests {
         //assert
         assert_eq!(inventory_update.status, super::constants::INVENTORY_STATUS_AVAILABLE.to_string());
     }
+
+    #[test]
+    fn test_create_inventory_update_refunded() {
+        //arrange
+        let inventory = Inventory {
+            id: ObjectId::new(),
+            concurrency_stamp: Default::default(),
+            status: INVENTORY_STATUS_RESERVED.to_string(),
+        };
+        let create_order_transaction = |r#type: &str| OrderTransaction {
+            items: vec![
+                OrderTransactionItem {
+                    inventories: vec![
+                        OrderTransactionItemInventory{
+                            inventory_id:inventory.id.clone(),
+                            ..Default::default()
+                        }],
+                    ..Default::default()
+                }],
+            r#type: r#type.to_string(),
+            ..Default::default()
+        };
+        let refunded = vec![
+            create_order_transaction(constants::ORDER_TRANSACTION_TYPE_SALE),
+            create_order_transaction(constants::ORDER_TRANSACTION_TYPE_REFUND),
+        ];
+        let sold_again = vec![
+            create_order_transaction(constants::ORDER_TRANSACTION_TYPE_SALE),
+            create_order_transaction(constants::ORDER_TRANSACTION_TYPE_REFUND),
+            create_order_transaction(constants::ORDER_TRANSACTION_TYPE_SALE),
+        ];
+
+        //act
+        let refunded_update = create_inventory_update(&inventory, &refunded);
+        let sold_again_update = create_inventory_update(&inventory, &sold_again);
+
+        //assert
+        assert_eq!(refunded_update.status, super::constants::INVENTORY_STATUS_AVAILABLE.to_string());
+        assert_eq!(sold_again_update.status, super::constants::INVENTORY_STATUS_SOLD.to_string());
+    }
+
+    #[test]
+    fn test_get_inventory_releases() {
+        //arrange
+        let inventory_id = ObjectId::new();
+        let reserved_until = chrono::Utc::now();
+        let basket = Basket {
+            basket_items: vec![
+                BasketItem {
+                    basketed_inventories: vec![
+                        BasketedInventory { inventory_id: inventory_id.to_hex(), reserved_until },
+                        BasketedInventory { inventory_id: "not-an-id".to_string(), reserved_until },
+                    ],
+                }],
+            ..Default::default()
+        };
+
+        //act
+        let inventory_releases = get_inventory_releases(&basket);
+
+        //assert
+        assert_eq!(inventory_releases, vec![InventoryRelease { id: inventory_id, reserved_until }]);
+    }
 }
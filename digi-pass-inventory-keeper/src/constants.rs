@@ -3,3 +3,20 @@ pub const INVENTORY_STATUS_RESERVED: &str = "Reserved";
 pub const INVENTORY_STATUS_SOLD: &str = "Sold";
 // pub const INVENTORY_STATUS_CANCELLED: &str = "Cancelled";
 
+pub const ORDER_TRANSACTION_TYPE_SALE: &str = "sale";
+pub const ORDER_TRANSACTION_TYPE_REFUND: &str = "refund";
+
+
+pub const BASKET_STATUS_OPEN: &str = "open";
+pub const BASKET_STATUS_ABANDONED: &str = "abandoned";
+
+/// Amounts were bare PHP centavos before they carried their currency.
+pub const LEGACY_CURRENCY: &str = "PHP";
+
+pub const PAYMENT_STATUS_PAID: &str = "paid";
+/// Held for a manual capture, the basket is kept until the payment is captured or voided.
+pub const PAYMENT_STATUS_AUTHORIZED: &str = "authorized";
+
+pub const DEFAULT_BASKET_RETENTION_DAYS: u64 = 30;
+/// Expired baskets are swept in pages of this size until none are left.
+pub const BASKET_SWEEP_PAGE_SIZE: i64 = 1000;
@@ -3,14 +3,14 @@ mod models;
 mod persistence;
 mod constants;
 
-use std::env;
+use std::{env, time::Duration};
 
 use aws_sdk_secretsmanager::types::Filter;
 use dotenvy::dotenv;
 use mongodb::Client;
 use tracing_subscriber::fmt;
 
-use crate::{application::InventoryKeeperService, persistence::{InventoryRepository, OrderTransactionRepository}};
+use crate::{application::{BasketSweeperService, InventoryKeeperService}, persistence::{BasketRepository, InventoryRepository, OrderTransactionRepository, PaymentRepository}};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()>{
@@ -37,6 +37,21 @@ async fn main() -> anyhow::Result<()>{
     
     tracing_subscriber::fmt().event_format(format).init();
     tracing::info!("Starting Inventory Keeper!");
+
+    let retention_days = env::var("BasketSweeper__RetentionDays")
+        .ok()
+        .and_then(|days| days.parse::<u64>().ok())
+        .unwrap_or(constants::DEFAULT_BASKET_RETENTION_DAYS);
+    let sweeper_service = BasketSweeperService::new(
+        BasketRepository::new(client.clone(), database.clone(), "Baskets".to_string()),
+        PaymentRepository::new(client.clone(), database.clone(), "Payments".to_string()),
+        InventoryRepository::new(client.clone(), database.clone(), "Inventories".to_string()),
+    );
+    // Sweep first so the inventories of abandoned baskets are not left for the keeping below.
+    if let Err(err) = sweeper_service.sweep_abandoned_baskets(Duration::from_secs(retention_days * 24 * 60 * 60)).await {
+        tracing::error!("Error sweeping abandoned baskets: {:?}", err);
+    }
+
     let inventory_repository = InventoryRepository::new(client.clone(), database.clone(), "Inventories".to_string());
     let order_transaction_repository = OrderTransactionRepository::new(client.clone(), database.clone(), "OrderTransactions".to_string());
     let keeper_service = InventoryKeeperService::new(inventory_repository, order_transaction_repository);
@@ -1,6 +1,8 @@
 use bson::oid::ObjectId;
 use serde::{Deserialize, Serialize};
 
+use crate::constants;
+
 #[derive(Serialize, Deserialize, Debug, Default)]
 pub struct Inventory {
     #[serde(rename = "_id", )]
@@ -31,7 +33,8 @@ pub struct OrderTransaction {
 pub struct OrderTransactionItem {
     pub id: String,
     pub created_at: chrono::DateTime<chrono::Utc>,
-    pub price: i32,
+    #[serde(deserialize_with = "deserialize_legacy_money")]
+    pub price: Money,
     pub inventories: Vec<OrderTransactionItemInventory>,
 }
 
@@ -41,4 +44,74 @@ pub struct OrderTransactionItemInventory {
     pub event_id: ObjectId,
     pub name: String,
     pub created_at: chrono::DateTime<chrono::Utc>,
-}
\ No newline at end of file
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Money {
+    pub amount: i64,
+    pub currency: String,
+}
+
+/// Reads prices stored as bare `i32` centavos before they became `Money`.
+fn deserialize_legacy_money<'de, D>(deserializer: D) -> Result<Money, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StoredMoney {
+        Money(Money),
+        Legacy(i64),
+    }
+
+    Ok(match StoredMoney::deserialize(deserializer)? {
+        StoredMoney::Money(money) => money,
+        StoredMoney::Legacy(amount) => Money {
+            amount,
+            currency: constants::LEGACY_CURRENCY.to_string(),
+        },
+    })
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Basket {
+    #[serde(rename = "_id", )]
+    pub id: ObjectId,
+    /// Empty for baskets stored before they had one.
+    #[serde(default)]
+    pub concurrency_stamp: String,
+    pub basket_items: Vec<BasketItem>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct BasketItem {
+    pub basketed_inventories: Vec<BasketedInventory>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct BasketedInventory {
+    pub inventory_id: String,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub reserved_until: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Payment {
+    pub basket_id: Option<ObjectId>,
+    pub status: String,
+}
+
+/// An inventory held by a basket, only released while the reservation is still the basket's own.
+#[derive(Debug, PartialEq)]
+pub struct InventoryRelease {
+    pub id: ObjectId,
+    pub reserved_until: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Default)]
+pub struct BasketSweepResult {
+    pub expired: usize,
+    pub skipped_paid: usize,
+    pub abandoned: usize,
+    pub released_inventories: u64,
+}
@@ -1,8 +1,13 @@
 
-use bson::{doc, oid::ObjectId};
+use std::time::Duration;
+
+use bson::{doc, oid::ObjectId, Bson};
 use chrono::{DateTime, Utc};
-use mongodb::{options::FindOptions, Client, Collection};
-use crate::models::{Inventory, InventoryUpdate, OrderTransaction};
+use mongodb::{error::ErrorKind, options::{FindOptions, IndexOptions}, Client, Collection, IndexModel};
+use crate::{constants, models::{Basket, Inventory, InventoryRelease, InventoryUpdate, OrderTransaction, Payment}};
+
+/// Returned when an index exists with the same keys but different options.
+const INDEX_OPTIONS_CONFLICT_CODE: i32 = 85;
 
 
 pub struct InventoryRepository {
@@ -68,7 +73,32 @@ impl InventoryRepository {
         Ok(())
     }
 
+    /// Returns the number of inventories released.
+    pub async fn release_inventories(&self, inventory_releases: Vec<InventoryRelease>) -> anyhow::Result<u64> {
+        let collection = self.get_collection();
+        let mut released = 0;
+        for inventory_release in inventory_releases.iter() {
+            let update_result = collection.update_one(
+                doc! {
+                    "_id": &inventory_release.id,
+                    "status": constants::INVENTORY_STATUS_RESERVED,
+                    "reserved_until": inventory_release.reserved_until,
+                },
+                doc! {"$set": {
+                    "status": constants::INVENTORY_STATUS_AVAILABLE,
+                    "reserved_until": Utc::now(),
+                    "concurrency_stamp": ObjectId::new().to_hex()
+                }},
+                None
+            ).await;
+            match update_result {
+                Ok(update_result) => released += update_result.modified_count,
+                Err(err) => tracing::error!("Error releasing inventory {:?}: {:?}", inventory_release.id, err),
+            }
+        }
 
+        Ok(released)
+    }
 }
 
 
@@ -106,4 +136,144 @@ impl  OrderTransactionRepository {
 
         return Ok(result);
     }
-}
\ No newline at end of file
+}
+
+pub struct BasketRepository {
+    client: Client,
+    database: String,
+    collection: String
+}
+
+impl BasketRepository {
+
+    pub fn new(client: Client, database: String, collection: String) -> Self {
+        BasketRepository {
+            client,
+            database,
+            collection
+        }
+    }
+
+    fn get_collection(&self) -> Collection<Basket> {
+        self.client.database(&self.database).collection(&self.collection)
+    }
+
+    /// Open baskets past their validity that were never claimed for purchase, a page of at most `limit`
+    /// starting after the basket `after_id`. Paged by id, so baskets that are skipped do not fill every page.
+    pub async fn get_expired_baskets(&self, now: DateTime<Utc>, after_id: Option<ObjectId>, limit: i64) -> anyhow::Result<Vec<Basket>> {
+        let collection = self.get_collection();
+        let options = FindOptions::builder()
+                  .sort(doc! {"_id": 1})
+                  .limit(limit)
+                  .build();
+
+        // Baskets created before the status field existed have no status.
+        let mut filter = doc! {
+            "status": { "$in": [constants::BASKET_STATUS_OPEN, Bson::Null] },
+            "order_id": Bson::Null,
+            "valid_until": { "$lt": now }
+        };
+        if let Some(after_id) = after_id {
+            filter.insert("_id", doc! {"$gt": after_id});
+        }
+        let mut cursor = collection.find(filter, options).await?;
+
+        let mut result = vec![];
+
+        while cursor.advance().await? {
+            result.push(cursor.deserialize_current()?);
+        }
+
+        Ok(result)
+    }
+
+    /// Returns false when the basket changed since it was read, e.g. a purchase claimed it.
+    pub async fn mark_abandoned(&self, basket: &Basket, now: DateTime<Utc>) -> anyhow::Result<bool> {
+        let collection = self.get_collection();
+        let update_result = collection.update_one(
+            doc! {"_id": &basket.id, "concurrency_stamp": concurrency_stamp_filter(&basket.concurrency_stamp), "order_id": Bson::Null},
+            doc! {"$set": {
+                "status": constants::BASKET_STATUS_ABANDONED,
+                "abandoned_at": now,
+                "concurrency_stamp": ObjectId::new().to_hex()
+            }},
+            None
+        ).await?;
+
+        Ok(update_result.modified_count == 1)
+    }
+
+    /// Lets mongodb delete abandoned baskets once the retention period is over. An index left by a
+    /// different retention is changed in place, creating it again would fail.
+    pub async fn ensure_abandoned_basket_retention(&self, retention: Duration) -> anyhow::Result<()> {
+        let collection = self.get_collection();
+        let index = IndexModel::builder()
+            .keys(doc! {"abandoned_at": 1})
+            .options(IndexOptions::builder().expire_after(retention).build())
+            .build();
+        match collection.create_index(index, None).await {
+            Ok(_) => Ok(()),
+            Err(err) => match *err.kind {
+                ErrorKind::Command(ref command_error) if command_error.code == INDEX_OPTIONS_CONFLICT_CODE => {
+                    self.client.database(&self.database).run_command(doc! {
+                        "collMod": &self.collection,
+                        "index": {
+                            "keyPattern": {"abandoned_at": 1},
+                            "expireAfterSeconds": retention.as_secs() as i64
+                        }
+                    }, None).await?;
+                    Ok(())
+                }
+                _ => Err(err.into()),
+            },
+        }
+    }
+}
+
+
+/// Baskets stored before they had a concurrency stamp are read with an empty one, `null` also matches the missing field.
+fn concurrency_stamp_filter(concurrency_stamp: &str) -> Bson {
+    if concurrency_stamp.is_empty() {
+        Bson::Null
+    } else {
+        Bson::String(concurrency_stamp.to_string())
+    }
+}
+
+pub struct PaymentRepository {
+    client: Client,
+    database: String,
+    collection: String
+}
+
+impl PaymentRepository {
+
+    pub fn new(client: Client, database: String, collection: String) -> Self {
+        PaymentRepository {
+            client,
+            database,
+            collection
+        }
+    }
+
+    /// Authorized payments count as paid, their money is held until staff capture or void it.
+    pub async fn get_paid_basket_ids(&self, basket_ids: Vec<ObjectId>) -> anyhow::Result<Vec<ObjectId>> {
+        let collection: Collection<Payment> = self.client.database(&self.database).collection(&self.collection);
+
+        let mut cursor = collection.find(doc! {
+            "basket_id": { "$in": basket_ids },
+            "status": { "$in": [constants::PAYMENT_STATUS_PAID, constants::PAYMENT_STATUS_AUTHORIZED] }
+        }, None).await?;
+
+        let mut result = vec![];
+
+        while cursor.advance().await? {
+            let payment: Payment = cursor.deserialize_current()?;
+            if let Some(basket_id) = payment.basket_id {
+                result.push(basket_id);
+            }
+        }
+
+        Ok(result)
+    }
+}
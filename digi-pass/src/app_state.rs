@@ -1,10 +1,14 @@
-use crate::{baskets::application::BasketService, events::application::EventService, inventories::application::InventoryService, orders::application::OrderService, passes::application::PassService, payments::application::PaymentService};
+use crate::{baskets::application::BasketService, bundles::application::BundleService, discounts::application::DiscountService, events::application::EventService, inventories::application::InventoryService, orders::application::OrderService, passes::application::PassService, payments::{application::PaymentService, payment_providers::fake_provider::FakeCheckouts}};
 
 pub struct AppState {
     pub event_service: EventService,
     pub inventory_service: InventoryService,
     pub basket_service: BasketService,
+    pub discount_service: DiscountService,
+    pub bundle_service: BundleService,
     pub payment_service: PaymentService,
     pub order_service: OrderService,
     pub pass_service: PassService,
+    /// Only set when the fake payment provider is configured.
+    pub fake_checkouts: Option<FakeCheckouts>,
 }
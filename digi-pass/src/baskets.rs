@@ -1,6 +1,8 @@
+mod constants;
 mod data_models;
 mod basket_repository;
 mod errors;
+mod pricing;
 pub mod data_transfer_objects;
 pub mod basket_controller;
 pub mod application;
\ No newline at end of file
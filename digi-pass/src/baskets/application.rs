@@ -1,30 +1,47 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
-use chrono::Utc;
+use bson::oid::ObjectId;
+use chrono::{DateTime, Utc};
 use mongodb::Client;
 
-use crate::events::{application::EventService, data_transfer_objects::EventDetails};
+use crate::bundles::application::BundleService;
+use crate::discounts::{
+    application::DiscountService,
+    data_transfer_objects::{AppliedDiscount, DiscountableItem, RedeemDiscount},
+    errors::DiscountErrors,
+};
+use crate::events::{application::EventService, data_transfer_objects::{AttendeeConfig, EventDetails}};
+use crate::money::{Money, DEFAULT_CURRENCY};
 use crate::orders::application::OrderService;
+use crate::payments::data_transfer_objects::PaymentStatus;
 use crate::payments::data_transfer_objects::PaymentView;
 use crate::{
     inventories::{
         application::InventoryService,
         data_transfer_objects::{ReserveInventories, ReservedInventory},
+        errors::InventoryErrors,
     },
     payments::application::PaymentService,
 };
 
+use super::constants::{BASKET_STATUS_ABANDONED, BASKET_STATUS_PURCHASED, BASKET_STATUS_PURCHASING};
 use super::errors::BasketErrors;
+use super::pricing;
 use super::{
     basket_repository::{BasketRepository, MongoDbBasketRepository},
-    data_models::{self, Basket, BasketItem, BasketedInventory},
-    data_transfer_objects::{self, AddBasketItemRequest, CreateBasketRequest, CreateBasketResult},
+    data_models::{self, Attendee, AttendeeAnswer, Basket, BasketDiscount, BasketItem, BasketItemBundle, BasketedInventory},
+    data_transfer_objects::{
+        self, AddBasketItemRequest, AddBundleRequest, AttendeeRequest, BasketDetails, BasketState, CreateBasketRequest,
+        CreateBasketResult, UpdateAttendeesRequest, UpdateBasketResult,
+    },
 };
 
 #[derive(Clone)]
 pub struct BasketService {
     inventory_service: InventoryService,
     event_service: EventService,
+    discount_service: DiscountService,
+    bundle_service: BundleService,
     basket_repository: Arc<dyn BasketRepository>,
 }
 
@@ -34,6 +51,8 @@ impl BasketService {
         database: String,
         inventory_service: InventoryService,
         event_service: EventService,
+        discount_service: DiscountService,
+        bundle_service: BundleService,
     ) -> Self {
         let basket_repository = Arc::new(MongoDbBasketRepository::new(
             client.clone(),
@@ -44,61 +63,217 @@ impl BasketService {
             inventory_service,
             basket_repository,
             event_service,
+            discount_service,
+            bundle_service,
         }
     }
 
     pub async fn create_basket(
         &self,
         create_basket_request: CreateBasketRequest,
+        customer_id: &str,
     ) -> anyhow::Result<CreateBasketResult> {
         let inventory_requests =
             generate_reserve_inventory_request(&create_basket_request.add_basket_item_request);
 
         let mut basket_items: Vec<BasketItem> = vec![];
         for inventory_request in inventory_requests {
-            let event = self
-                .event_service
-                .get_event(&inventory_request.event_id)
-                .await?
-                .ok_or(anyhow::anyhow!(
-                    "Event not found: {:?}",
-                    inventory_request.event_id.clone()
-                ))?;
-
-            let result = self
-                .inventory_service
-                .reserve_inventories(&inventory_request)
-                .await?;
-            if result.reserved_inventories.len() != inventory_request.quantity as usize {
-                return Err(anyhow::anyhow!(
-                    "Not enough inventories: {:?}",
-                    result.reserved_inventories.len()
-                ));
+            let currency = basket_items.first().map(|basket_item| basket_item.price.currency.clone());
+            match self.reserve_basket_items(&inventory_request, currency.as_deref()).await {
+                Ok(mut reserved_basket_items) => basket_items.append(&mut reserved_basket_items),
+                Err(err) => {
+                    self.release_reserved_inventories(&get_reserved_inventories(&basket_items)).await;
+                    return Err(err);
+                }
             }
-            let basketed_inventories: Vec<BasketedInventory> = result
-                    .reserved_inventories
-                    .iter()
-                    .map(|ri| create_basketed_inventory(&event, ri))
-                    .collect();
-
-            for basketed_inventory in basketed_inventories {
-                let basket_item = BasketItem {
-                    price: event.price ,
-                    basketed_inventories: vec![basketed_inventory],
-                };
-    
-                basket_items.push(basket_item);
+        }
+        for add_bundle_request in create_basket_request.add_bundle_request.iter() {
+            let currency = basket_items.first().map(|basket_item| basket_item.price.currency.clone());
+            match self.reserve_bundle_items(add_bundle_request, currency.as_deref()).await {
+                Ok(mut reserved_basket_items) => basket_items.append(&mut reserved_basket_items),
+                Err(err) => {
+                    self.release_reserved_inventories(&get_reserved_inventories(&basket_items)).await;
+                    return Err(err);
+                }
             }
-            
         }
 
         //create and save basket
+        let reserved_inventories = get_reserved_inventories(&basket_items);
         let valid_until = Utc::now() + chrono::Duration::minutes(30);
-        let basket = Basket::new(valid_until, basket_items);
-        let basket_id = self.basket_repository.add(basket).await?;
+        let currency = basket_items
+            .first()
+            .map(|basket_item| basket_item.price.currency.clone())
+            .unwrap_or(DEFAULT_CURRENCY.to_string());
+        let mut basket = Basket::new(customer_id.to_string(), currency, valid_until, basket_items);
+        pricing::reprice_basket(&mut basket);
+        let basket_id = match self.basket_repository.add(basket).await {
+            Ok(Some(basket_id)) => basket_id,
+            Ok(None) => {
+                self.release_reserved_inventories(&reserved_inventories).await;
+                return Err(anyhow::anyhow!("Failed creating basket"));
+            }
+            Err(err) => {
+                self.release_reserved_inventories(&reserved_inventories).await;
+                return Err(err);
+            }
+        };
+
+        Ok(CreateBasketResult { basket_id })
+    }
+
+    pub async fn add_basket_item(
+        &self,
+        payment_service: &PaymentService,
+        basket_id: &str,
+        customer_id: &str,
+        add_basket_item_request: AddBasketItemRequest,
+    ) -> anyhow::Result<UpdateBasketResult> {
+        let basket = self.get_modifiable_basket(payment_service, basket_id, customer_id).await?;
+
+        let inventory_request = ReserveInventories {
+            event_id: add_basket_item_request.event_id,
+            quantity: add_basket_item_request.quantity,
+        };
+        let basket_items = self
+            .reserve_basket_items(&inventory_request, get_accepted_currency(&basket))
+            .await?;
+
+        self.add_reserved_basket_items(basket_id, basket, basket_items).await
+    }
+
+    pub async fn add_bundle(
+        &self,
+        payment_service: &PaymentService,
+        basket_id: &str,
+        customer_id: &str,
+        add_bundle_request: AddBundleRequest,
+    ) -> anyhow::Result<UpdateBasketResult> {
+        let basket = self.get_modifiable_basket(payment_service, basket_id, customer_id).await?;
+
+        let basket_items = self
+            .reserve_bundle_items(&add_bundle_request, get_accepted_currency(&basket))
+            .await?;
+
+        self.add_reserved_basket_items(basket_id, basket, basket_items).await
+    }
+
+    /// Saves freshly reserved items into the basket, releasing them again if that fails.
+    async fn add_reserved_basket_items(
+        &self,
+        basket_id: &str,
+        mut basket: Basket,
+        mut basket_items: Vec<BasketItem>,
+    ) -> anyhow::Result<UpdateBasketResult> {
+        let reserved_inventories = get_reserved_inventories(&basket_items);
+        if let Some(basket_item) = basket_items.first() {
+            basket.currency = basket_item.price.currency.clone();
+        }
+        basket.basket_items.append(&mut basket_items);
+
+        let update_result = match self.refresh_basket_discount(&mut basket).await {
+            Ok(_) => {
+                pricing::reprice_basket(&mut basket);
+                self.basket_repository.update(&mut basket).await
+            }
+            Err(err) => Err(err),
+        };
+        if let Err(err) = update_result {
+            self.release_reserved_inventories(&reserved_inventories).await;
+            return Err(err);
+        }
+
+        Ok(UpdateBasketResult {
+            basket_id: basket_id.to_string(),
+            price: compute_basket_total_price(&basket),
+        })
+    }
+
+    pub async fn remove_basket_item(
+        &self,
+        payment_service: &PaymentService,
+        basket_id: &str,
+        customer_id: &str,
+        basket_item_id: &str,
+    ) -> anyhow::Result<UpdateBasketResult> {
+        let mut basket = self.get_modifiable_basket(payment_service, basket_id, customer_id).await?;
+
+        let index = basket
+            .basket_items
+            .iter()
+            .position(|basket_item| basket_item.id == basket_item_id)
+            .ok_or(BasketErrors::BasketItemNotFound)?;
+        let removed_basket_item = basket.basket_items.remove(index);
+
+        self.refresh_basket_discount(&mut basket).await?;
+        pricing::reprice_basket(&mut basket);
+        self.basket_repository.update(&mut basket).await?;
+        self.release_reserved_inventories(&get_reserved_inventories(&[removed_basket_item]))
+            .await;
+
+        Ok(UpdateBasketResult {
+            basket_id: basket_id.to_string(),
+            price: compute_basket_total_price(&basket),
+        })
+    }
+
+    pub async fn apply_discount(
+        &self,
+        payment_service: &PaymentService,
+        basket_id: &str,
+        customer_id: &str,
+        code: &str,
+    ) -> anyhow::Result<UpdateBasketResult> {
+        let mut basket = self.get_modifiable_basket(payment_service, basket_id, customer_id).await?;
+
+        let applied_discount = self
+            .discount_service
+            .apply_discount(code, customer_id, &get_discountable_items(&basket))
+            .await
+            .map_err(map_discount_error)?;
+        set_basket_discount(&mut basket, applied_discount);
+        pricing::reprice_basket(&mut basket);
+
+        self.basket_repository.update(&mut basket).await?;
+
+        Ok(UpdateBasketResult {
+            basket_id: basket_id.to_string(),
+            price: compute_basket_total_price(&basket),
+        })
+    }
+
+    /// Sets who each ticket is for. Details are checked against the event's attendee config as they come in.
+    pub async fn update_attendees(
+        &self,
+        payment_service: &PaymentService,
+        basket_id: &str,
+        customer_id: &str,
+        update_attendees_request: UpdateAttendeesRequest,
+    ) -> anyhow::Result<UpdateBasketResult> {
+        let mut basket = self.get_modifiable_basket(payment_service, basket_id, customer_id).await?;
+        let attendee_configs = self.get_attendee_configs(&basket).await?;
+
+        for attendee_request in update_attendees_request.attendees {
+            let basketed_inventory = basket
+                .basket_items
+                .iter_mut()
+                .flat_map(|basket_item| basket_item.basketed_inventories.iter_mut())
+                .find(|basketed_inventory| basketed_inventory.inventory_id == attendee_request.inventory_id)
+                .ok_or(BasketErrors::BasketItemNotFound)?;
+            let attendee_config = attendee_configs
+                .get(&basketed_inventory.event_id)
+                .ok_or(anyhow::anyhow!("Event not found: {:?}", basketed_inventory.event_id))?;
+
+            let attendee = map_attendee_request(attendee_request);
+            validate_attendee(attendee_config, Some(&attendee)).map_err(BasketErrors::InvalidAttendee)?;
+            basketed_inventory.attendee = Some(attendee);
+        }
 
-        Ok(CreateBasketResult {
-            basket_id: basket_id.ok_or(anyhow::anyhow!("Failed creating basket"))?,
+        self.basket_repository.update(&mut basket).await?;
+
+        Ok(UpdateBasketResult {
+            basket_id: basket_id.to_string(),
+            price: compute_basket_total_price(&basket),
         })
     }
 
@@ -111,7 +286,7 @@ impl BasketService {
         match basket {
             None => Ok(None),
             Some(basket) => {
-                if !is_all_inventory_reserved(&basket) {
+                if basket.order_id.is_some() || !is_all_inventory_reserved(&basket) {
                     return Ok(None);
                 }
                 if is_basket_expired(&basket) {
@@ -122,71 +297,672 @@ impl BasketService {
         }
     }
 
+    /// Returns false when the basket changed since it was read with `concurrency_stamp`.
+    pub async fn claim_for_checkout(&self, basket_id: &str, concurrency_stamp: &str) -> anyhow::Result<bool> {
+        self.basket_repository.claim_for_checkout(basket_id, concurrency_stamp).await
+    }
+
+    pub async fn get_basket_details(
+        &self,
+        payment_service: &PaymentService,
+        order_service: &OrderService,
+        basket_id: &str,
+        customer_id: &str,
+    ) -> anyhow::Result<Option<BasketDetails>> {
+        let basket = match self.basket_repository.get(basket_id).await? {
+            Some(basket) => basket,
+            None => return Ok(None),
+        };
+        check_basket_owner(&basket, customer_id)?;
+
+        let basket_payments = payment_service.get_basket_payments(basket_id).await?;
+        let order_id = match get_purchased_order_id(&basket) {
+            Some(order_id) => Some(order_id.to_string()),
+            None => order_service.get_order_id_by_basket_id(basket_id).await?,
+        };
+        let state = derive_basket_state(&basket, &basket_payments, &order_id);
+        let basket_dto = map_dto_basket_from_data_basket(&basket, basket_payments)?;
+
+        Ok(Some(BasketDetails {
+            id: basket_dto.id,
+            state,
+            valid_until: basket_dto.valid_until,
+            reserved_until: get_earliest_reserved_until(&basket),
+            basket_items: basket_dto.basket_items,
+            payments: basket_dto.payments,
+            discount: basket_dto.discount,
+            price_lines: basket_dto.price_lines,
+            price: basket_dto.price,
+            order_id,
+        }))
+    }
+
     pub async fn purchase_basket(
         &self,
         payment_service: &PaymentService,
         order_service: &OrderService,
         basket_id: &str,
+        customer_id: &str,
     ) -> anyhow::Result<String> {
         let basket = self.basket_repository.get(basket_id).await?;
 
         let basket = basket.ok_or(BasketErrors::BasketNotFound)?;
+        check_basket_owner(&basket, customer_id)?;
+        self.purchase(payment_service, order_service, basket).await
+    }
+
+    /// Purchases the basket on behalf of its owner once a payment is confirmed by the provider.
+    /// Returns `None` while the paid payments do not yet cover the basket.
+    pub async fn purchase_paid_basket(
+        &self,
+        payment_service: &PaymentService,
+        order_service: &OrderService,
+        basket_id: &str,
+    ) -> anyhow::Result<Option<String>> {
+        let basket = self
+            .basket_repository
+            .get(basket_id)
+            .await?
+            .ok_or(BasketErrors::BasketNotFound)?;
+
+        match self.purchase(payment_service, order_service, basket).await {
+            Ok(order_id) => Ok(Some(order_id)),
+            Err(err) if matches!(err.downcast_ref::<BasketErrors>(), Some(BasketErrors::BasketUnpaid)) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn purchase(
+        &self,
+        payment_service: &PaymentService,
+        order_service: &OrderService,
+        basket: Basket,
+    ) -> anyhow::Result<String> {
+        let basket_id = &basket.id.ok_or(anyhow::anyhow!("No basket id!"))?.to_hex();
+        if let Some(order_id) = get_purchased_order_id(&basket) {
+            return Ok(order_id.to_string());
+        }
+        if let (BASKET_STATUS_PURCHASING, Some(order_id)) = (basket.status.as_str(), &basket.order_id) {
+            return self
+                .resume_purchase(payment_service, order_service, &basket, order_id)
+                .await;
+        }
         if !is_all_inventory_reserved(&basket) {
             return Err(BasketErrors::BasketExpired.into());
         }
         if is_basket_expired(&basket) {
             return Err(BasketErrors::BasketExpired.into());
         }
+        let attendee_configs = self.get_attendee_configs(&basket).await?;
+        check_attendees_complete(&basket, &attendee_configs)?;
 
         let basket_payments = payment_service.get_basket_payments(basket_id).await?;
 
-        let paid_payments: i32 = basket_payments
+        let paid_payments = sum_paid_payments(&basket_payments, &basket.currency);
+        if paid_payments < compute_basket_total_price(&basket).amount {
+            return Err(BasketErrors::BasketUnpaid.into());
+        }
+        let basket_dto = map_dto_basket_from_data_basket(&basket, basket_payments)?;
+
+        let order_id = basket_dto
+            .original_order_id
+            .clone()
+            .unwrap_or(ObjectId::new().to_hex());
+        if !self.basket_repository.claim_for_purchase(basket_id, &order_id).await? {
+            // Another purchase of the same basket got here first.
+            let basket = self
+                .basket_repository
+                .get(basket_id)
+                .await?
+                .ok_or(BasketErrors::BasketNotFound)?;
+            return basket.order_id.ok_or(BasketErrors::BasketLocked.into());
+        }
+
+        self.complete_purchase(order_service, &basket, basket_dto, &order_id).await
+    }
+
+    /// Finishes a purchase whose claim was left behind, e.g. by a crash after the basket was claimed.
+    async fn resume_purchase(
+        &self,
+        payment_service: &PaymentService,
+        order_service: &OrderService,
+        basket: &Basket,
+        order_id: &str,
+    ) -> anyhow::Result<String> {
+        let basket_id = basket.id.ok_or(anyhow::anyhow!("No basket id!"))?.to_hex();
+        if order_service.get_order_id_by_basket_id(&basket_id).await?.is_some() {
+            self.basket_repository.mark_purchased(&basket_id, order_id).await?;
+            return Ok(order_id.to_string());
+        }
+
+        let basket_payments = payment_service.get_basket_payments(&basket_id).await?;
+        let basket_dto = map_dto_basket_from_data_basket(basket, basket_payments)?;
+        self.complete_purchase(order_service, basket, basket_dto, order_id).await
+    }
+
+    /// Redeems the basket's discount and creates the order for a claimed basket.
+    /// Both are undone if either fails so the purchase can be retried.
+    async fn complete_purchase(
+        &self,
+        order_service: &OrderService,
+        basket: &Basket,
+        basket_dto: data_transfer_objects::Basket,
+        order_id: &str,
+    ) -> anyhow::Result<String> {
+        let basket_id = basket_dto.id.clone();
+        if let Some(discount) = basket.discount.clone() {
+            let redeem_discount = RedeemDiscount {
+                discount_id: discount.discount_id,
+                customer_id: basket.owner_id.clone(),
+                basket_id: basket_id.clone(),
+                order_id: order_id.to_string(),
+                amount: discount.amount,
+            };
+            if let Err(err) = self.discount_service.redeem_discount(redeem_discount).await {
+                self.release_purchase_claim(&basket_id, order_id).await;
+                return Err(map_discount_error(err));
+            }
+        }
+
+        if let Err(err) = order_service.create_order(basket_dto, order_id).await {
+            if let Err(release_err) = self.discount_service.release_redemption(order_id).await {
+                tracing::error!("Failed releasing discount redemption of order {}: {:?}", order_id, release_err);
+            }
+            self.release_purchase_claim(&basket_id, order_id).await;
+            return Err(err);
+        }
+        self.basket_repository.mark_purchased(&basket_id, order_id).await?;
+
+        Ok(order_id.to_string())
+    }
+
+    async fn release_purchase_claim(&self, basket_id: &str, order_id: &str) {
+        if let Err(err) = self.basket_repository.release_purchase_claim(basket_id, order_id).await {
+            tracing::error!("Failed releasing purchase claim on basket {}: {:?}", basket_id, err);
+        }
+    }
+
+    async fn reserve_basket_items(
+        &self,
+        inventory_request: &ReserveInventories,
+        currency: Option<&str>,
+    ) -> anyhow::Result<Vec<BasketItem>> {
+        let (event, reserved_inventories) = self
+            .reserve_event_inventories(inventory_request, currency)
+            .await?;
+
+        let basket_items = reserved_inventories
             .iter()
-            .filter_map(|p| {
-                if p.status == "paid" {
-                    return Some(p.amount);
-                } else {
-                    return None;
+            .map(|ri| {
+                BasketItem::new(
+                    event.price.clone(),
+                    event.convenience_fee.clone(),
+                    event.tax_rate,
+                    vec![create_basketed_inventory(&event, ri)],
+                )
+            })
+            .collect();
+        Ok(basket_items)
+    }
+
+    /// Every unit of a bundle is a single item holding one inventory of each of the bundle's events.
+    async fn reserve_bundle_items(
+        &self,
+        add_bundle_request: &AddBundleRequest,
+        currency: Option<&str>,
+    ) -> anyhow::Result<Vec<BasketItem>> {
+        let bundle = self
+            .bundle_service
+            .get_bundle(&add_bundle_request.bundle_id)
+            .await?
+            .ok_or(BasketErrors::BundleNotFound)?;
+        check_currency(&bundle.price.currency, currency)?;
+
+        let mut reserved_events: Vec<(EventDetails, Vec<ReservedInventory>)> = vec![];
+        for event_id in bundle.event_ids.iter() {
+            let inventory_request = ReserveInventories {
+                event_id: event_id.clone(),
+                quantity: add_bundle_request.quantity,
+            };
+            match self
+                .reserve_event_inventories(&inventory_request, Some(&bundle.price.currency))
+                .await
+            {
+                Ok(reserved_event) => reserved_events.push(reserved_event),
+                Err(err) => {
+                    let reserved_inventories: Vec<ReservedInventory> = reserved_events
+                        .into_iter()
+                        .flat_map(|(_, reserved_inventories)| reserved_inventories)
+                        .collect();
+                    self.release_reserved_inventories(&reserved_inventories).await;
+                    return Err(err);
                 }
+            }
+        }
+
+        let basket_item_bundle = BasketItemBundle {
+            bundle_id: bundle.id.clone(),
+            name: bundle.name.clone(),
+        };
+        let basket_items = (0..add_bundle_request.quantity as usize)
+            .map(|index| {
+                let basketed_inventories = reserved_events
+                    .iter()
+                    .map(|(event, reserved_inventories)| create_basketed_inventory(event, &reserved_inventories[index]))
+                    .collect();
+                BasketItem::new(
+                    bundle.price.clone(),
+                    bundle.convenience_fee.clone(),
+                    bundle.tax_rate,
+                    basketed_inventories,
+                )
+                .with_bundle(basket_item_bundle.clone())
             })
-            .sum();
-        if paid_payments < compute_basket_total_price(&basket) {
-            return Err(BasketErrors::BasketUnpaid.into());
+            .collect();
+        Ok(basket_items)
+    }
+
+    /// Reserves exactly the requested quantity of the event or nothing at all.
+    async fn reserve_event_inventories(
+        &self,
+        inventory_request: &ReserveInventories,
+        currency: Option<&str>,
+    ) -> anyhow::Result<(EventDetails, Vec<ReservedInventory>)> {
+        let event = self
+            .event_service
+            .get_event(&inventory_request.event_id)
+            .await?
+            .ok_or(anyhow::anyhow!(
+                "Event not found: {:?}",
+                inventory_request.event_id.clone()
+            ))?;
+        check_currency(&event.price.currency, currency)?;
+
+        let result = self
+            .inventory_service
+            .reserve_inventories(inventory_request)
+            .await
+            .map_err(map_inventory_error)?;
+        if result.reserved_inventories.len() != inventory_request.quantity as usize {
+            self.release_reserved_inventories(&result.reserved_inventories).await;
+            return Err(BasketErrors::NotEnoughInventories {
+                event_id: inventory_request.event_id.clone(),
+                requested: inventory_request.quantity,
+                available: result.reserved_inventories.len() as i32,
+            }
+            .into());
         }
-        let basket_dto = map_dto_basket_from_data_basket(&basket, basket_payments)?;
-        
-        let order_id = order_service.create_order(basket_dto).await?;
 
-        return Ok(order_id);
+        Ok((event, result.reserved_inventories))
+    }
+
+    /// Attendee config of every event in the basket, keyed by event id.
+    async fn get_attendee_configs(&self, basket: &Basket) -> anyhow::Result<HashMap<String, AttendeeConfig>> {
+        let mut attendee_configs = HashMap::new();
+        let basketed_inventories = basket
+            .basket_items
+            .iter()
+            .flat_map(|basket_item| basket_item.basketed_inventories.iter());
+        for basketed_inventory in basketed_inventories {
+            if attendee_configs.contains_key(&basketed_inventory.event_id) {
+                continue;
+            }
+            let event = self
+                .event_service
+                .get_event(&basketed_inventory.event_id)
+                .await?
+                .ok_or(anyhow::anyhow!("Event not found: {:?}", basketed_inventory.event_id))?;
+            attendee_configs.insert(basketed_inventory.event_id.clone(), event.attendee_config);
+        }
+        Ok(attendee_configs)
+    }
+
+    /// Keeps the basket and its inventories reserved until `held_until` while its payment is authorized but not captured.
+    pub async fn hold_basket(&self, basket_id: &str, held_until: DateTime<Utc>) -> anyhow::Result<()> {
+        let mut basket = self
+            .basket_repository
+            .get(basket_id)
+            .await?
+            .ok_or(BasketErrors::BasketNotFound)?;
+        let reserved_inventories = get_reserved_inventories(&basket.basket_items);
+        let extended = self
+            .inventory_service
+            .extend_reservations(&reserved_inventories, held_until)
+            .await?;
+        if extended.len() < reserved_inventories.len() {
+            tracing::warn!("Only {} of {} reservations of basket {} could be held", extended.len(), reserved_inventories.len(), basket_id);
+        }
+
+        for basketed_inventory in basket.basket_items.iter_mut().flat_map(|item| item.basketed_inventories.iter_mut()) {
+            if extended.iter().any(|reserved_inventory| reserved_inventory.inventory_id == basketed_inventory.inventory_id) {
+                basketed_inventory.reserved_until = held_until;
+            }
+        }
+        basket.valid_until = basket.valid_until.max(held_until);
+        self.basket_repository.update(&mut basket).await
+    }
+
+    /// Gives the inventories of a held basket back once its authorization is voided, the basket expires right away.
+    pub async fn release_basket(&self, basket_id: &str) -> anyhow::Result<()> {
+        let mut basket = self
+            .basket_repository
+            .get(basket_id)
+            .await?
+            .ok_or(BasketErrors::BasketNotFound)?;
+        if basket.order_id.is_some() {
+            return Err(BasketErrors::BasketLocked.into());
+        }
+
+        self.inventory_service
+            .release_inventories(&get_reserved_inventories(&basket.basket_items))
+            .await?;
+        basket.valid_until = Utc::now();
+        self.basket_repository.update(&mut basket).await
+    }
+
+    /// Releases reservations no basket points at anymore.
+    /// Failures are only logged since the reservations will still lapse on their own.
+    async fn release_reserved_inventories(&self, reserved_inventories: &[ReservedInventory]) {
+        if let Err(err) = self
+            .inventory_service
+            .release_inventories(reserved_inventories)
+            .await
+        {
+            tracing::error!("Failed releasing reserved inventories: {:?}", err);
+        }
+    }
+
+    /// Recomputes the basket's discount after its items changed.
+    /// A code that no longer applies is removed instead of failing the change.
+    async fn refresh_basket_discount(&self, basket: &mut Basket) -> anyhow::Result<()> {
+        let discount = match basket.discount.clone() {
+            Some(discount) => discount,
+            None => return Ok(()),
+        };
+
+        let result = self
+            .discount_service
+            .apply_discount(&discount.code, &basket.owner_id, &get_discountable_items(basket))
+            .await;
+        match result {
+            Ok(applied_discount) => set_basket_discount(basket, applied_discount),
+            Err(err) => match err.downcast::<DiscountErrors>() {
+                Ok(DiscountErrors::Unknown(err)) => return Err(err),
+                Ok(err) => {
+                    tracing::info!("Removing discount {} from basket: {}", discount.code, err);
+                    clear_basket_discount(basket);
+                }
+                Err(err) => return Err(err),
+            },
+        }
+        Ok(())
+    }
+
+    async fn get_modifiable_basket(
+        &self,
+        payment_service: &PaymentService,
+        basket_id: &str,
+        customer_id: &str,
+    ) -> anyhow::Result<Basket> {
+        let basket = self
+            .basket_repository
+            .get(basket_id)
+            .await?
+            .ok_or(BasketErrors::BasketNotFound)?;
+        check_basket_owner(&basket, customer_id)?;
+        if basket.order_id.is_some() {
+            return Err(BasketErrors::BasketLocked.into());
+        }
+        if !is_all_inventory_reserved(&basket) || is_basket_expired(&basket) {
+            return Err(BasketErrors::BasketExpired.into());
+        }
+
+        let basket_payments = payment_service.get_basket_payments(basket_id).await?;
+        if basket_payments.iter().any(is_payment_locking_basket) {
+            return Err(BasketErrors::BasketLocked.into());
+        }
+        Ok(basket)
     }
 }
 
-fn is_basket_expired(basket: &Basket) -> bool {
-    return basket.valid_until < Utc::now();
+fn get_purchased_order_id(basket: &Basket) -> Option<&str> {
+    match basket.status.as_str() {
+        BASKET_STATUS_PURCHASED => basket.order_id.as_deref(),
+        _ => None,
+    }
+}
+
+fn derive_basket_state(
+    basket: &Basket,
+    payments: &[PaymentView],
+    order_id: &Option<String>,
+) -> BasketState {
+    if order_id.is_some() {
+        return BasketState::Purchased;
+    }
+
+    // Free baskets are paid by a payment of zero.
+    let has_paid_payment = payments.iter().any(|p| p.status == PaymentStatus::Paid);
+    let paid_payments = sum_paid_payments(payments, &basket.currency);
+    if has_paid_payment && paid_payments >= compute_basket_total_price(basket).amount {
+        return BasketState::Paid;
+    }
+
+    if !is_all_inventory_reserved(basket) || is_basket_expired(basket) {
+        return BasketState::Expired;
+    }
+
+    if payments.iter().any(|p| matches!(p.status, PaymentStatus::Pending | PaymentStatus::ReviewRequired | PaymentStatus::Authorized)) {
+        return BasketState::AwaitingPayment;
+    }
+    BasketState::Active
+}
+
+/// Payments in another currency never count towards the basket.
+fn sum_paid_payments(payments: &[PaymentView], currency: &str) -> i64 {
+    payments
+        .iter()
+        .filter(|p| p.status == PaymentStatus::Paid && p.amount.currency == currency)
+        .map(|p| p.amount.amount)
+        .sum()
+}
+
+/// An emptied basket can take items in any currency.
+fn get_accepted_currency(basket: &Basket) -> Option<&str> {
+    match basket.basket_items.is_empty() {
+        true => None,
+        false => Some(basket.currency.as_str()),
+    }
+}
+
+fn check_currency(currency: &str, accepted_currency: Option<&str>) -> anyhow::Result<()> {
+    match accepted_currency {
+        Some(accepted_currency) if currency != accepted_currency => Err(BasketErrors::CurrencyMismatch {
+            expected: accepted_currency.to_string(),
+            actual: currency.to_string(),
+        }
+        .into()),
+        _ => Ok(()),
+    }
+}
+
+fn map_attendee_request(attendee_request: AttendeeRequest) -> Attendee {
+    Attendee {
+        name: attendee_request.name,
+        email: attendee_request.email,
+        answers: attendee_request
+            .answers
+            .into_iter()
+            .map(|answer| AttendeeAnswer {
+                question_id: answer.question_id,
+                answer: answer.answer,
+            })
+            .collect(),
+    }
+}
+
+/// Returns what is wrong with the attendee, a missing attendee is treated as one with no details.
+fn validate_attendee(attendee_config: &AttendeeConfig, attendee: Option<&Attendee>) -> Result<(), String> {
+    if attendee_config.require_name && attendee.and_then(|a| a.name.as_ref()).is_none() {
+        return Err("Attendee name is required.".to_string());
+    }
+    if attendee_config.require_email && attendee.and_then(|a| a.email.as_ref()).is_none() {
+        return Err("Attendee email is required.".to_string());
+    }
+
+    let answers = attendee.map(|a| a.answers.as_slice()).unwrap_or_default();
+    for answer in answers {
+        if !attendee_config.questions.iter().any(|q| q.id == answer.question_id) {
+            return Err(format!("Unknown attendee question {}.", answer.question_id));
+        }
+    }
+    for question in attendee_config.questions.iter().filter(|q| q.required) {
+        let answered = answers
+            .iter()
+            .any(|a| a.question_id == question.id && !a.answer.trim().is_empty());
+        if !answered {
+            return Err(format!("{} is required.", question.label));
+        }
+    }
+    Ok(())
+}
+
+fn check_attendees_complete(basket: &Basket, attendee_configs: &HashMap<String, AttendeeConfig>) -> anyhow::Result<()> {
+    let basketed_inventories = basket
+        .basket_items
+        .iter()
+        .flat_map(|basket_item| basket_item.basketed_inventories.iter());
+    for basketed_inventory in basketed_inventories {
+        let attendee_config = match attendee_configs.get(&basketed_inventory.event_id) {
+            Some(attendee_config) => attendee_config,
+            None => continue,
+        };
+        validate_attendee(attendee_config, basketed_inventory.attendee.as_ref()).map_err(|message| {
+            BasketErrors::InvalidAttendee(format!("Ticket {}: {}", basketed_inventory.inventory_id, message))
+        })?;
+    }
+    Ok(())
 }
 
-fn compute_basket_total_price(basket: &Basket) -> i32 {
-    let total_price = basket
+fn get_earliest_reserved_until(basket: &Basket) -> Option<DateTime<Utc>> {
+    basket
         .basket_items
         .iter()
-        .map(|bi| bi.price)
-        .sum();
+        .flat_map(|basket_item| basket_item.basketed_inventories.iter())
+        .map(|basketed_inventory| basketed_inventory.reserved_until)
+        .min()
+}
 
-    return total_price;
+fn map_inventory_error(err: anyhow::Error) -> anyhow::Error {
+    match err.downcast::<InventoryErrors>() {
+        Ok(InventoryErrors::NotEnoughInventories {
+            event_id,
+            requested,
+            available,
+        }) => BasketErrors::NotEnoughInventories {
+            event_id,
+            requested,
+            available,
+        }
+        .into(),
+        Ok(err) => err.into(),
+        Err(err) => err,
+    }
+}
+
+fn map_discount_error(err: anyhow::Error) -> anyhow::Error {
+    match err.downcast::<DiscountErrors>() {
+        Ok(DiscountErrors::Unknown(err)) => err,
+        Ok(err) => BasketErrors::InvalidDiscount(err.to_string()).into(),
+        Err(err) => err,
+    }
+}
+
+fn get_discountable_items(basket: &Basket) -> Vec<DiscountableItem> {
+    basket
+        .basket_items
+        .iter()
+        .map(|basket_item| DiscountableItem {
+            event_ids: basket_item
+                .basketed_inventories
+                .iter()
+                .map(|basketed_inventory| basketed_inventory.event_id.clone())
+                .collect(),
+            bundle_id: basket_item.bundle.as_ref().map(|bundle| bundle.bundle_id.clone()),
+            price: basket_item.price.clone(),
+        })
+        .collect()
+}
+
+fn set_basket_discount(basket: &mut Basket, applied_discount: AppliedDiscount) {
+    for (basket_item, item_discount) in basket
+        .basket_items
+        .iter_mut()
+        .zip(applied_discount.item_discounts.iter())
+    {
+        basket_item.discount = Money::new(*item_discount, &basket_item.price.currency);
+    }
+    basket.discount = Some(BasketDiscount {
+        discount_id: applied_discount.discount_id,
+        code: applied_discount.code,
+        amount: Money::new(applied_discount.amount, &basket.currency),
+    });
+}
+
+fn clear_basket_discount(basket: &mut Basket) {
+    for basket_item in basket.basket_items.iter_mut() {
+        basket_item.discount = Money::zero(&basket_item.price.currency);
+    }
+    basket.discount = None;
+}
+
+fn is_payment_locking_basket(payment: &PaymentView) -> bool {
+    matches!(payment.status, PaymentStatus::Pending | PaymentStatus::Paid | PaymentStatus::ReviewRequired | PaymentStatus::Authorized)
+}
+
+fn get_reserved_inventories(basket_items: &[BasketItem]) -> Vec<ReservedInventory> {
+    basket_items
+        .iter()
+        .flat_map(|basket_item| basket_item.basketed_inventories.iter())
+        .map(|basketed_inventory| {
+            ReservedInventory::new(
+                basketed_inventory.inventory_id.clone(),
+                basketed_inventory.reserved_until,
+            )
+        })
+        .collect()
+}
+
+/// Baskets stored before they had an owner belong to nobody.
+fn check_basket_owner(basket: &Basket, customer_id: &str) -> anyhow::Result<()> {
+    if basket.owner_id.is_empty() || basket.owner_id != customer_id {
+        return Err(BasketErrors::BasketNotOwned.into());
+    }
+    Ok(())
+}
+
+/// Abandoned baskets were swept by the inventory keeper, their inventories may already be sold to someone else.
+fn is_basket_expired(basket: &Basket) -> bool {
+    return basket.status == BASKET_STATUS_ABANDONED || basket.valid_until < Utc::now();
+}
+
+fn compute_basket_total_price(basket: &Basket) -> Money {
+    pricing::compute_basket_total(basket)
 }
 
 fn map_dto_basket_from_data_basket(
     data_basket: &data_models::Basket,
     payments: Vec<PaymentView>,
 ) -> anyhow::Result<data_transfer_objects::Basket> {
-    let mut price = 0;
+    let mut price = Money::zero(&data_basket.currency);
     let mut basket_items = vec![];
 
     for basket_item in data_basket.basket_items.iter() {
 
-        price += basket_item.price;
+        price = price.checked_add(&pricing::compute_item_total(basket_item))?;
 
         let dto_basket_item = data_transfer_objects::BasketItem {
+            id: basket_item.id.clone(),
             basketed_inventories: basket_item
                 .basketed_inventories
                 .iter()
@@ -196,10 +972,18 @@ fn map_dto_basket_from_data_basket(
                         name: basketed_inventory.name.clone(),
                         inventory_id: basketed_inventory.inventory_id.clone(),
                         reserved_until: basketed_inventory.reserved_until,
+                        attendee: basketed_inventory.attendee.as_ref().map(map_dto_attendee),
                     },
                 )
                 .collect(),
-            price: basket_item.price,
+            bundle: basket_item.bundle.as_ref().map(|bundle| data_transfer_objects::BasketItemBundle {
+                bundle_id: bundle.bundle_id.clone(),
+                name: bundle.name.clone(),
+            }),
+            price: basket_item.price.clone(),
+            discount: basket_item.discount.clone(),
+            fee: basket_item.fee.clone(),
+            tax: basket_item.tax.clone(),
         };
         basket_items.push(dto_basket_item);
     }
@@ -209,22 +993,53 @@ fn map_dto_basket_from_data_basket(
             .id
             .map(|id| id.to_hex())
             .ok_or(anyhow::anyhow!("No basket id!"))?,
+        concurrency_stamp: data_basket.concurrency_stamp.clone(),
+        owner_id: data_basket.owner_id.clone(),
         original_order_id: None,
         valid_until: data_basket.valid_until,
         basket_items,
+        discount: data_basket.discount.as_ref().map(|discount| data_transfer_objects::BasketDiscount {
+            discount_id: discount.discount_id.clone(),
+            code: discount.code.clone(),
+            amount: discount.amount.clone(),
+        }),
+        price_lines: pricing::get_price_lines(data_basket)
+            .into_iter()
+            .map(|price_line| data_transfer_objects::BasketPriceLine {
+                line_type: price_line.line_type,
+                name: price_line.name,
+                amount: price_line.amount,
+            })
+            .collect(),
         price,
         payments: payments.iter().map(payment_view_to_basket_payment).collect(),
     };
     Ok(dto_basket)
 }
 
+fn map_dto_attendee(attendee: &Attendee) -> data_transfer_objects::Attendee {
+    data_transfer_objects::Attendee {
+        name: attendee.name.clone(),
+        email: attendee.email.clone(),
+        answers: attendee
+            .answers
+            .iter()
+            .map(|answer| data_transfer_objects::AttendeeAnswer {
+                question_id: answer.question_id.clone(),
+                answer: answer.answer.clone(),
+            })
+            .collect(),
+    }
+}
+
 fn payment_view_to_basket_payment(payment_view: &PaymentView) -> data_transfer_objects::BasketPayment {
     data_transfer_objects::BasketPayment {
         id: payment_view.id.clone(),
         created_at: payment_view.created_at,
-        status: payment_view.status.clone(),
-        amount: payment_view.amount,
-        currency: payment_view.currency.clone(),
+        status: payment_view.status,
+        failure_reason: payment_view.failure_reason.clone(),
+        paid_amount: payment_view.paid_amount.clone(),
+        amount: payment_view.amount.clone(),
         provider: payment_view.provider.clone(),
         payment_type: payment_view.payment_type.clone(),
     }
@@ -267,6 +1082,7 @@ fn generate_reserve_inventory_request(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::events::data_transfer_objects::AttendeeQuestion;
 
     #[test]
     fn test_generate_reserve_inventory_request() {
@@ -299,4 +1115,187 @@ mod tests {
         assert_eq!(&actuals[1].event_id, &expected[1].event_id);
         assert_eq!(&actuals[1].quantity, &expected[1].quantity);
     }
+
+    #[test]
+    fn test_get_reserved_inventories() {
+        let reserved_until = Utc::now();
+        let basket_items = vec![
+            BasketItem::new(php(100), php(0), 0, vec![BasketedInventory::new("event".to_string(), "Event".to_string(), "inventory_1".to_string(), reserved_until)]),
+            BasketItem::new(php(100), php(0), 0, vec![BasketedInventory::new("event".to_string(), "Event".to_string(), "inventory_2".to_string(), reserved_until)]),
+        ];
+
+        let actuals = get_reserved_inventories(&basket_items);
+
+        assert_eq!(actuals.len(), 2);
+        assert_eq!(actuals[0].inventory_id, "inventory_1".to_string());
+        assert_eq!(actuals[1].inventory_id, "inventory_2".to_string());
+        assert_eq!(actuals[1].reserved_until, reserved_until);
+    }
+
+    fn php(amount: i64) -> Money {
+        Money::new(amount, "PHP")
+    }
+
+    fn create_payment_view(status: PaymentStatus, amount: Money) -> PaymentView {
+        PaymentView {
+            id: "payment".to_string(),
+            concurrency_stamp: "stamp".to_string(),
+            basket_id: "basket".to_string(),
+            amount,
+            provider: "Maya".to_string(),
+            status,
+            failure_reason: None,
+            paid_amount: None,
+            created_at: Utc::now(),
+            payment_type: "checkout".to_string(),
+        }
+    }
+
+    fn create_basket(valid_until: DateTime<Utc>, reserved_until: DateTime<Utc>) -> Basket {
+        Basket::new(
+            "customer".to_string(),
+            "PHP".to_string(),
+            valid_until,
+            vec![BasketItem::new(php(100), php(0), 0, vec![BasketedInventory::new("event".to_string(), "Event".to_string(), "inventory".to_string(), reserved_until)])],
+        )
+    }
+
+    #[test]
+    fn test_derive_basket_state() {
+        let later = Utc::now() + chrono::Duration::minutes(30);
+        let earlier = Utc::now() - chrono::Duration::minutes(30);
+        let basket = create_basket(later, later);
+        let expired_basket = create_basket(earlier, later);
+
+        assert_eq!(derive_basket_state(&basket, &[], &None), BasketState::Active);
+        assert_eq!(derive_basket_state(&basket, &[create_payment_view(PaymentStatus::Pending, php(100))], &None), BasketState::AwaitingPayment);
+        assert_eq!(derive_basket_state(&basket, &[create_payment_view(PaymentStatus::ReviewRequired, php(100))], &None), BasketState::AwaitingPayment);
+        assert_eq!(derive_basket_state(&basket, &[create_payment_view(PaymentStatus::Authorized, php(100))], &None), BasketState::AwaitingPayment);
+        assert_eq!(derive_basket_state(&basket, &[create_payment_view(PaymentStatus::Voided, php(100))], &None), BasketState::Active);
+        assert_eq!(derive_basket_state(&basket, &[create_payment_view(PaymentStatus::Paid, php(100))], &None), BasketState::Paid);
+        assert_eq!(derive_basket_state(&basket, &[create_payment_view(PaymentStatus::Paid, php(50))], &None), BasketState::Active);
+        assert_eq!(derive_basket_state(&basket, &[create_payment_view(PaymentStatus::Paid, Money::new(100, "USD"))], &None), BasketState::Active);
+        assert_eq!(derive_basket_state(&basket, &[], &Some("order".to_string())), BasketState::Purchased);
+        assert_eq!(derive_basket_state(&expired_basket, &[create_payment_view(PaymentStatus::Pending, php(100))], &None), BasketState::Expired);
+
+        let mut free_basket = create_basket(later, later);
+        free_basket.basket_items.clear();
+        assert_eq!(derive_basket_state(&free_basket, &[], &None), BasketState::Active);
+        assert_eq!(derive_basket_state(&free_basket, &[create_payment_view(PaymentStatus::Paid, php(0))], &None), BasketState::Paid);
+    }
+
+    #[test]
+    fn test_get_purchased_order_id() {
+        let later = Utc::now() + chrono::Duration::minutes(30);
+        let mut basket = create_basket(later, later);
+        assert_eq!(get_purchased_order_id(&basket), None);
+
+        basket.status = BASKET_STATUS_PURCHASING.to_string();
+        basket.order_id = Some("order".to_string());
+        assert_eq!(get_purchased_order_id(&basket), None);
+
+        basket.status = BASKET_STATUS_PURCHASED.to_string();
+        assert_eq!(get_purchased_order_id(&basket), Some("order"));
+    }
+
+    #[test]
+    fn test_get_earliest_reserved_until() {
+        let now = Utc::now();
+        let mut basket = create_basket(now, now + chrono::Duration::minutes(90));
+        basket.basket_items.push(BasketItem::new(php(100), php(0), 0, vec![BasketedInventory::new("event".to_string(), "Event".to_string(), "inventory_2".to_string(), now + chrono::Duration::minutes(60))]));
+
+        assert_eq!(get_earliest_reserved_until(&basket), Some(now + chrono::Duration::minutes(60)));
+        assert_eq!(get_earliest_reserved_until(&Basket::new("customer".to_string(), "PHP".to_string(), now, vec![])), None);
+    }
+
+    #[test]
+    fn test_map_inventory_error() {
+        let err: anyhow::Error = InventoryErrors::NotEnoughInventories {
+            event_id: "event".to_string(),
+            requested: 3,
+            available: 1,
+        }
+        .into();
+
+        let actual = map_inventory_error(err);
+
+        assert_eq!(
+            actual.to_string(),
+            "Not enough inventories for event event: requested 3, available 1 (2 short)."
+        );
+        assert!(actual.downcast_ref::<BasketErrors>().is_some());
+    }
+
+    #[test]
+    fn test_set_basket_discount() {
+        let now = Utc::now();
+        let mut basket = create_basket(now, now);
+        basket.basket_items.push(BasketItem::new(php(100), php(0), 0, vec![]));
+        let applied_discount = AppliedDiscount {
+            discount_id: "discount".to_string(),
+            code: "CODE".to_string(),
+            amount: 130,
+            item_discounts: vec![100, 30],
+        };
+
+        set_basket_discount(&mut basket, applied_discount);
+
+        assert_eq!(compute_basket_total_price(&basket), php(70));
+        assert_eq!(basket.discount.as_ref().map(|d| d.amount.clone()), Some(php(130)));
+
+        clear_basket_discount(&mut basket);
+
+        assert_eq!(compute_basket_total_price(&basket), php(200));
+        assert!(basket.discount.is_none());
+    }
+
+    #[test]
+    fn test_check_basket_owner() {
+        let basket = create_basket(Utc::now(), Utc::now());
+
+        assert!(check_basket_owner(&basket, "customer").is_ok());
+        assert!(check_basket_owner(&basket, "someone-else").is_err());
+
+        let unowned_basket = Basket { owner_id: String::new(), ..create_basket(Utc::now(), Utc::now()) };
+        assert!(check_basket_owner(&unowned_basket, "").is_err());
+    }
+
+    #[test]
+    fn test_check_currency() {
+        let mut basket = create_basket(Utc::now(), Utc::now());
+
+        assert!(check_currency("PHP", get_accepted_currency(&basket)).is_ok());
+        assert!(check_currency("USD", get_accepted_currency(&basket)).is_err());
+
+        basket.basket_items.clear();
+
+        assert!(check_currency("USD", get_accepted_currency(&basket)).is_ok());
+    }
+
+    #[test]
+    fn test_validate_attendee() {
+        let attendee_config = AttendeeConfig {
+            require_name: true,
+            require_email: false,
+            questions: vec![
+                AttendeeQuestion { id: "size".to_string(), label: "Shirt size".to_string(), required: true },
+                AttendeeQuestion { id: "diet".to_string(), label: "Diet".to_string(), required: false },
+            ],
+        };
+        let attendee = |name: Option<&str>, answers: Vec<(&str, &str)>| Attendee {
+            name: name.map(|n| n.to_string()),
+            email: None,
+            answers: answers
+                .into_iter()
+                .map(|(question_id, answer)| AttendeeAnswer { question_id: question_id.to_string(), answer: answer.to_string() })
+                .collect(),
+        };
+
+        assert!(validate_attendee(&attendee_config, Some(&attendee(Some("Juan"), vec![("size", "M")]))).is_ok());
+        assert!(validate_attendee(&attendee_config, None).is_err());
+        assert!(validate_attendee(&attendee_config, Some(&attendee(None, vec![("size", "M")]))).is_err());
+        assert!(validate_attendee(&attendee_config, Some(&attendee(Some("Juan"), vec![("size", " ")]))).is_err());
+        assert!(validate_attendee(&attendee_config, Some(&attendee(Some("Juan"), vec![("size", "M"), ("age", "30")]))).is_err());
+        assert!(validate_attendee(&AttendeeConfig::default(), None).is_ok());
+    }
 }
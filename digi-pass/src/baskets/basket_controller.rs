@@ -1,27 +1,106 @@
 use std::sync::Arc;
 use crate::error::AppError;
-use axum::{extract::State, Json};
+use axum::{extract::{Path, State}, http::StatusCode, response::IntoResponse, Json};
 
-use crate::{app_state::AppState, validation::ValidatedJson};
+use jwt_authorizer::JwtClaims;
+use serde_json::Value;
+
+use crate::{app_state::AppState, helpers, validation::ValidatedJson};
 
 use super::{data_transfer_objects::{
-    CreateBasketRequest, CreateBasketResult, PurchaseBasketRequest, PurchaseBasketResult,
+    AddBasketItemRequest, AddBundleRequest, ApplyDiscountRequest, CreateBasketRequest, CreateBasketResult, PurchaseBasketRequest, PurchaseBasketResult, UpdateAttendeesRequest, UpdateBasketResult,
 }, errors::BasketError};
 
 pub async fn create(
     State(state): State<Arc<AppState>>,
+    JwtClaims(claims): JwtClaims<Value>,
     ValidatedJson(data): ValidatedJson<CreateBasketRequest>,
 ) -> Result<Json<CreateBasketResult>, AppError> {
-    let result = state.basket_service.create_basket(data).await?;
+    let customer_id = helpers::get_subject(&claims)?;
+    let result = state.basket_service.create_basket(data, &customer_id).await?;
     Ok(Json(result))
 }
 
 pub async fn post_purchase(
     State(state): State<Arc<AppState>>,
+    JwtClaims(claims): JwtClaims<Value>,
     ValidatedJson(data): ValidatedJson<PurchaseBasketRequest>,
 ) -> Result<Json<PurchaseBasketResult>, BasketError> {
-    let result = state.basket_service.purchase_basket(&state.payment_service, &state.order_service, &data.basket_id).await?;
+    let customer_id = helpers::get_subject(&claims)?;
+    let result = state.basket_service.purchase_basket(&state.payment_service, &state.order_service, &data.basket_id, &customer_id).await?;
 
     return  Ok(Json(PurchaseBasketResult { order_id: result }));
 }
 
+
+pub async fn post_item(
+    State(state): State<Arc<AppState>>,
+    JwtClaims(claims): JwtClaims<Value>,
+    Path(basket_id): Path<String>,
+    ValidatedJson(data): ValidatedJson<AddBasketItemRequest>,
+) -> Result<Json<UpdateBasketResult>, BasketError> {
+    let customer_id = helpers::get_subject(&claims)?;
+    let result = state.basket_service.add_basket_item(&state.payment_service, &basket_id, &customer_id, data).await?;
+    Ok(Json(result))
+}
+
+pub async fn post_bundle(
+    State(state): State<Arc<AppState>>,
+    JwtClaims(claims): JwtClaims<Value>,
+    Path(basket_id): Path<String>,
+    ValidatedJson(data): ValidatedJson<AddBundleRequest>,
+) -> Result<Json<UpdateBasketResult>, BasketError> {
+    let customer_id = helpers::get_subject(&claims)?;
+    let result = state.basket_service.add_bundle(&state.payment_service, &basket_id, &customer_id, data).await?;
+    Ok(Json(result))
+}
+
+pub async fn delete_item(
+    State(state): State<Arc<AppState>>,
+    JwtClaims(claims): JwtClaims<Value>,
+    Path((basket_id, basket_item_id)): Path<(String, String)>,
+) -> Result<Json<UpdateBasketResult>, BasketError> {
+    let customer_id = helpers::get_subject(&claims)?;
+    let result = state.basket_service.remove_basket_item(&state.payment_service, &basket_id, &customer_id, &basket_item_id).await?;
+    Ok(Json(result))
+}
+
+pub async fn get(
+    State(state): State<Arc<AppState>>,
+    JwtClaims(claims): JwtClaims<Value>,
+    Path(basket_id): Path<String>,
+) -> impl IntoResponse {
+    let customer_id = match helpers::get_subject(&claims) {
+        Ok(customer_id) => customer_id,
+        Err(err) => return BasketError::from(err).into_response(),
+    };
+    let result = state.basket_service.get_basket_details(&state.payment_service, &state.order_service, &basket_id, &customer_id).await;
+
+    match result {
+        Ok(Some(basket_details)) => (StatusCode::OK, Json(basket_details)).into_response(),
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(err) => BasketError::from(err).into_response(),
+    }
+}
+
+pub async fn post_discount(
+    State(state): State<Arc<AppState>>,
+    JwtClaims(claims): JwtClaims<Value>,
+    Path(basket_id): Path<String>,
+    ValidatedJson(data): ValidatedJson<ApplyDiscountRequest>,
+) -> Result<Json<UpdateBasketResult>, BasketError> {
+    let customer_id = helpers::get_subject(&claims)?;
+    let result = state.basket_service.apply_discount(&state.payment_service, &basket_id, &customer_id, &data.code).await?;
+    Ok(Json(result))
+}
+
+pub async fn put_attendees(
+    State(state): State<Arc<AppState>>,
+    JwtClaims(claims): JwtClaims<Value>,
+    Path(basket_id): Path<String>,
+    ValidatedJson(data): ValidatedJson<UpdateAttendeesRequest>,
+) -> Result<Json<UpdateBasketResult>, BasketError> {
+    let customer_id = helpers::get_subject(&claims)?;
+    let result = state.basket_service.update_attendees(&state.payment_service, &basket_id, &customer_id, data).await?;
+    Ok(Json(result))
+}
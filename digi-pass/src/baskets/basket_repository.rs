@@ -1,15 +1,20 @@
 use std::str::FromStr;
 
 use axum::async_trait;
-use bson::oid::ObjectId;
+use bson::{doc, oid::ObjectId, Bson};
 use mongodb::{Client, Collection};
-use super::data_models::Basket;
+use super::{constants::{BASKET_STATUS_OPEN, BASKET_STATUS_PURCHASED, BASKET_STATUS_PURCHASING}, data_models::Basket};
 
 
 #[async_trait]
 pub trait BasketRepository : Send + Sync  {
     async fn add(&self, basket: Basket) -> anyhow::Result<Option<String>>;
     async fn get (&self, id: &str) -> anyhow::Result<Option<Basket>>;
+    async fn update(&self, basket: &mut Basket) -> anyhow::Result<()>;
+    async fn claim_for_purchase(&self, id: &str, order_id: &str) -> anyhow::Result<bool>;
+    async fn claim_for_checkout(&self, id: &str, concurrency_stamp: &str) -> anyhow::Result<bool>;
+    async fn mark_purchased(&self, id: &str, order_id: &str) -> anyhow::Result<()>;
+    async fn release_purchase_claim(&self, id: &str, order_id: &str) -> anyhow::Result<()>;
 }
 
 #[derive(Clone)]
@@ -36,6 +41,15 @@ impl MongoDbBasketRepository {
     
 }
 
+/// Baskets stored before they had a concurrency stamp are read with an empty one, `null` also matches the missing field.
+fn concurrency_stamp_filter(concurrency_stamp: &str) -> Bson {
+    if concurrency_stamp.is_empty() {
+        Bson::Null
+    } else {
+        Bson::String(concurrency_stamp.to_string())
+    }
+}
+
 #[async_trait]
 impl BasketRepository for MongoDbBasketRepository{
     async fn add(&self, basket: Basket) -> anyhow::Result<Option<String>>{
@@ -47,8 +61,65 @@ impl BasketRepository for MongoDbBasketRepository{
 
     async fn get (&self, id: &str) -> anyhow::Result<Option<Basket>> {
         let collection = self.get_collection();
-        let filter = mongodb::bson::doc! {"_id": ObjectId::from_str(id)? };
+        let filter = doc! {"_id": ObjectId::from_str(id)? };
         let result = collection.find_one(filter, None).await?;
         Ok(result)
     }
-}
\ No newline at end of file
+
+    async fn update(&self, basket: &mut Basket) -> anyhow::Result<()> {
+        let filter = doc! {
+            "_id": basket.id.ok_or(anyhow::anyhow!("Failed to get object id"))?,
+            "concurrency_stamp": concurrency_stamp_filter(&basket.concurrency_stamp),
+            // Fails when a checkout started since the basket was read.
+            "checkout_stamp": basket.checkout_stamp.as_deref().map_or(Bson::Null, |stamp| Bson::String(stamp.to_string())),
+        };
+
+        basket.concurrency_stamp = ObjectId::new().to_hex();
+
+        let update = doc! {"$set": bson::to_bson(&basket)? };
+
+        let update_result = self.get_collection().update_one(filter, update, None).await?;
+        if update_result.matched_count == 0 {
+            return Err(anyhow::anyhow!("Failed to update basket"));
+        }
+        Ok(())
+    }
+
+    /// Only one caller can claim a basket, the concurrency stamp is bumped so pending updates fail.
+    async fn claim_for_purchase(&self, id: &str, order_id: &str) -> anyhow::Result<bool> {
+        let filter = doc! {"_id": ObjectId::from_str(id)?, "order_id": null};
+        let update = doc! {"$set": {"status": BASKET_STATUS_PURCHASING, "order_id": order_id, "concurrency_stamp": ObjectId::new().to_hex()}};
+
+        let update_result = self.get_collection().update_one(filter, update, None).await?;
+        Ok(update_result.modified_count == 1)
+    }
+
+    /// Fails when the basket changed since it was read, the concurrency stamp is left alone so checkouts
+    /// started at the same time can all claim it and settle on one of them.
+    async fn claim_for_checkout(&self, id: &str, concurrency_stamp: &str) -> anyhow::Result<bool> {
+        let filter = doc! {"_id": ObjectId::from_str(id)?, "concurrency_stamp": concurrency_stamp_filter(concurrency_stamp), "order_id": null};
+        let update = doc! {"$set": {"checkout_stamp": ObjectId::new().to_hex()}};
+
+        let update_result = self.get_collection().update_one(filter, update, None).await?;
+        Ok(update_result.matched_count == 1)
+    }
+
+    async fn mark_purchased(&self, id: &str, order_id: &str) -> anyhow::Result<()> {
+        let filter = doc! {"_id": ObjectId::from_str(id)?, "order_id": order_id};
+        let update = doc! {"$set": {"status": BASKET_STATUS_PURCHASED}};
+
+        let update_result = self.get_collection().update_one(filter, update, None).await?;
+        if update_result.matched_count == 0 {
+            return Err(anyhow::anyhow!("Failed to mark basket as purchased"));
+        }
+        Ok(())
+    }
+
+    async fn release_purchase_claim(&self, id: &str, order_id: &str) -> anyhow::Result<()> {
+        let filter = doc! {"_id": ObjectId::from_str(id)?, "order_id": order_id, "status": BASKET_STATUS_PURCHASING};
+        let update = doc! {"$set": {"status": BASKET_STATUS_OPEN, "order_id": null, "concurrency_stamp": ObjectId::new().to_hex()}};
+
+        self.get_collection().update_one(filter, update, None).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,4 @@
+pub const BASKET_STATUS_OPEN: &str = "open";
+pub const BASKET_STATUS_PURCHASING: &str = "purchasing";
+pub const BASKET_STATUS_PURCHASED: &str = "purchased";
+pub const BASKET_STATUS_ABANDONED: &str = "abandoned";
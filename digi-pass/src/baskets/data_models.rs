@@ -2,22 +2,60 @@ use bson::oid::ObjectId;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use crate::money::{self, Money};
+
+use super::constants::BASKET_STATUS_OPEN;
+
 #[derive(Serialize, Deserialize)]
 pub struct Basket{
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<ObjectId>,
+    /// Empty for baskets stored before they had one.
+    #[serde(default)]
+    pub concurrency_stamp: String,
+    /// The `sub` of the customer who created the basket, empty for baskets stored before they had an owner.
+    #[serde(default)]
+    pub owner_id: String,
+    /// Every item in the basket is priced in this currency, baskets stored before it existed were in PHP.
+    #[serde(default = "default_currency")]
+    pub currency: String,
     #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
     pub valid_until: DateTime<Utc>,
     pub basket_items: Vec<BasketItem>,
+    #[serde(default)]
+    pub discount: Option<BasketDiscount>,
+    #[serde(default = "default_status")]
+    pub status: String,
+    /// Claimed before the order is created so retried purchases end up with the same order.
+    #[serde(default)]
+    pub order_id: Option<String>,
+    /// Renewed by every checkout once its payment is stored, updates of a basket read before that fail.
+    #[serde(default)]
+    pub checkout_stamp: Option<String>,
+}
+
+fn default_status() -> String {
+    BASKET_STATUS_OPEN.to_string()
+}
+
+fn default_currency() -> String {
+    money::DEFAULT_CURRENCY.to_string()
 }
 
 
 impl Basket {
-    pub fn new(valid_until: DateTime<Utc>, basket_items: Vec<BasketItem>) -> Self {
+    pub fn new(owner_id: String, currency: String, valid_until: DateTime<Utc>, basket_items: Vec<BasketItem>) -> Self {
         Basket {
             id: None,
+            concurrency_stamp: ObjectId::new().to_hex(),
+            owner_id,
+            currency,
             valid_until,
             basket_items,
+            discount: None,
+            status: default_status(),
+            order_id: None,
+            checkout_stamp: None,
         }
     }
 }
@@ -25,8 +63,55 @@ impl Basket {
 
 #[derive(Serialize, Deserialize)]
 pub struct BasketItem{
+    pub id: String,
     pub basketed_inventories: Vec<BasketedInventory>,
-    pub price: i32,
+    #[serde(deserialize_with = "crate::money::deserialize_legacy_money")]
+    pub price: Money,
+    #[serde(default = "crate::money::default_legacy_money")]
+    pub discount: Money,
+    #[serde(default = "crate::money::default_legacy_money")]
+    pub fee: Money,
+    /// In basis points.
+    #[serde(default)]
+    pub tax_rate: i32,
+    #[serde(default = "crate::money::default_legacy_money")]
+    pub tax: Money,
+    /// Set when the item is a bundle, its inventories are then sold and refunded together.
+    #[serde(default)]
+    pub bundle: Option<BasketItemBundle>,
+}
+
+impl BasketItem {
+    pub fn new(price: Money, fee: Money, tax_rate: i32, basketed_inventories: Vec<BasketedInventory>) -> Self {
+        BasketItem {
+            id: ObjectId::new().to_hex(),
+            basketed_inventories,
+            discount: Money::zero(&price.currency),
+            tax: Money::zero(&price.currency),
+            price,
+            fee,
+            tax_rate,
+            bundle: None,
+        }
+    }
+
+    pub fn with_bundle(mut self, bundle: BasketItemBundle) -> Self {
+        self.bundle = Some(bundle);
+        self
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BasketItemBundle {
+    pub bundle_id: String,
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BasketDiscount {
+    pub discount_id: String,
+    pub code: String,
+    pub amount: Money,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -36,6 +121,8 @@ pub struct BasketedInventory{
     pub inventory_id: String,
     #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
     pub reserved_until: DateTime<Utc>,
+    #[serde(default)]
+    pub attendee: Option<Attendee>,
 }
 
 impl BasketedInventory {
@@ -45,7 +132,22 @@ impl BasketedInventory {
             event_id,
             name,
             inventory_id,
-            reserved_until
+            reserved_until,
+            attendee: None,
         }
     }
+}
+
+/// The person the ticket is for.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Attendee {
+    pub name: Option<String>,
+    pub email: Option<String>,
+    pub answers: Vec<AttendeeAnswer>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AttendeeAnswer {
+    pub question_id: String,
+    pub answer: String,
 }
\ No newline at end of file
@@ -2,9 +2,13 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use validator::Validate;
 
+use crate::{money::Money, payments::data_transfer_objects::PaymentStatus};
+
 #[derive(Debug, Validate, Deserialize)]
 pub struct CreateBasketRequest {
     pub add_basket_item_request: Vec<AddBasketItemRequest>,
+    #[serde(default)]
+    pub add_bundle_request: Vec<AddBundleRequest>,
 }
 
 #[derive(Debug, Validate, Deserialize)]
@@ -15,25 +19,91 @@ pub struct AddBasketItemRequest {
     pub quantity: i32,
 }
 
+#[derive(Debug, Validate, Deserialize)]
+pub struct AddBundleRequest {
+    #[validate(length(min = 1))]
+    pub bundle_id: String,
+    #[validate(range(min = 1))]
+    pub quantity: i32,
+}
+
 #[derive(Serialize)]
 pub struct CreateBasketResult {
     pub basket_id: String,
 }
 
+#[derive(Serialize)]
+pub struct UpdateBasketResult {
+    pub basket_id: String,
+    pub price: Money,
+}
+
 #[derive(Serialize, Default, Debug)]
 pub struct Basket {
     pub id: String,
+    pub concurrency_stamp: String,
+    pub owner_id: String,
     pub original_order_id: Option<String>,
     pub valid_until: DateTime<Utc>,
     pub basket_items: Vec<BasketItem>,
     pub payments: Vec<BasketPayment>,
-    pub price: i32,
+    pub discount: Option<BasketDiscount>,
+    pub price_lines: Vec<BasketPriceLine>,
+    pub price: Money,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub enum BasketState {
+    Active,
+    Expired,
+    AwaitingPayment,
+    Paid,
+    Purchased,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BasketDetails {
+    pub id: String,
+    pub state: BasketState,
+    pub valid_until: DateTime<Utc>,
+    pub reserved_until: Option<DateTime<Utc>>,
+    pub basket_items: Vec<BasketItem>,
+    pub payments: Vec<BasketPayment>,
+    pub discount: Option<BasketDiscount>,
+    pub price_lines: Vec<BasketPriceLine>,
+    pub price: Money,
+    pub order_id: Option<String>,
 }
 
 #[derive(Serialize, Default, Debug)]
 pub struct BasketItem{
+    pub id: String,
     pub basketed_inventories: Vec<BasketedInventory>,
-    pub price: i32,
+    pub price: Money,
+    pub discount: Money,
+    pub fee: Money,
+    pub tax: Money,
+    pub bundle: Option<BasketItemBundle>,
+}
+
+#[derive(Serialize, Default, Debug)]
+pub struct BasketItemBundle {
+    pub bundle_id: String,
+    pub name: String,
+}
+
+#[derive(Serialize, Default, Debug)]
+pub struct BasketPriceLine {
+    pub line_type: String,
+    pub name: String,
+    pub amount: Money,
+}
+
+#[derive(Serialize, Default, Debug)]
+pub struct BasketDiscount {
+    pub discount_id: String,
+    pub code: String,
+    pub amount: Money,
 }
 
 #[derive(Serialize, Default, Debug)]
@@ -42,19 +112,60 @@ pub struct BasketedInventory{
     pub name: String,
     pub inventory_id: String,
     pub reserved_until: DateTime<Utc>,
+    pub attendee: Option<Attendee>,
+}
+
+#[derive(Serialize, Default, Debug)]
+pub struct Attendee {
+    pub name: Option<String>,
+    pub email: Option<String>,
+    pub answers: Vec<AttendeeAnswer>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct AttendeeAnswer {
+    pub question_id: String,
+    pub answer: String,
 }
 
 #[derive(Serialize, Default, Debug)]
 pub struct BasketPayment {
     pub id: String,
-    pub amount: i32,
-    pub currency: String,
+    pub amount: Money,
     pub provider: String,
-    pub status: String,
+    pub status: PaymentStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure_reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub paid_amount: Option<Money>,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub payment_type: String,
 }
 
+#[derive(Debug, Validate, Deserialize)]
+pub struct ApplyDiscountRequest {
+    #[validate(length(min = 1))]
+    pub code: String,
+}
+
+#[derive(Debug, Validate, Deserialize)]
+pub struct UpdateAttendeesRequest {
+    #[validate(length(min = 1), custom(function = "crate::validation::validate_all"))]
+    pub attendees: Vec<AttendeeRequest>,
+}
+
+#[derive(Debug, Validate, Serialize, Deserialize)]
+pub struct AttendeeRequest {
+    #[validate(length(min = 1))]
+    pub inventory_id: String,
+    #[validate(length(min = 1))]
+    pub name: Option<String>,
+    #[validate(email)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub answers: Vec<AttendeeAnswer>,
+}
+
 #[derive(Debug, Validate, Deserialize)]
 pub struct PurchaseBasketRequest {
     #[validate(length(min = 1))]
@@ -6,10 +6,33 @@ use thiserror::Error;
 pub enum BasketErrors {
     #[error("Basket not found.")]
     BasketNotFound,
+    #[error("Basket does not belong to the caller.")]
+    BasketNotOwned,
     #[error("Basket is expired.")]
     BasketExpired,
     #[error("Basket is unpaid.")]
     BasketUnpaid,
+    #[error("Basket item not found.")]
+    BasketItemNotFound,
+    #[error("Bundle not found.")]
+    BundleNotFound,
+    #[error("Basket can no longer be modified.")]
+    BasketLocked,
+    #[error("Not enough inventories for event {event_id}: requested {requested}, available {available} ({} short).", requested - available)]
+    NotEnoughInventories {
+        event_id: String,
+        requested: i32,
+        available: i32,
+    },
+    #[error("{0}")]
+    InvalidDiscount(String),
+    #[error("{0}")]
+    InvalidAttendee(String),
+    #[error("Basket is priced in {expected} but the item is priced in {actual}.")]
+    CurrencyMismatch {
+        expected: String,
+        actual: String,
+    },
     #[error("{0}")]
     Unknown(#[from] anyhow::Error)
 }
@@ -24,8 +47,16 @@ impl IntoResponse for BasketError {
     fn into_response(self) -> Response {
         match self.error {
             BasketErrors::BasketNotFound => (StatusCode::BAD_REQUEST, format!("{}", BasketErrors::BasketNotFound)),
+            BasketErrors::BasketNotOwned => (StatusCode::FORBIDDEN, format!("{}", BasketErrors::BasketNotOwned)),
             BasketErrors::BasketExpired => (StatusCode::BAD_REQUEST, format!("{}", BasketErrors::BasketExpired)),
             BasketErrors::BasketUnpaid => (StatusCode::BAD_REQUEST, format!("{}", BasketErrors::BasketUnpaid)),
+            BasketErrors::BasketItemNotFound => (StatusCode::BAD_REQUEST, format!("{}", BasketErrors::BasketItemNotFound)),
+            BasketErrors::BundleNotFound => (StatusCode::BAD_REQUEST, format!("{}", BasketErrors::BundleNotFound)),
+            BasketErrors::BasketLocked => (StatusCode::CONFLICT, format!("{}", BasketErrors::BasketLocked)),
+            err @ BasketErrors::NotEnoughInventories { .. } => (StatusCode::BAD_REQUEST, format!("{}", err)),
+            BasketErrors::InvalidDiscount(message) => (StatusCode::BAD_REQUEST, message),
+            BasketErrors::InvalidAttendee(message) => (StatusCode::BAD_REQUEST, message),
+            err @ BasketErrors::CurrencyMismatch { .. } => (StatusCode::BAD_REQUEST, format!("{}", err)),
             BasketErrors::Unknown(err) => (StatusCode::INTERNAL_SERVER_ERROR, format!("{}", err)),
         }.into_response()
     }
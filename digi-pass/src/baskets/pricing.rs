@@ -0,0 +1,130 @@
+use crate::money::Money;
+
+use super::data_models::{Basket, BasketItem};
+
+pub const PRICE_LINE_TYPE_FEE: &str = "fee";
+pub const PRICE_LINE_TYPE_TAX: &str = "tax";
+
+const CONVENIENCE_FEE_NAME: &str = "Convenience Fee";
+const TAX_NAME: &str = "VAT";
+
+/// A fee or tax added on top of the basket's items.
+#[derive(Debug, PartialEq)]
+pub struct PriceLine {
+    pub line_type: String,
+    pub name: String,
+    pub amount: Money,
+}
+
+/// Recomputes the tax of every item. Needs to run whenever prices, discounts or fees change.
+pub fn reprice_basket(basket: &mut Basket) {
+    for basket_item in basket.basket_items.iter_mut() {
+        basket_item.tax = compute_item_tax(basket_item);
+    }
+}
+
+/// Tax is levied on what the customer actually pays for the item, fee included.
+/// All of an item's amounts share the currency of its price.
+pub fn compute_item_tax(basket_item: &BasketItem) -> Money {
+    let taxable = basket_item.price.amount - basket_item.discount.amount + basket_item.fee.amount;
+    Money::new(
+        (taxable * basket_item.tax_rate as i64 + 5_000) / 10_000,
+        &basket_item.price.currency,
+    )
+}
+
+pub fn compute_item_total(basket_item: &BasketItem) -> Money {
+    Money::new(
+        basket_item.price.amount - basket_item.discount.amount + basket_item.fee.amount + basket_item.tax.amount,
+        &basket_item.price.currency,
+    )
+}
+
+/// Items are only ever added in the basket's currency, so their amounts can be summed directly.
+pub fn compute_basket_total(basket: &Basket) -> Money {
+    sum_items(basket, |bi| compute_item_total(bi).amount)
+}
+
+pub fn get_price_lines(basket: &Basket) -> Vec<PriceLine> {
+    let fees = sum_items(basket, |bi| bi.fee.amount);
+    let tax = sum_items(basket, |bi| bi.tax.amount);
+
+    let mut price_lines = vec![];
+    if fees.amount > 0 {
+        price_lines.push(PriceLine {
+            line_type: PRICE_LINE_TYPE_FEE.to_string(),
+            name: CONVENIENCE_FEE_NAME.to_string(),
+            amount: fees,
+        });
+    }
+    if tax.amount > 0 {
+        price_lines.push(PriceLine {
+            line_type: PRICE_LINE_TYPE_TAX.to_string(),
+            name: TAX_NAME.to_string(),
+            amount: tax,
+        });
+    }
+    price_lines
+}
+
+fn sum_items(basket: &Basket, amount: impl Fn(&BasketItem) -> i64) -> Money {
+    Money::new(basket.basket_items.iter().map(amount).sum(), &basket.currency)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+
+    fn php(amount: i64) -> Money {
+        Money::new(amount, "PHP")
+    }
+
+    #[test]
+    fn test_compute_item_tax_includes_fee_and_discount() {
+        let mut basket_item = BasketItem::new(php(25000), php(1500), 1200, vec![]);
+        basket_item.discount = php(2500);
+
+        assert_eq!(compute_item_tax(&basket_item), php(2880));
+    }
+
+    #[test]
+    fn test_compute_item_tax_rounds_half_up() {
+        let basket_item = BasketItem::new(php(5), php(0), 1000, vec![]);
+
+        assert_eq!(compute_item_tax(&basket_item), php(1));
+    }
+
+    #[test]
+    fn test_reprice_basket() {
+        let mut basket = Basket::new(
+            "customer".to_string(),
+            "PHP".to_string(),
+            Utc::now(),
+            vec![
+                BasketItem::new(php(10000), php(500), 1200, vec![]),
+                BasketItem::new(php(10000), php(500), 0, vec![]),
+            ],
+        );
+
+        reprice_basket(&mut basket);
+
+        assert_eq!(compute_basket_total(&basket), php(10000 + 500 + 1260 + 10000 + 500));
+        assert_eq!(
+            get_price_lines(&basket),
+            vec![
+                PriceLine {
+                    line_type: PRICE_LINE_TYPE_FEE.to_string(),
+                    name: CONVENIENCE_FEE_NAME.to_string(),
+                    amount: php(1000),
+                },
+                PriceLine {
+                    line_type: PRICE_LINE_TYPE_TAX.to_string(),
+                    name: TAX_NAME.to_string(),
+                    amount: php(1260),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,6 @@
+mod data_models;
+mod persistence;
+pub mod errors;
+pub mod application;
+pub mod data_transfer_objects;
+pub mod controller;
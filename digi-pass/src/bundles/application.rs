@@ -0,0 +1,116 @@
+use std::{str::FromStr, sync::Arc};
+
+use bson::oid::ObjectId;
+use chrono::Utc;
+use mongodb::Client;
+
+use crate::{events::application::EventService, money::Money};
+
+use super::{
+    data_models::Bundle,
+    data_transfer_objects::{BundleDetails, CreateBundle, CreateBundleResult},
+    errors::BundleErrors,
+    persistence::{BundleRepository, MongoDbBundleRepository},
+};
+
+#[derive(Clone)]
+pub struct BundleService {
+    event_service: EventService,
+    bundle_repository: Arc<dyn BundleRepository>,
+}
+
+impl BundleService {
+    pub fn new(client: Client, database: String, event_service: EventService) -> Self {
+        let bundle_repository = Arc::new(MongoDbBundleRepository::new(client, database));
+        BundleService {
+            event_service,
+            bundle_repository,
+        }
+    }
+
+    /// Every event in the bundle has to exist and be priced in the bundle's currency.
+    pub async fn create_bundle(&self, data: CreateBundle) -> anyhow::Result<CreateBundleResult> {
+        for event_id in data.event_ids.iter() {
+            let event = self
+                .event_service
+                .get_event(event_id)
+                .await?
+                .ok_or(BundleErrors::EventNotFound(event_id.clone()))?;
+            if event.price.currency != data.currency {
+                return Err(BundleErrors::CurrencyMismatch {
+                    event_id: event_id.clone(),
+                    expected: data.currency.clone(),
+                    actual: event.price.currency,
+                }
+                .into());
+            }
+        }
+
+        let bundle = map_bundle(data)?;
+        let id = self.bundle_repository.add(&bundle).await?;
+        Ok(CreateBundleResult { id })
+    }
+
+    pub async fn get_bundle(&self, bundle_id: &str) -> anyhow::Result<Option<BundleDetails>> {
+        let bundle = self
+            .bundle_repository
+            .get(ObjectId::from_str(bundle_id)?)
+            .await?;
+        Ok(bundle.as_ref().map(map_bundle_details))
+    }
+}
+
+fn map_bundle(data: CreateBundle) -> anyhow::Result<Bundle> {
+    let event_ids = data
+        .event_ids
+        .iter()
+        .map(|event_id| ObjectId::from_str(event_id))
+        .collect::<Result<Vec<ObjectId>, _>>()?;
+
+    Ok(Bundle {
+        id: None,
+        name: data.name,
+        event_ids,
+        price: Money::new(data.price, &data.currency),
+        convenience_fee: Money::new(data.convenience_fee, &data.currency),
+        tax_rate: data.tax_rate,
+        created_at: Utc::now(),
+    })
+}
+
+fn map_bundle_details(bundle: &Bundle) -> BundleDetails {
+    BundleDetails {
+        id: bundle.id.map(|id| id.to_hex()).unwrap_or_default(),
+        name: bundle.name.clone(),
+        event_ids: bundle.event_ids.iter().map(|id| id.to_hex()).collect(),
+        price: bundle.price.clone(),
+        convenience_fee: bundle.convenience_fee.clone(),
+        tax_rate: bundle.tax_rate,
+        created_at: bundle.created_at,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map_bundle() {
+        let event_ids = vec![ObjectId::new().to_hex(), ObjectId::new().to_hex()];
+        let create_bundle = CreateBundle {
+            name: "Festival Pass".to_string(),
+            event_ids: event_ids.clone(),
+            currency: "PHP".to_string(),
+            price: 50000,
+            convenience_fee: 1500,
+            tax_rate: 1200,
+        };
+
+        let bundle = map_bundle(create_bundle).unwrap();
+        let bundle_details = map_bundle_details(&bundle);
+
+        assert_eq!(bundle.price, Money::new(50000, "PHP"));
+        assert_eq!(bundle.convenience_fee, Money::new(1500, "PHP"));
+        assert_eq!(bundle_details.event_ids, event_ids);
+    }
+}
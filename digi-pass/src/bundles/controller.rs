@@ -0,0 +1,34 @@
+use std::sync::Arc;
+use axum::{extract::{Path, State}, http::StatusCode, response::IntoResponse, Json};
+
+use jwt_authorizer::JwtClaims;
+use serde_json::Value;
+
+use crate::{app_state::AppState, helpers, validation::ValidatedJson};
+
+use super::{data_transfer_objects::{CreateBundle, CreateBundleResult}, errors::{BundleError, BundleErrors}};
+
+pub async fn create(
+    State(state): State<Arc<AppState>>,
+    JwtClaims(claims): JwtClaims<Value>,
+    ValidatedJson(data): ValidatedJson<CreateBundle>,
+) -> Result<Json<CreateBundleResult>, BundleError> {
+    if !helpers::is_staff(&claims) {
+        return Err(BundleError { error: BundleErrors::NotStaff });
+    }
+    let result = state.bundle_service.create_bundle(data).await?;
+    Ok(Json(result))
+}
+
+pub async fn get(
+    State(state): State<Arc<AppState>>,
+    Path(bundle_id): Path<String>,
+) -> impl IntoResponse {
+    let result = state.bundle_service.get_bundle(&bundle_id).await;
+
+    match result {
+        Ok(Some(bundle_details)) => (StatusCode::OK, Json(bundle_details)).into_response(),
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(err) => BundleError::from(err).into_response(),
+    }
+}
@@ -0,0 +1,20 @@
+use bson::oid::ObjectId;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::money::Money;
+
+/// A package of one ticket from each of its events, sold at a single price.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Bundle {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub name: String,
+    pub event_ids: Vec<ObjectId>,
+    pub price: Money,
+    pub convenience_fee: Money,
+    /// In basis points.
+    pub tax_rate: i32,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub created_at: DateTime<Utc>,
+}
@@ -0,0 +1,58 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use validator::{Validate, ValidationError};
+
+use crate::money::{self, Money};
+
+#[derive(Debug, Validate, Deserialize)]
+#[validate(schema(function = "validate_create_bundle", skip_on_field_errors = false))]
+pub struct CreateBundle {
+    #[validate(length(min = 1))]
+    pub name: String,
+    /// One ticket of each of these events goes into the bundle.
+    #[validate(length(min = 2))]
+    pub event_ids: Vec<String>,
+    #[serde(default = "default_currency")]
+    pub currency: String,
+    /// In the currency's minor unit, a bundle is never free.
+    #[validate(range(min = 1))]
+    pub price: i64,
+    #[validate(range(min = 0))]
+    #[serde(default)]
+    pub convenience_fee: i64,
+    /// Tax rate in basis points, e.g. 1200 for 12% VAT.
+    #[validate(range(min = 0, max = 10000))]
+    #[serde(default)]
+    pub tax_rate: i32,
+}
+
+fn default_currency() -> String {
+    money::DEFAULT_CURRENCY.to_string()
+}
+
+fn validate_create_bundle(create_bundle: &&CreateBundle) -> Result<(), ValidationError> {
+    if !money::is_valid_currency(&create_bundle.currency) {
+        return Err(ValidationError::new("currency must be an ISO 4217 code"));
+    }
+    let event_ids = &create_bundle.event_ids;
+    if event_ids.iter().enumerate().any(|(index, event_id)| event_ids[..index].contains(event_id)) {
+        return Err(ValidationError::new("event_ids must be unique"));
+    }
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CreateBundleResult {
+    pub id: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BundleDetails {
+    pub id: String,
+    pub name: String,
+    pub event_ids: Vec<String>,
+    pub price: Money,
+    pub convenience_fee: Money,
+    pub tax_rate: i32,
+    pub created_at: DateTime<Utc>,
+}
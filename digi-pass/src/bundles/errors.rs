@@ -0,0 +1,53 @@
+use axum::response::{IntoResponse, Response};
+use axum::http::StatusCode;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum BundleErrors {
+    #[error("Event not found: {0}")]
+    EventNotFound(String),
+    #[error("Event {event_id} is priced in {actual} but the bundle is priced in {expected}.")]
+    CurrencyMismatch {
+        event_id: String,
+        expected: String,
+        actual: String,
+    },
+    #[error("Only staff can manage bundles.")]
+    NotStaff,
+    #[error("{0}")]
+    Unknown(#[from] anyhow::Error)
+}
+
+pub struct BundleError {
+    pub error: BundleErrors,
+}
+
+
+// Tell axum how to convert `BundleErrors` into a response.
+impl IntoResponse for BundleError {
+    fn into_response(self) -> Response {
+        let status_code = match &self.error {
+            BundleErrors::NotStaff => StatusCode::FORBIDDEN,
+            BundleErrors::Unknown(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::BAD_REQUEST,
+        };
+
+        (status_code, format!("{}", self.error)).into_response()
+    }
+}
+
+impl From<anyhow::Error> for BundleError
+{
+    fn from(err: anyhow::Error) -> Self {
+        match err.downcast::<BundleErrors>() {
+            Ok(err) => 
+            Self {
+                error: err,
+            },
+            Err(err) => 
+            Self {
+                error: BundleErrors::Unknown(err).into(),
+            },
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use async_trait::async_trait;
+use bson::{doc, oid::ObjectId};
+use mongodb::{Client, Collection};
+
+use super::data_models::Bundle;
+
+#[async_trait]
+pub trait BundleRepository: Send + Sync {
+    async fn add(&self, bundle: &Bundle) -> anyhow::Result<String>;
+    async fn get(&self, id: ObjectId) -> anyhow::Result<Option<Bundle>>;
+}
+
+pub struct MongoDbBundleRepository {
+    pub client: Client,
+    pub database: String,
+}
+
+impl MongoDbBundleRepository {
+    pub fn new(client: Client, database: String) -> Self {
+        MongoDbBundleRepository { client, database }
+    }
+
+    fn get_collection(&self) -> Collection<Bundle> {
+        let database = self.client.database(&self.database[..]);
+        database.collection::<Bundle>("Bundles")
+    }
+}
+
+#[async_trait]
+impl BundleRepository for MongoDbBundleRepository {
+    async fn add(&self, bundle: &Bundle) -> anyhow::Result<String> {
+        let result = self.get_collection().insert_one(bundle, None).await?;
+        let hex = result
+            .inserted_id
+            .as_object_id()
+            .ok_or(anyhow::anyhow!("Failed to get object id"))?
+            .to_hex();
+        Ok(hex)
+    }
+
+    async fn get(&self, id: ObjectId) -> anyhow::Result<Option<Bundle>> {
+        let bundle = self
+            .get_collection()
+            .find_one(doc! {"_id": id}, None)
+            .await?;
+        Ok(bundle)
+    }
+}
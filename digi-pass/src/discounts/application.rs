@@ -0,0 +1,332 @@
+use std::{str::FromStr, sync::Arc};
+
+use bson::oid::ObjectId;
+use chrono::{DateTime, Utc};
+use mongodb::Client;
+
+use crate::money::DEFAULT_CURRENCY;
+
+use super::{
+    constants::{DISCOUNT_TYPE_FIXED, DISCOUNT_TYPE_PERCENTAGE},
+    data_models::{Discount, DiscountRedemption},
+    data_transfer_objects::{AppliedDiscount, CreateDiscount, CreateDiscountResult, DiscountableItem, RedeemDiscount},
+    errors::DiscountErrors,
+    persistence::{DiscountRepository, MongoDbDiscountRepository},
+};
+
+#[derive(Clone)]
+pub struct DiscountService {
+    discount_repository: Arc<dyn DiscountRepository>,
+}
+
+impl DiscountService {
+    pub fn new(client: Client, database: String) -> Self {
+        let discount_repository = Arc::new(MongoDbDiscountRepository::new(client, database));
+        DiscountService {
+            discount_repository,
+        }
+    }
+
+    /// Codes are looked up by value, the unique index keeps them from resolving to an arbitrary discount.
+    pub async fn ensure_discount_indexes(&self) -> anyhow::Result<()> {
+        self.discount_repository.ensure_indexes().await
+    }
+
+    pub async fn create_discount(&self, data: CreateDiscount) -> anyhow::Result<CreateDiscountResult> {
+        let discount = map_discount(data)?;
+        let id = self.discount_repository.add(&discount).await?;
+        Ok(CreateDiscountResult { id })
+    }
+
+    /// Checks the code can be used by the customer and computes how much it takes off each item.
+    /// Nothing is counted here, see `redeem_discount`.
+    pub async fn apply_discount(
+        &self,
+        code: &str,
+        customer_id: &str,
+        items: &[DiscountableItem],
+    ) -> anyhow::Result<AppliedDiscount> {
+        let discount = self
+            .discount_repository
+            .get_by_code(&normalize_code(code))
+            .await?
+            .ok_or(DiscountErrors::DiscountNotFound)?;
+
+        if !is_discount_active(&discount, Utc::now()) {
+            return Err(DiscountErrors::DiscountNotActive.into());
+        }
+        if let Some(max_uses) = discount.max_uses {
+            if discount.uses >= max_uses {
+                return Err(DiscountErrors::UsageLimitReached.into());
+            }
+        }
+        let discount_id = discount.id.ok_or(anyhow::anyhow!("Failed to get object id"))?;
+        if let Some(max_uses_per_customer) = discount.max_uses_per_customer {
+            let redemptions = self
+                .discount_repository
+                .count_redemptions(discount_id, customer_id)
+                .await?;
+            if redemptions >= max_uses_per_customer as u64 {
+                return Err(DiscountErrors::CustomerUsageLimitReached.into());
+            }
+        }
+
+        let item_discounts = compute_item_discounts(&discount, items);
+        let amount: i64 = item_discounts.iter().sum();
+        if amount == 0 {
+            return Err(DiscountErrors::DiscountNotApplicable.into());
+        }
+
+        Ok(AppliedDiscount {
+            discount_id: discount_id.to_hex(),
+            code: discount.code,
+            amount,
+            item_discounts,
+        })
+    }
+
+    /// Counts a use of the discount against its limits, for the purchase claiming `order_id`.
+    /// Redeeming the same order again does nothing, so resumed purchases are only counted once.
+    /// Give the use back with `release_redemption` when the order could not be created.
+    pub async fn redeem_discount(&self, redeem_discount: RedeemDiscount) -> anyhow::Result<()> {
+        if self
+            .discount_repository
+            .get_redemption_by_order_id(&redeem_discount.order_id)
+            .await?
+            .is_some()
+        {
+            return Ok(());
+        }
+
+        let discount_id = ObjectId::from_str(&redeem_discount.discount_id)?;
+        let discount = self
+            .discount_repository
+            .get(discount_id)
+            .await?
+            .ok_or(DiscountErrors::DiscountNotFound)?;
+
+        let customer_id = redeem_discount.customer_id.clone();
+        if let Some(max_uses_per_customer) = discount.max_uses_per_customer {
+            if !self
+                .discount_repository
+                .increment_customer_uses(discount_id, &customer_id, max_uses_per_customer)
+                .await?
+            {
+                return Err(DiscountErrors::CustomerUsageLimitReached.into());
+            }
+        }
+        if !self.discount_repository.increment_uses(&discount).await? {
+            self.release_customer_use(&discount, &customer_id).await?;
+            return Err(DiscountErrors::UsageLimitReached.into());
+        }
+
+        let redemption = DiscountRedemption {
+            id: None,
+            discount_id,
+            code: discount.code.clone(),
+            customer_id: redeem_discount.customer_id,
+            basket_id: redeem_discount.basket_id,
+            order_id: redeem_discount.order_id,
+            amount: redeem_discount.amount,
+            redeemed_at: Utc::now(),
+        };
+        if let Err(err) = self.discount_repository.add_redemption(&redemption).await {
+            self.discount_repository.decrement_uses(discount_id).await?;
+            self.release_customer_use(&discount, &customer_id).await?;
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    async fn release_customer_use(&self, discount: &Discount, customer_id: &str) -> anyhow::Result<()> {
+        if discount.max_uses_per_customer.is_none() {
+            return Ok(());
+        }
+        let discount_id = discount.id.ok_or(anyhow::anyhow!("Failed to get object id"))?;
+        self.discount_repository.decrement_customer_uses(discount_id, customer_id).await
+    }
+
+    /// Gives back the use counted for `order_id`, if any.
+    pub async fn release_redemption(&self, order_id: &str) -> anyhow::Result<()> {
+        let Some(redemption) = self.discount_repository.get_redemption_by_order_id(order_id).await? else {
+            return Ok(());
+        };
+        let redemption_id = redemption.id.ok_or(anyhow::anyhow!("Failed to get object id"))?;
+        if self.discount_repository.delete_redemption(redemption_id).await? {
+            self.discount_repository.decrement_uses(redemption.discount_id).await?;
+            self.discount_repository.decrement_customer_uses(redemption.discount_id, &redemption.customer_id).await?;
+        }
+        Ok(())
+    }
+}
+
+fn map_discount(data: CreateDiscount) -> anyhow::Result<Discount> {
+    let event_ids = data
+        .event_ids
+        .iter()
+        .map(|event_id| ObjectId::from_str(event_id))
+        .collect::<Result<Vec<ObjectId>, _>>()?;
+    let bundle_ids = data
+        .bundle_ids
+        .iter()
+        .map(|bundle_id| ObjectId::from_str(bundle_id))
+        .collect::<Result<Vec<ObjectId>, _>>()?;
+
+    let currency = match data.discount_type.as_str() {
+        DISCOUNT_TYPE_FIXED => Some(data.currency.unwrap_or(DEFAULT_CURRENCY.to_string())),
+        _ => None,
+    };
+
+    Ok(Discount {
+        id: None,
+        code: normalize_code(&data.code),
+        discount_type: data.discount_type,
+        value: data.value,
+        currency,
+        event_ids,
+        bundle_ids,
+        valid_from: data.valid_from,
+        valid_until: data.valid_until,
+        max_uses: data.max_uses,
+        max_uses_per_customer: data.max_uses_per_customer,
+        uses: 0,
+        created_at: Utc::now(),
+    })
+}
+
+fn normalize_code(code: &str) -> String {
+    code.trim().to_uppercase()
+}
+
+fn is_discount_active(discount: &Discount, now: DateTime<Utc>) -> bool {
+    discount.valid_from <= now && now < discount.valid_until
+}
+
+/// Fixed discounts only ever apply to items priced in their own currency.
+/// Codes scoped to events or bundles apply to items matching either, unscoped codes apply to every item.
+fn is_item_in_scope(discount: &Discount, item: &DiscountableItem) -> bool {
+    if let Some(currency) = &discount.currency {
+        if currency != &item.price.currency {
+            return false;
+        }
+    }
+    if discount.event_ids.is_empty() && discount.bundle_ids.is_empty() {
+        return true;
+    }
+    let in_events = item
+        .event_ids
+        .iter()
+        .any(|event_id| discount.event_ids.iter().any(|id| &id.to_hex() == event_id));
+    let in_bundles = item
+        .bundle_id
+        .as_ref()
+        .is_some_and(|bundle_id| discount.bundle_ids.iter().any(|id| &id.to_hex() == bundle_id));
+    in_events || in_bundles
+}
+
+/// Percentage codes take the percentage off every item in scope.
+/// Fixed codes take the amount off the items in scope in order, never going below zero.
+fn compute_item_discounts(discount: &Discount, items: &[DiscountableItem]) -> Vec<i64> {
+    let mut remaining = discount.value as i64;
+    items
+        .iter()
+        .map(|item| {
+            if !is_item_in_scope(discount, item) {
+                return 0;
+            }
+            if discount.discount_type == DISCOUNT_TYPE_PERCENTAGE {
+                return item.price.amount * discount.value as i64 / 100;
+            }
+            let item_discount = remaining.min(item.price.amount);
+            remaining -= item_discount;
+            item_discount
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::money::Money;
+
+    fn create_discount(discount_type: &str, value: i32, event_ids: Vec<ObjectId>) -> Discount {
+        Discount {
+            id: None,
+            code: "CODE".to_string(),
+            discount_type: discount_type.to_string(),
+            value,
+            currency: match discount_type {
+                DISCOUNT_TYPE_FIXED => Some("PHP".to_string()),
+                _ => None,
+            },
+            event_ids,
+            bundle_ids: vec![],
+            valid_from: Utc::now() - chrono::Duration::days(1),
+            valid_until: Utc::now() + chrono::Duration::days(1),
+            max_uses: None,
+            max_uses_per_customer: None,
+            uses: 0,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_compute_item_discounts_percentage() {
+        let event_id = ObjectId::new();
+        let discount = create_discount(DISCOUNT_TYPE_PERCENTAGE, 10, vec![event_id]);
+        let items = vec![
+            DiscountableItem { event_ids: vec![event_id.to_hex()], bundle_id: None, price: Money::new(25000, "PHP") },
+            DiscountableItem { event_ids: vec![ObjectId::new().to_hex()], bundle_id: None, price: Money::new(25000, "PHP") },
+        ];
+
+        assert_eq!(compute_item_discounts(&discount, &items), vec![2500, 0]);
+    }
+
+    #[test]
+    fn test_compute_item_discounts_fixed_spreads_over_items() {
+        let discount = create_discount(DISCOUNT_TYPE_FIXED, 15000, vec![]);
+        let items = vec![
+            DiscountableItem { event_ids: vec![ObjectId::new().to_hex()], bundle_id: None, price: Money::new(10000, "PHP") },
+            DiscountableItem { event_ids: vec![ObjectId::new().to_hex()], bundle_id: None, price: Money::new(10000, "PHP") },
+            DiscountableItem { event_ids: vec![ObjectId::new().to_hex()], bundle_id: None, price: Money::new(10000, "PHP") },
+        ];
+
+        assert_eq!(compute_item_discounts(&discount, &items), vec![10000, 5000, 0]);
+    }
+
+    #[test]
+    fn test_compute_item_discounts_fixed_skips_other_currencies() {
+        let discount = create_discount(DISCOUNT_TYPE_FIXED, 15000, vec![]);
+        let items = vec![
+            DiscountableItem { event_ids: vec![ObjectId::new().to_hex()], bundle_id: None, price: Money::new(10000, "USD") },
+            DiscountableItem { event_ids: vec![ObjectId::new().to_hex()], bundle_id: None, price: Money::new(10000, "PHP") },
+        ];
+
+        assert_eq!(compute_item_discounts(&discount, &items), vec![0, 10000]);
+    }
+
+    #[test]
+    fn test_compute_item_discounts_scoped_to_bundles() {
+        let bundle_id = ObjectId::new();
+        let discount = Discount {
+            bundle_ids: vec![bundle_id],
+            ..create_discount(DISCOUNT_TYPE_PERCENTAGE, 10, vec![])
+        };
+        let items = vec![
+            DiscountableItem { event_ids: vec![ObjectId::new().to_hex()], bundle_id: Some(bundle_id.to_hex()), price: Money::new(60000, "PHP") },
+            DiscountableItem { event_ids: vec![ObjectId::new().to_hex()], bundle_id: Some(ObjectId::new().to_hex()), price: Money::new(60000, "PHP") },
+            DiscountableItem { event_ids: vec![ObjectId::new().to_hex()], bundle_id: None, price: Money::new(25000, "PHP") },
+        ];
+
+        assert_eq!(compute_item_discounts(&discount, &items), vec![6000, 0, 0]);
+    }
+
+    #[test]
+    fn test_is_discount_active() {
+        let discount = create_discount(DISCOUNT_TYPE_FIXED, 100, vec![]);
+
+        assert!(is_discount_active(&discount, Utc::now()));
+        assert!(!is_discount_active(&discount, Utc::now() + chrono::Duration::days(2)));
+        assert!(!is_discount_active(&discount, Utc::now() - chrono::Duration::days(2)));
+    }
+}
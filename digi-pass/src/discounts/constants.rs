@@ -0,0 +1,2 @@
+pub const DISCOUNT_TYPE_PERCENTAGE: &str = "percentage";
+pub const DISCOUNT_TYPE_FIXED: &str = "fixed";
@@ -0,0 +1,21 @@
+use std::sync::Arc;
+use axum::{extract::State, Json};
+
+use jwt_authorizer::JwtClaims;
+use serde_json::Value;
+
+use crate::{app_state::AppState, helpers, validation::ValidatedJson};
+
+use super::{data_transfer_objects::{CreateDiscount, CreateDiscountResult}, errors::{DiscountError, DiscountErrors}};
+
+pub async fn create(
+    State(state): State<Arc<AppState>>,
+    JwtClaims(claims): JwtClaims<Value>,
+    ValidatedJson(data): ValidatedJson<CreateDiscount>,
+) -> Result<Json<CreateDiscountResult>, DiscountError> {
+    if !helpers::is_staff(&claims) {
+        return Err(DiscountError { error: DiscountErrors::NotStaff });
+    }
+    let result = state.discount_service.create_discount(data).await?;
+    Ok(Json(result))
+}
@@ -0,0 +1,54 @@
+use bson::oid::ObjectId;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::money::Money;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Discount {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub code: String,
+    pub discount_type: String,
+    pub value: i32,
+    #[serde(default)]
+    pub currency: Option<String>,
+    pub event_ids: Vec<ObjectId>,
+    /// Ticket tiers the code applies to, sold as bundles.
+    #[serde(default)]
+    pub bundle_ids: Vec<ObjectId>,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub valid_from: DateTime<Utc>,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub valid_until: DateTime<Utc>,
+    pub max_uses: Option<i32>,
+    pub max_uses_per_customer: Option<i32>,
+    pub uses: i32,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DiscountRedemption {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub discount_id: ObjectId,
+    pub code: String,
+    pub customer_id: String,
+    pub basket_id: String,
+    pub order_id: String,
+    pub amount: Money,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub redeemed_at: DateTime<Utc>,
+}
+
+/// How often a customer used a discount, counted up in a single update so its per customer limit holds
+/// under concurrent purchases.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DiscountCustomerUses {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub discount_id: ObjectId,
+    pub customer_id: String,
+    pub uses: i32,
+}
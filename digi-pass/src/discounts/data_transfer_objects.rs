@@ -0,0 +1,80 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use validator::{Validate, ValidationError};
+
+use crate::money::{self, Money};
+
+use super::constants::{DISCOUNT_TYPE_FIXED, DISCOUNT_TYPE_PERCENTAGE};
+
+#[derive(Debug, Validate, Deserialize)]
+#[validate(schema(function = "validate_create_discount", skip_on_field_errors = false))]
+pub struct CreateDiscount {
+    #[validate(length(min = 1))]
+    pub code: String,
+    pub discount_type: String,
+    #[validate(range(min = 1))]
+    pub value: i32,
+    /// Currency of a fixed discount's value, percentage discounts apply to any currency.
+    pub currency: Option<String>,
+    #[serde(default)]
+    pub event_ids: Vec<String>,
+    /// Leave both `event_ids` and `bundle_ids` empty for a code that applies to everything.
+    #[serde(default)]
+    pub bundle_ids: Vec<String>,
+    pub valid_from: DateTime<Utc>,
+    pub valid_until: DateTime<Utc>,
+    #[validate(range(min = 1))]
+    pub max_uses: Option<i32>,
+    #[validate(range(min = 1))]
+    pub max_uses_per_customer: Option<i32>,
+}
+
+fn validate_create_discount(create_discount: &&CreateDiscount) -> Result<(), ValidationError> {
+    if create_discount.discount_type != DISCOUNT_TYPE_PERCENTAGE && create_discount.discount_type != DISCOUNT_TYPE_FIXED {
+        return Err(ValidationError::new("discount_type must be percentage or fixed"));
+    }
+    if create_discount.discount_type == DISCOUNT_TYPE_PERCENTAGE && create_discount.value > 100 {
+        return Err(ValidationError::new("percentage value must not exceed 100"));
+    }
+    if let Some(currency) = &create_discount.currency {
+        if !money::is_valid_currency(currency) {
+            return Err(ValidationError::new("currency must be an ISO 4217 code"));
+        }
+    }
+    if create_discount.valid_from > create_discount.valid_until {
+        return Err(ValidationError::new("valid_from must be before valid_until"));
+    }
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CreateDiscountResult {
+    pub id: String,
+}
+
+/// A priced line the discount may apply to, e.g. a basket item.
+#[derive(Debug)]
+pub struct DiscountableItem {
+    pub event_ids: Vec<String>,
+    pub bundle_id: Option<String>,
+    pub price: Money,
+}
+
+/// The outcome of applying a code, with one entry in `item_discounts` per `DiscountableItem` given.
+/// Amounts are in the minor unit of the items' currency.
+#[derive(Debug)]
+pub struct AppliedDiscount {
+    pub discount_id: String,
+    pub code: String,
+    pub amount: i64,
+    pub item_discounts: Vec<i64>,
+}
+
+#[derive(Debug)]
+pub struct RedeemDiscount {
+    pub discount_id: String,
+    pub customer_id: String,
+    pub basket_id: String,
+    pub order_id: String,
+    pub amount: Money,
+}
@@ -0,0 +1,58 @@
+use axum::response::{IntoResponse, Response};
+use axum::http::StatusCode;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum DiscountErrors {
+    #[error("Discount code not found.")]
+    DiscountNotFound,
+    #[error("Discount code is not active.")]
+    DiscountNotActive,
+    #[error("Discount code has reached its usage limit.")]
+    UsageLimitReached,
+    #[error("Discount code has reached its usage limit for this customer.")]
+    CustomerUsageLimitReached,
+    #[error("Discount code does not apply to any basket item.")]
+    DiscountNotApplicable,
+    #[error("Only staff can manage discount codes.")]
+    NotStaff,
+    #[error("Discount code {0} already exists.")]
+    DuplicateCode(String),
+    #[error("{0}")]
+    Unknown(#[from] anyhow::Error)
+}
+
+pub struct DiscountError {
+    pub error: DiscountErrors,
+}
+
+
+// Tell axum how to convert `DiscountErrors` into a response.
+impl IntoResponse for DiscountError {
+    fn into_response(self) -> Response {
+        let status_code = match &self.error {
+            DiscountErrors::NotStaff => StatusCode::FORBIDDEN,
+            DiscountErrors::DuplicateCode(_) => StatusCode::CONFLICT,
+            DiscountErrors::Unknown(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::BAD_REQUEST,
+        };
+
+        (status_code, format!("{}", self.error)).into_response()
+    }
+}
+
+impl From<anyhow::Error> for DiscountError
+{
+    fn from(err: anyhow::Error) -> Self {
+        match err.downcast::<DiscountErrors>() {
+            Ok(err) => 
+            Self {
+                error: err,
+            },
+            Err(err) => 
+            Self {
+                error: DiscountErrors::Unknown(err).into(),
+            },
+        }
+    }
+}
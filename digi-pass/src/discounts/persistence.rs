@@ -0,0 +1,188 @@
+use async_trait::async_trait;
+use bson::{doc, oid::ObjectId};
+use mongodb::{error::{ErrorKind, WriteFailure}, options::{IndexOptions, UpdateOptions}, Client, Collection, IndexModel};
+
+use super::{data_models::{Discount, DiscountCustomerUses, DiscountRedemption}, errors::DiscountErrors};
+
+#[async_trait]
+pub trait DiscountRepository: Send + Sync {
+    async fn ensure_indexes(&self) -> anyhow::Result<()>;
+    /// Fails with `DiscountErrors::DuplicateCode` when the code is taken.
+    async fn add(&self, discount: &Discount) -> anyhow::Result<String>;
+    async fn get_by_code(&self, code: &str) -> anyhow::Result<Option<Discount>>;
+    async fn get(&self, id: ObjectId) -> anyhow::Result<Option<Discount>>;
+    async fn increment_uses(&self, discount: &Discount) -> anyhow::Result<bool>;
+    async fn decrement_uses(&self, id: ObjectId) -> anyhow::Result<()>;
+    /// Returns false when the customer already used the discount `max_uses_per_customer` times.
+    async fn increment_customer_uses(&self, discount_id: ObjectId, customer_id: &str, max_uses_per_customer: i32) -> anyhow::Result<bool>;
+    async fn decrement_customer_uses(&self, discount_id: ObjectId, customer_id: &str) -> anyhow::Result<()>;
+    async fn add_redemption(&self, redemption: &DiscountRedemption) -> anyhow::Result<()>;
+    async fn get_redemption_by_order_id(&self, order_id: &str) -> anyhow::Result<Option<DiscountRedemption>>;
+    async fn delete_redemption(&self, id: ObjectId) -> anyhow::Result<bool>;
+    async fn count_redemptions(&self, discount_id: ObjectId, customer_id: &str) -> anyhow::Result<u64>;
+}
+
+pub struct MongoDbDiscountRepository {
+    pub client: Client,
+    pub database: String,
+}
+
+impl MongoDbDiscountRepository {
+    pub fn new(client: Client, database: String) -> Self {
+        MongoDbDiscountRepository { client, database }
+    }
+
+    fn get_collection(&self) -> Collection<Discount> {
+        let database = self.client.database(&self.database[..]);
+        database.collection::<Discount>("Discounts")
+    }
+
+    fn get_redemption_collection(&self) -> Collection<DiscountRedemption> {
+        let database = self.client.database(&self.database[..]);
+        database.collection::<DiscountRedemption>("DiscountRedemptions")
+    }
+
+    fn get_customer_uses_collection(&self) -> Collection<DiscountCustomerUses> {
+        let database = self.client.database(&self.database[..]);
+        database.collection::<DiscountCustomerUses>("DiscountCustomerUses")
+    }
+}
+
+#[async_trait]
+impl DiscountRepository for MongoDbDiscountRepository {
+    async fn ensure_indexes(&self) -> anyhow::Result<()> {
+        let index = IndexModel::builder()
+            .keys(doc! {"code": 1})
+            .options(IndexOptions::builder().unique(true).build())
+            .build();
+        self.get_collection().create_index(index, None).await?;
+
+        let index = IndexModel::builder()
+            .keys(doc! {"discount_id": 1, "customer_id": 1})
+            .options(IndexOptions::builder().unique(true).build())
+            .build();
+        self.get_customer_uses_collection().create_index(index, None).await?;
+        Ok(())
+    }
+
+    async fn add(&self, discount: &Discount) -> anyhow::Result<String> {
+        let result = match self.get_collection().insert_one(discount, None).await {
+            Ok(result) => result,
+            Err(err) => match *err.kind {
+                // Duplicate key on the code index.
+                ErrorKind::Write(WriteFailure::WriteError(ref write_error)) if write_error.code == 11000 => {
+                    return Err(DiscountErrors::DuplicateCode(discount.code.clone()).into())
+                }
+                _ => return Err(err.into()),
+            },
+        };
+        let hex = result
+            .inserted_id
+            .as_object_id()
+            .ok_or(anyhow::anyhow!("Failed to get object id"))?
+            .to_hex();
+        Ok(hex)
+    }
+
+    async fn get_by_code(&self, code: &str) -> anyhow::Result<Option<Discount>> {
+        let discount = self
+            .get_collection()
+            .find_one(doc! {"code": code}, None)
+            .await?;
+        Ok(discount)
+    }
+
+    async fn get(&self, id: ObjectId) -> anyhow::Result<Option<Discount>> {
+        let discount = self
+            .get_collection()
+            .find_one(doc! {"_id": id}, None)
+            .await?;
+        Ok(discount)
+    }
+
+    async fn increment_uses(&self, discount: &Discount) -> anyhow::Result<bool> {
+        let id = discount.id.ok_or(anyhow::anyhow!("Failed to get object id"))?;
+        let filter = match discount.max_uses {
+            Some(max_uses) => doc! {"_id": id, "uses": {"$lt": max_uses}},
+            None => doc! {"_id": id},
+        };
+        let update_result = self
+            .get_collection()
+            .update_one(filter, doc! {"$inc": {"uses": 1}}, None)
+            .await?;
+        Ok(update_result.modified_count == 1)
+    }
+
+    async fn decrement_uses(&self, id: ObjectId) -> anyhow::Result<()> {
+        let _ = self
+            .get_collection()
+            .update_one(doc! {"_id": id, "uses": {"$gt": 0}}, doc! {"$inc": {"uses": -1}}, None)
+            .await?;
+        Ok(())
+    }
+
+    /// The first use inserts the counter. Once the limit is reached the filter no longer matches,
+    /// the upsert then collides with the existing counter on the unique index.
+    async fn increment_customer_uses(&self, discount_id: ObjectId, customer_id: &str, max_uses_per_customer: i32) -> anyhow::Result<bool> {
+        let options = UpdateOptions::builder().upsert(true).build();
+        let update_result = self
+            .get_customer_uses_collection()
+            .update_one(
+                doc! {"discount_id": discount_id, "customer_id": customer_id, "uses": {"$lt": max_uses_per_customer}},
+                doc! {"$inc": {"uses": 1}},
+                options,
+            )
+            .await;
+        match update_result {
+            Ok(_) => Ok(true),
+            Err(err) => match *err.kind {
+                ErrorKind::Write(WriteFailure::WriteError(ref write_error)) if write_error.code == 11000 => Ok(false),
+                _ => Err(err.into()),
+            },
+        }
+    }
+
+    async fn decrement_customer_uses(&self, discount_id: ObjectId, customer_id: &str) -> anyhow::Result<()> {
+        let _ = self
+            .get_customer_uses_collection()
+            .update_one(
+                doc! {"discount_id": discount_id, "customer_id": customer_id, "uses": {"$gt": 0}},
+                doc! {"$inc": {"uses": -1}},
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn add_redemption(&self, redemption: &DiscountRedemption) -> anyhow::Result<()> {
+        let _ = self
+            .get_redemption_collection()
+            .insert_one(redemption, None)
+            .await?;
+        Ok(())
+    }
+
+    async fn get_redemption_by_order_id(&self, order_id: &str) -> anyhow::Result<Option<DiscountRedemption>> {
+        let redemption = self
+            .get_redemption_collection()
+            .find_one(doc! {"order_id": order_id}, None)
+            .await?;
+        Ok(redemption)
+    }
+
+    async fn delete_redemption(&self, id: ObjectId) -> anyhow::Result<bool> {
+        let delete_result = self
+            .get_redemption_collection()
+            .delete_one(doc! {"_id": id}, None)
+            .await?;
+        Ok(delete_result.deleted_count == 1)
+    }
+
+    async fn count_redemptions(&self, discount_id: ObjectId, customer_id: &str) -> anyhow::Result<u64> {
+        let count = self
+            .get_redemption_collection()
+            .count_documents(doc! {"discount_id": discount_id, "customer_id": customer_id}, None)
+            .await?;
+        Ok(count)
+    }
+}
@@ -2,8 +2,10 @@ use std::sync::Arc;
 
 use mongodb::Client;
 
+use crate::money::Money;
+
 use super::{
-    data_models::Event, data_transfer_objects::{CreateEvent, CreateEventResult, EventDetails}, event_repository::{EventRepository, MongoDbEventRepository}
+    data_models::{AttendeeConfig, AttendeeQuestion, Event}, data_transfer_objects::{self, CreateEvent, CreateEventResult, EventDetails}, event_repository::{EventRepository, MongoDbEventRepository}
 };
 
 #[derive(Clone)]
@@ -45,7 +47,10 @@ fn map_event(data: CreateEvent) -> Event {
     Event {
         id: None,
         name: data.name,
-        price: data.price,
+        price: Money::new(data.price, &data.currency),
+        convenience_fee: Money::new(data.convenience_fee, &data.currency),
+        tax_rate: data.tax_rate,
+        attendee_config: map_attendee_config(data.attendee_config),
         start_sale_date_time: data.start_sale_date_time,
         end_sale_date_time: data.end_sale_date_time,
         start_date_time: data.start_date_time,
@@ -53,6 +58,22 @@ fn map_event(data: CreateEvent) -> Event {
     }
 }
 
+fn map_attendee_config(data: data_transfer_objects::AttendeeConfig) -> AttendeeConfig {
+    AttendeeConfig {
+        require_name: data.require_name,
+        require_email: data.require_email,
+        questions: data
+            .questions
+            .into_iter()
+            .map(|question| AttendeeQuestion {
+                id: question.id,
+                label: question.label,
+                required: question.required,
+            })
+            .collect(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -63,7 +84,11 @@ mod tests {
         let now = Utc::now();
         let create_event_data = CreateEvent {
             name: "Test Event".to_string(),
-            price: 0,
+            currency: "USD".to_string(),
+            price: 1500,
+            convenience_fee: 0,
+            tax_rate: 0,
+            attendee_config: Default::default(),
             start_sale_date_time: now,
             end_sale_date_time: now,
             start_date_time: now,
@@ -74,6 +99,8 @@ mod tests {
 
         assert_eq!(event.id, Option::None);
         assert_eq!(event.name, "Test Event".to_string());
+        assert_eq!(event.price, Money::new(1500, "USD"));
+        assert_eq!(event.convenience_fee, Money::new(0, "USD"));
         assert_eq!(event.start_sale_date_time, now);
         assert_eq!(event.end_sale_date_time, now);
         assert_eq!(event.start_date_time, now);
@@ -2,13 +2,22 @@ use bson::oid::ObjectId;
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
 
+use crate::money::Money;
+
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Event {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<ObjectId>,
     pub name: String,
-    pub price: i32,
+    #[serde(deserialize_with = "crate::money::deserialize_legacy_money")]
+    pub price: Money,
+    #[serde(default = "crate::money::default_legacy_money")]
+    pub convenience_fee: Money,
+    #[serde(default)]
+    pub tax_rate: i32,
+    #[serde(default)]
+    pub attendee_config: AttendeeConfig,
     #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
     pub start_sale_date_time: chrono::DateTime<Utc>,
     #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
@@ -19,4 +28,17 @@ pub struct Event {
     pub end_date_time: chrono::DateTime<Utc>,
 }
 
+/// What buyers have to tell us about each ticket holder.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct AttendeeConfig {
+    pub require_name: bool,
+    pub require_email: bool,
+    pub questions: Vec<AttendeeQuestion>,
+}
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AttendeeQuestion {
+    pub id: String,
+    pub label: String,
+    pub required: bool,
+}
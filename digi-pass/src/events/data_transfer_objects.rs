@@ -1,23 +1,49 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use validator::{Validate, ValidationError};
-use crate::helpers;
+use crate::{helpers, money::{self, Money}};
 
-use super::data_models::Event;
+use super::data_models::{self, Event};
 
 #[derive(Debug, Validate, Deserialize)]
 #[validate(schema(function = "validate_create_event", skip_on_field_errors = false))]
 pub struct  CreateEvent {
     #[validate(length(min = 1))]
     pub name: String,
+    /// ISO 4217 code all of the event's amounts are in.
+    #[validate(custom(function = "validate_currency"))]
+    #[serde(default = "default_currency")]
+    pub currency: String,
+    /// In the currency's minor unit.
     #[validate(range(min = 0))]
-    pub price: i32,
+    pub price: i64,
+    /// Charged on top of the price for every ticket.
+    #[validate(range(min = 0))]
+    #[serde(default)]
+    pub convenience_fee: i64,
+    /// Tax rate in basis points, e.g. 1200 for 12% VAT.
+    #[validate(range(min = 0, max = 10000))]
+    #[serde(default)]
+    pub tax_rate: i32,
+    #[serde(default)]
+    pub attendee_config: AttendeeConfig,
     pub start_sale_date_time: DateTime<Utc>,
     pub end_sale_date_time: DateTime<Utc>,
     pub start_date_time: DateTime<Utc>,
     pub end_date_time: DateTime<Utc>,
 }
 
+fn default_currency() -> String {
+    money::DEFAULT_CURRENCY.to_string()
+}
+
+fn validate_currency(currency: &str) -> Result<(), ValidationError> {
+    if !money::is_valid_currency(currency) {
+        return Err(ValidationError::new("currency must be an ISO 4217 code"));
+    }
+    Ok(())
+}
+
 fn validate_create_event(create_event:&&CreateEvent) -> Result<(), ValidationError> {
     if create_event.start_date_time > create_event.end_date_time {
         return Err(ValidationError::new("start_date_time must be before end_date_time"));
@@ -31,6 +57,15 @@ fn validate_create_event(create_event:&&CreateEvent) -> Result<(), ValidationErr
     if create_event.start_date_time < helpers::get_current_time() {
         return Err(ValidationError::new("start_date_time must be in the future"));
     }
+    let questions = &create_event.attendee_config.questions;
+    for (index, question) in questions.iter().enumerate() {
+        if question.id.is_empty() || question.label.is_empty() {
+            return Err(ValidationError::new("attendee questions must have an id and a label"));
+        }
+        if questions[..index].iter().any(|q| q.id == question.id) {
+            return Err(ValidationError::new("attendee question ids must be unique"));
+        }
+    }
     Ok(())
 }
 
@@ -43,13 +78,52 @@ pub struct CreateEventResult {
 pub struct  EventDetails {
     pub id: String,
     pub name: String,
-    pub price: i32,
+    pub price: Money,
+    pub convenience_fee: Money,
+    pub tax_rate: i32,
+    pub attendee_config: AttendeeConfig,
     pub start_sale_date_time: DateTime<Utc>,
     pub end_sale_date_time: DateTime<Utc>,
     pub start_date_time: DateTime<Utc>,
     pub end_date_time: DateTime<Utc>,
 }
 
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct AttendeeConfig {
+    #[serde(default)]
+    pub require_name: bool,
+    #[serde(default)]
+    pub require_email: bool,
+    #[serde(default)]
+    pub questions: Vec<AttendeeQuestion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AttendeeQuestion {
+    pub id: String,
+    pub label: String,
+    #[serde(default)]
+    pub required: bool,
+}
+
+impl From<&data_models::AttendeeConfig> for AttendeeConfig {
+    fn from(value: &data_models::AttendeeConfig) -> Self {
+        AttendeeConfig {
+            require_name: value.require_name,
+            require_email: value.require_email,
+            questions: value
+                .questions
+                .iter()
+                .map(|question| AttendeeQuestion {
+                    id: question.id.clone(),
+                    label: question.label.clone(),
+                    required: question.required,
+                })
+                .collect(),
+        }
+    }
+}
+
 impl  From<&Event> for EventDetails {
     fn from(value: &Event) -> Self {
         let id = match  value.id {
@@ -59,7 +133,10 @@ impl  From<&Event> for EventDetails {
         EventDetails {
             id,
             name: value.name.clone(),
-            price: value.price,
+            price: value.price.clone(),
+            convenience_fee: value.convenience_fee.clone(),
+            tax_rate: value.tax_rate,
+            attendee_config: (&value.attendee_config).into(),
             start_sale_date_time: value.start_sale_date_time,
             end_sale_date_time: value.end_sale_date_time,
             start_date_time: value.start_date_time,
@@ -79,7 +156,11 @@ mod validate_create_event_tests {
     fn test_validation_fails_on_empty_name() {
         let create_event = CreateEvent {
             name: "".to_string(),
+            currency: "PHP".to_string(),
             price: 0,
+            convenience_fee: 0,
+            tax_rate: 0,
+            attendee_config: AttendeeConfig::default(),
             start_sale_date_time: helpers::get_current_time(),
             end_sale_date_time: helpers::get_current_time(),
             start_date_time: helpers::get_current_time(),
@@ -93,7 +174,11 @@ mod validate_create_event_tests {
     fn test_validation_fails_on_start_sale_after_end_sale() {
         let create_event = CreateEvent {
             name: "Event".to_string(),
+            currency: "PHP".to_string(),
             price: 0,
+            convenience_fee: 0,
+            tax_rate: 0,
+            attendee_config: AttendeeConfig::default(),
             start_sale_date_time: helpers::get_current_time(),
             end_sale_date_time: helpers::get_current_time() - Duration::days(1),
             start_date_time: helpers::get_current_time(),
@@ -107,7 +192,11 @@ mod validate_create_event_tests {
     fn test_validation_fails_on_start_before_start_sale() {
         let create_event = CreateEvent {
             name: "Event".to_string(),
+            currency: "PHP".to_string(),
             price: 0,
+            convenience_fee: 0,
+            tax_rate: 0,
+            attendee_config: AttendeeConfig::default(),
             start_sale_date_time: helpers::get_current_time() + Duration::days(1),
             end_sale_date_time: helpers::get_current_time() + Duration::days(2),
             start_date_time: helpers::get_current_time(),
@@ -121,7 +210,11 @@ mod validate_create_event_tests {
     fn test_validation_fails_on_start_in_the_past() {
         let create_event = CreateEvent {
             name: "Event".to_string(),
+            currency: "PHP".to_string(),
             price: 0,
+            convenience_fee: 0,
+            tax_rate: 0,
+            attendee_config: AttendeeConfig::default(),
             start_sale_date_time: helpers::get_current_time() - Duration::days(1),
             end_sale_date_time: helpers::get_current_time(),
             start_date_time: helpers::get_current_time() - Duration::days(2),
@@ -131,11 +224,60 @@ mod validate_create_event_tests {
         assert!(create_event.validate().is_err());
     }
 
+    #[test]
+    fn test_validation_fails_on_invalid_currency() {
+        let create_event = CreateEvent {
+            name: "Event".to_string(),
+            currency: "peso".to_string(),
+            price: 0,
+            convenience_fee: 0,
+            tax_rate: 0,
+            attendee_config: AttendeeConfig::default(),
+            start_sale_date_time: helpers::get_current_time() - Duration::days(1),
+            end_sale_date_time: helpers::get_current_time(),
+            start_date_time: helpers::get_current_time() + Duration::days(1),
+            end_date_time: helpers::get_current_time() + Duration::days(2),
+        };
+
+        assert!(create_event.validate().is_err());
+    }
+
+    #[test]
+    fn test_validation_fails_on_duplicate_attendee_question() {
+        let question = AttendeeQuestion {
+            id: "size".to_string(),
+            label: "Shirt size".to_string(),
+            required: true,
+        };
+        let create_event = CreateEvent {
+            name: "Event".to_string(),
+            currency: "PHP".to_string(),
+            price: 0,
+            convenience_fee: 0,
+            tax_rate: 0,
+            attendee_config: AttendeeConfig {
+                require_name: true,
+                require_email: true,
+                questions: vec![question.clone(), question],
+            },
+            start_sale_date_time: helpers::get_current_time() - Duration::days(1),
+            end_sale_date_time: helpers::get_current_time(),
+            start_date_time: helpers::get_current_time() + Duration::days(1),
+            end_date_time: helpers::get_current_time() + Duration::days(2),
+        };
+
+        assert!(create_event.validate().is_err());
+    }
+
     #[test]
     fn test_validation_passes_on_valid_create_event() {
         let create_event = CreateEvent {
             name: "Event".to_string(),
+            currency: "PHP".to_string(),
             price: 0,
+            convenience_fee: 0,
+            tax_rate: 0,
+            attendee_config: AttendeeConfig::default(),
             start_sale_date_time: helpers::get_current_time() - Duration::days(1),
             end_sale_date_time: helpers::get_current_time(),
             start_date_time: helpers::get_current_time() + Duration::days(1),
@@ -15,3 +15,45 @@ pub fn get_current_time() -> chrono::DateTime<chrono::Utc> {
 }
 
 
+/// Gets the `sub` claim of the authenticated caller.
+pub fn get_subject(claims: &serde_json::Value) -> anyhow::Result<String> {
+    claims
+        .get("sub")
+        .and_then(|sub| sub.as_str())
+        .map(|sub| sub.to_string())
+        .ok_or(anyhow::anyhow!("Subject claim not found"))
+}
+
+
+/// Roles allowed to run back office actions such as refunds, captures and webhook replays.
+const STAFF_ROLES: [&str; 2] = ["admin", "staff"];
+
+/// Claims the identity provider may carry the caller's roles in.
+const ROLE_CLAIMS: [&str; 3] = ["cognito:groups", "groups", "roles"];
+
+/// Checks whether the authenticated caller has an admin or staff role.
+pub fn is_staff(claims: &serde_json::Value) -> bool {
+    ROLE_CLAIMS.iter()
+        .filter_map(|claim| claims.get(claim))
+        .any(|roles| match roles {
+            serde_json::Value::Array(roles) => roles.iter().any(|role| role.as_str().is_some_and(|role| STAFF_ROLES.contains(&role))),
+            serde_json::Value::String(role) => STAFF_ROLES.contains(&role.as_str()),
+            _ => false,
+        })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_is_staff() {
+        assert!(is_staff(&json!({ "sub": "admin", "cognito:groups": ["customers", "admin"] })));
+        assert!(is_staff(&json!({ "sub": "staff", "roles": "staff" })));
+        assert!(!is_staff(&json!({ "sub": "customer", "cognito:groups": ["customers"] })));
+        assert!(!is_staff(&json!({ "sub": "customer" })));
+    }
+}
@@ -4,4 +4,5 @@ mod inventory_repository;
 pub mod application;
 pub mod data_transfer_objects;
 pub mod constants;
+pub mod errors;
 pub mod controller;
\ No newline at end of file
@@ -7,8 +7,9 @@ use chrono::Utc;
 use mongodb::Client;
 use crate::events::application::EventService;
 
-use super::constants::{GENERATE_INVENTORY_STATUS_PENDING, INVENTORY_STATUS_AVAILABLE, INVENTORY_STATUS_RESERVED};
+use super::constants::{GENERATE_INVENTORY_STATUS_PENDING, INVENTORY_STATUS_AVAILABLE, INVENTORY_STATUS_CANCELLED, INVENTORY_STATUS_RESERVED, INVENTORY_STATUS_SOLD};
 use super::data_models::{GenerateInventory, Inventory};
+use super::errors::InventoryErrors;
 use super::data_transfer_objects::{
     CreateInventoryBatch, GenerateInventory as GenerateInventoryDto,
     GenerateInventoryResult, ReserveInventories, ReserveInventoriesResult, ReservedInventory,
@@ -59,7 +60,11 @@ impl InventoryService {
         let reserved_until = now + chrono::Duration::minutes(90);
         let mut inventories = self.inventory_repository.get_unreserved_inventories(reserve_inventories.event_id.clone(), reserve_inventories.quantity, now, INVENTORY_STATUS_AVAILABLE).await?;
         if inventories.len()  != reserve_inventories.quantity as usize {
-            return Err(anyhow::anyhow!("Not enough inventories: {:?}", inventories.len()));
+            return Err(InventoryErrors::NotEnoughInventories {
+                event_id: reserve_inventories.event_id.clone(),
+                requested: reserve_inventories.quantity,
+                available: inventories.len() as i32,
+            }.into());
         }
         for inventory in inventories.iter_mut() {
             inventory.reserved_until = reserved_until;
@@ -73,6 +78,80 @@ impl InventoryService {
                 .collect()
         })
     }
+
+    /// Returns reserved inventories to stock.
+    /// Only inventories still holding the given reservation are released, so an inventory that was re-reserved by someone else is left untouched.
+    pub async fn release_inventories(&self, reserved_inventories: &[ReservedInventory]) -> anyhow::Result<()> {
+        if reserved_inventories.is_empty() {
+            return Ok(());
+        }
+        let inventory_ids = reserved_inventories.iter()
+            .map(|reserved_inventory| ObjectId::from_str(&reserved_inventory.inventory_id))
+            .collect::<Result<Vec<ObjectId>, _>>()?;
+
+        let now = Utc::now();
+        let mut inventories = self.inventory_repository.get_inventories_by_ids(inventory_ids).await?;
+        inventories.retain(|inventory| is_reservation_held(inventory, reserved_inventories));
+        for inventory in inventories.iter_mut() {
+            inventory.reserved_until = now;
+            inventory.status = INVENTORY_STATUS_AVAILABLE.to_string();
+        }
+        self.inventory_repository.batch_update_reservations(&inventories).await?;
+        Ok(())
+    }
+
+    /// Pushes back the end of reservations that are still held, e.g. while a payment waits to be captured.
+    /// Returns the reservations that were extended, ones that already lapsed are left alone.
+    pub async fn extend_reservations(&self, reserved_inventories: &[ReservedInventory], reserved_until: chrono::DateTime<Utc>) -> anyhow::Result<Vec<ReservedInventory>> {
+        if reserved_inventories.is_empty() {
+            return Ok(vec![]);
+        }
+        let inventory_ids = reserved_inventories.iter()
+            .map(|reserved_inventory| ObjectId::from_str(&reserved_inventory.inventory_id))
+            .collect::<Result<Vec<ObjectId>, _>>()?;
+
+        let mut inventories = self.inventory_repository.get_inventories_by_ids(inventory_ids).await?;
+        inventories.retain(|inventory| is_reservation_held(inventory, reserved_inventories));
+        for inventory in inventories.iter_mut() {
+            inventory.reserved_until = reserved_until;
+        }
+        self.inventory_repository.batch_update_reservations(&inventories).await?;
+        Ok(inventories.iter()
+            .filter_map(|inventory| inventory.id.map(|id| ReservedInventory::new(id.to_hex(), inventory.reserved_until)))
+            .collect())
+    }
+
+    /// Puts refunded inventories back on sale when `restock` is set, otherwise takes them off sale for good.
+    /// The keeper may not have marked them sold yet, so reserved ones are handled the same way.
+    pub async fn return_refunded_inventories(&self, inventory_ids: &[String], restock: bool) -> anyhow::Result<()> {
+        if inventory_ids.is_empty() {
+            return Ok(());
+        }
+        let inventory_ids = inventory_ids.iter()
+            .map(|inventory_id| ObjectId::from_str(inventory_id))
+            .collect::<Result<Vec<ObjectId>, _>>()?;
+
+        let status = if restock { INVENTORY_STATUS_AVAILABLE } else { INVENTORY_STATUS_CANCELLED };
+        let now = Utc::now();
+        let mut inventories = self.inventory_repository.get_inventories_by_ids(inventory_ids).await?;
+        inventories.retain(|inventory| inventory.status == INVENTORY_STATUS_SOLD || inventory.status == INVENTORY_STATUS_RESERVED);
+        for inventory in inventories.iter_mut() {
+            inventory.reserved_until = now;
+            inventory.status = status.to_string();
+        }
+        self.inventory_repository.batch_update_reservations(&inventories).await?;
+        Ok(())
+    }
+}
+
+fn is_reservation_held(inventory: &Inventory, reserved_inventories: &[ReservedInventory]) -> bool {
+    if inventory.status != INVENTORY_STATUS_RESERVED {
+        return false;
+    }
+    reserved_inventories.iter().any(|reserved_inventory| {
+        inventory.id.map(|id| id.to_hex()).as_deref() == Some(&reserved_inventory.inventory_id[..])
+            && inventory.reserved_until.timestamp_millis() == reserved_inventory.reserved_until.timestamp_millis()
+    })
 }
 
 
@@ -1,7 +1,7 @@
 pub const INVENTORY_STATUS_AVAILABLE: &str = "Available";
 pub const INVENTORY_STATUS_RESERVED: &str = "Reserved";
-// pub const INVENTORY_STATUS_SOLD: &str = "Sold";
-// pub const INVENTORY_STATUS_CANCELLED: &str = "Cancelled";
+pub const INVENTORY_STATUS_SOLD: &str = "Sold";
+pub const INVENTORY_STATUS_CANCELLED: &str = "Cancelled";
 
 
 pub const GENERATE_INVENTORY_STATUS_PENDING: &str = "Pending";
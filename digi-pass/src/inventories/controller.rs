@@ -4,7 +4,7 @@ use axum::{extract::State, http::StatusCode, Json};
 
 use crate::{app_state::AppState, validation::ValidatedJson};
 
-use super::data_transfer_objects::{CreateInventoryBatch, GenerateInventory, GenerateInventoryResult, ReserveInventories, ReserveInventoriesResult};
+use super::{data_transfer_objects::{CreateInventoryBatch, GenerateInventory, GenerateInventoryResult, ReserveInventories, ReserveInventoriesResult}, errors::InventoryError};
 
 pub async fn generate_async(
     State(state): State<Arc<AppState>>,
@@ -30,7 +30,7 @@ pub async fn add_batch(
 pub async fn reserve_inventories(
     State(state): State<Arc<AppState>>,
     ValidatedJson(data): ValidatedJson<ReserveInventories>,
-) -> Result<Json<ReserveInventoriesResult>, AppError> {
+) -> Result<Json<ReserveInventoriesResult>, InventoryError> {
     let result = state.inventory_service.reserve_inventories(&data).await?;
     Ok(Json(result))
 }
@@ -0,0 +1,48 @@
+use axum::response::{IntoResponse, Response};
+use axum::http::StatusCode;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum InventoryErrors {
+    #[error("Not enough inventories for event {event_id}: requested {requested}, available {available}.")]
+    NotEnoughInventories {
+        event_id: String,
+        requested: i32,
+        available: i32,
+    },
+    #[error("{0}")]
+    Unknown(#[from] anyhow::Error)
+}
+
+pub struct InventoryError {
+    pub error: InventoryErrors,
+}
+
+
+// Tell axum how to convert `InventoryErrors` into a response.
+impl IntoResponse for InventoryError {
+    fn into_response(self) -> Response {
+        let status_code = match &self.error {
+            InventoryErrors::NotEnoughInventories { .. } => StatusCode::BAD_REQUEST,
+            InventoryErrors::Unknown(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        (status_code, format!("{}", self.error)).into_response()
+    }
+}
+
+impl From<anyhow::Error> for InventoryError
+{
+    fn from(err: anyhow::Error) -> Self {
+        match err.downcast::<InventoryErrors>() {
+            Ok(err) => 
+            Self {
+                error: err,
+            },
+            Err(err) => 
+            Self {
+                error: InventoryErrors::Unknown(err).into(),
+            },
+        }
+    }
+}
@@ -13,6 +13,7 @@ pub trait InventoryRepository : Send + Sync{
     async fn add_generate_inventory(&self, generate_inventory: &GenerateInventory) -> anyhow::Result<String>;
     async fn get_unreserved_inventories(&self, event_id: String, quantity: i32, cut_off: chrono::DateTime<chrono::Utc>, status: &str) -> anyhow::Result<Vec<Inventory>>;
     async fn batch_update_reservations(&self, inventories: &[Inventory]) -> anyhow::Result<()>;
+    async fn get_inventories_by_ids(&self, inventory_ids: Vec<ObjectId>) -> anyhow::Result<Vec<Inventory>>;
 }
 
 
@@ -76,6 +77,17 @@ impl InventoryRepository for MongoDbInventoryRepository {
         Ok(inventories)
     }
 
+    async fn get_inventories_by_ids(&self, inventory_ids: Vec<ObjectId>) -> anyhow::Result<Vec<Inventory>>{
+        let inventory_collection = self.get_inventory_collection();
+
+        let mut docs = inventory_collection.find(doc!{"_id": doc! { "$in": inventory_ids } }, None).await?;
+        let mut inventories: Vec<Inventory> = vec![];
+        while docs.advance().await? {
+            inventories.push(docs.deserialize_current()?)
+        }
+        Ok(inventories)
+    }
+
     async fn batch_update_reservations(&self, inventories: &[Inventory]) -> anyhow::Result<()>{
         let context = BatchUpdateReservationContext { database: self.database.clone(), collecion: self.collection.clone(), inventories};
         let _ = self.client.start_session(None).await?
@@ -3,9 +3,13 @@ mod inventories;
 mod app_state;
 mod validation;
 mod baskets;
+mod discounts;
+mod bundles;
 mod payments;
 mod orders;
 mod passes;
+mod money;
+mod scheduled_jobs;
 pub mod error;
 pub mod helpers;
 
@@ -16,7 +20,7 @@ use lambda_http::run;
 use dotenv::dotenv;
 
 use axum::{
-    routing::{get, post}, Json, Router
+    routing::{delete, get, post, put}, Json, Router
 };
 use mongodb::Client;
 use serde::Serialize;
@@ -28,6 +32,10 @@ use crate::{app_state::AppState, orders::application::OrderService, passes::appl
 use crate::inventories::application::InventoryService;
 use crate::events::application::EventService;
 use crate::baskets::application::BasketService;
+use crate::discounts::application::DiscountService;
+use crate::bundles::application::BundleService;
+use crate::payments::constants::{MAYA_ANY_WEBHOOK_IP, PAYMENT_PROVIDER_FAKE};
+use crate::payments::payment_providers::{fake_provider::{FakeCheckouts, FakeProvider}, maya_provider::MayaProvider, stripe_provider::StripeProvider, PaymentProvider};
 
 use jwt_authorizer::{JwtAuthorizer, Validation};
 use jwt_authorizer::{Authorizer, IntoLayer};
@@ -135,20 +143,42 @@ async fn main() {
         .await
         .expect("Failed creating mongodb client");
 
-    let maya_base_url = env::var("Maya__BaseUrl")
-    .expect("Maya base url not found.");
-
-    let maya_secret_base64 = env::var("Maya__SecretKeyBase64")
-    .expect("Maya secret key not found.");
+    // `Payments__Provider=Fake` replaces the real providers, local runs then need no credentials or network.
+    let fake_checkouts = match env::var("Payments__Provider").as_deref() {
+        Ok(PAYMENT_PROVIDER_FAKE) => Some(FakeCheckouts::default()),
+        _ => None,
+    };
+    let payment_providers: Vec<Arc<dyn PaymentProvider>> = match &fake_checkouts {
+        Some(fake_checkouts) => {
+            tracing::warn!("Using the fake payment provider, nothing is charged.");
+            let base_url = env::var("Fake__BaseUrl").unwrap_or("http://localhost:3000".to_string());
+            vec![Arc::new(FakeProvider::new(base_url, fake_checkouts.clone()))]
+        }
+        None => load_payment_providers(),
+    };
 
     
     let event_service = EventService::new(client.clone(), database.clone());
 
     let inventory_service = InventoryService::new(client.clone(), database.clone(), event_service.clone());
 
-    let basket_service = BasketService::new(client.clone(), database.clone(), inventory_service.clone(), event_service.clone());
+    let discount_service = DiscountService::new(client.clone(), database.clone());
+    discount_service.ensure_discount_indexes().await.expect("Failed creating discount indexes");
 
-    let payment_service = PaymentService::new(basket_service.clone(), maya_base_url, maya_secret_base64, client.clone(), database.clone() );
+    let bundle_service = BundleService::new(client.clone(), database.clone(), event_service.clone());
+
+    let basket_service = BasketService::new(client.clone(), database.clone(), inventory_service.clone(), event_service.clone(), discount_service.clone(), bundle_service.clone());
+
+    // Comma separated, e.g. "https://shop.example.com,http://localhost:5173".
+    let redirect_url_allowed_origins: Vec<String> = env::var("Payments__RedirectUrlAllowedOrigins")
+        .unwrap_or_default()
+        .split(',')
+        .map(|origin| origin.trim().to_string())
+        .filter(|origin| !origin.is_empty())
+        .collect();
+
+    let payment_service = PaymentService::new(basket_service.clone(), payment_providers, redirect_url_allowed_origins, client.clone(), database.clone() );
+    payment_service.ensure_webhook_inbox_indexes().await.expect("Failed creating webhook inbox indexes");
     
     let order_service = OrderService::new(client.clone(), database.clone());
 
@@ -160,11 +190,28 @@ async fn main() {
         event_service,
         inventory_service,
         basket_service,
+        discount_service,
+        bundle_service,
         payment_service,
         order_service,
-        pass_service
+        pass_service,
+        fake_checkouts,
     });
 
+    if is_running_in_lambda() {
+        if env::var("ScheduledJobs__Only").is_ok_and(|only| only == "true") {
+            tracing::info!("Running in lambda, starting scheduled jobs");
+            scheduled_jobs::run_in_lambda(state).await.expect("Failed running scheduled jobs in lambda");
+            return;
+        }
+    }else{
+        let interval_seconds = env::var("ScheduledJobs__IntervalSeconds")
+            .ok()
+            .and_then(|seconds| seconds.parse::<u64>().ok())
+            .unwrap_or(scheduled_jobs::DEFAULT_INTERVAL_SECONDS);
+        scheduled_jobs::spawn_scheduled_jobs(state.clone(), std::time::Duration::from_secs(interval_seconds));
+    }
+
     // build our application with a route
     let app = Router::<Arc<AppState>>::new()
         // `GET /` goes to `root`
@@ -195,10 +242,74 @@ async fn main() {
             "/baskets/purchase",
             post(self::baskets::basket_controller::post_purchase),
         )
+        .route(
+            "/baskets/:basket_id",
+            get(self::baskets::basket_controller::get),
+        )
+        .route(
+            "/baskets/:basket_id/items",
+            post(self::baskets::basket_controller::post_item),
+        )
+        .route(
+            "/baskets/:basket_id/items/:basket_item_id",
+            delete(self::baskets::basket_controller::delete_item),
+        )
+        .route(
+            "/baskets/:basket_id/discounts",
+            post(self::baskets::basket_controller::post_discount),
+        )
+        .route(
+            "/baskets/:basket_id/bundles",
+            post(self::baskets::basket_controller::post_bundle),
+        )
+        .route(
+            "/baskets/:basket_id/attendees",
+            put(self::baskets::basket_controller::put_attendees),
+        )
+        .route(
+            "/discounts",
+            post(self::discounts::controller::create),
+        )
+        .route(
+            "/bundles",
+            post(self::bundles::controller::create),
+        )
+        .route(
+            "/bundles/:bundle_id",
+            get(self::bundles::controller::get),
+        )
         .route(
             "/payments/checkout",
             post(self::payments::controller::checkout),
         )
+        .route(
+            "/orders/:order_id/refunds",
+            post(self::orders::controller::post_refund),
+        )
+        .route(
+            "/payments/:payment_id/capture",
+            post(self::payments::controller::capture_payment),
+        )
+        .route(
+            "/payments/:payment_id/void",
+            post(self::payments::controller::void_payment),
+        )
+        .route(
+            "/payments/reconciliations",
+            post(self::payments::controller::reconcile),
+        )
+        .route(
+            "/payments/webhooks/failed",
+            get(self::payments::controller::get_failed_webhooks),
+        )
+        .route(
+            "/payments/webhooks/retries",
+            post(self::payments::controller::retry_webhooks),
+        )
+        .route(
+            "/payments/webhooks/:webhook_id/replay",
+            post(self::payments::controller::replay_webhook),
+        )
         .route(
             "/passes/:order_transaction_item_inventory_id",
             get(self::passes::passes_controller::get),
@@ -212,6 +323,14 @@ async fn main() {
             "/payments/webhook/maya",
             post(self::payments::controller::maya_webhook),
         )
+        .route(
+            "/payments/webhook/stripe",
+            post(self::payments::controller::stripe_webhook),
+        )
+        .route(
+            "/payments/fake/checkouts/:checkout_id",
+            get(self::payments::controller::get_fake_checkout).post(self::payments::controller::post_fake_checkout),
+        )
         .route("/", get(index))
         .route("/version", get(index))
         .layer(
@@ -230,6 +349,41 @@ async fn main() {
     }
 }
 
+fn load_payment_providers() -> Vec<Arc<dyn PaymentProvider>> {
+    let maya_base_url = env::var("Maya__BaseUrl")
+    .expect("Maya base url not found.");
+
+    let maya_secret_base64 = env::var("Maya__SecretKeyBase64")
+    .expect("Maya secret key not found.");
+
+    // Comma separated, e.g. "13.229.160.234,3.1.199.75".
+    let maya_webhook_allowed_ips: Vec<String> = env::var("Maya__WebhookAllowedIps")
+        .unwrap_or_default()
+        .split(',')
+        .map(|ip| ip.trim().to_string())
+        .filter(|ip| !ip.is_empty())
+        .collect();
+    if maya_webhook_allowed_ips.is_empty() {
+        tracing::warn!("Maya webhook allowed ips not set, every Maya webhook is rejected.");
+    } else if maya_webhook_allowed_ips.iter().any(|ip| ip == MAYA_ANY_WEBHOOK_IP) {
+        tracing::warn!("Maya webhooks are accepted from any source ip, only use this for sandbox testing.");
+    }
+
+    let mut payment_providers: Vec<Arc<dyn PaymentProvider>> = vec![Arc::new(MayaProvider::new(maya_base_url, maya_secret_base64, maya_webhook_allowed_ips))];
+    // Stripe is optional, it is only offered when configured.
+    if let Ok(stripe_secret_key) = env::var("Stripe__SecretKey") {
+        payment_providers.push(Arc::new(StripeProvider::new(
+            env::var("Stripe__BaseUrl").unwrap_or("https://api.stripe.com".to_string()),
+            stripe_secret_key,
+            env::var("Stripe__WebhookSecret").expect("Stripe webhook secret not found."),
+            env::var("Stripe__SuccessUrl").expect("Stripe success url not found."),
+            env::var("Stripe__CancelUrl").expect("Stripe cancel url not found."),
+        )));
+    }
+
+    payment_providers
+}
+
 #[derive(Serialize)]
 struct Version {
     major: i32,
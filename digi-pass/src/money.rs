@@ -0,0 +1,201 @@
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+pub const DEFAULT_CURRENCY: &str = "PHP";
+
+#[derive(Error, Debug)]
+pub enum MoneyErrors {
+    #[error("Currency mismatch: expected {expected}, got {actual}.")]
+    CurrencyMismatch { expected: String, actual: String },
+    #[error("Invalid {currency} amount: {amount:?}.")]
+    InvalidAmount { amount: String, currency: String },
+}
+
+/// An amount in the currency's minor unit (e.g. centavos for PHP) together with its ISO 4217 code.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Money {
+    pub amount: i64,
+    pub currency: String,
+}
+
+impl Money {
+    pub fn new(amount: i64, currency: &str) -> Self {
+        Money {
+            amount,
+            currency: currency.to_string(),
+        }
+    }
+
+    pub fn zero(currency: &str) -> Self {
+        Money::new(0, currency)
+    }
+
+    /// Parses an amount in the currency's major unit, e.g. "1234.56" PHP as 123456.
+    /// The inverse of [`Money::to_decimal_string`], amounts with more decimals than the currency has are rejected.
+    pub fn from_decimal_str(amount: &str, currency: &str) -> Result<Self, MoneyErrors> {
+        let invalid = || MoneyErrors::InvalidAmount {
+            amount: amount.to_string(),
+            currency: currency.to_string(),
+        };
+        let exponent = minor_unit_exponent(currency);
+        let (negative, digits) = match amount.trim().strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, amount.trim()),
+        };
+        let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        let is_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+        if whole.is_empty() || !is_digits(whole) || !is_digits(fraction) || fraction.len() > exponent as usize {
+            return Err(invalid());
+        }
+
+        let whole: i64 = whole.parse().map_err(|_| invalid())?;
+        let fraction: i64 = format!("{:0<width$}", fraction, width = exponent as usize)
+            .parse()
+            .unwrap_or(0);
+        let minor = whole
+            .checked_mul(10i64.pow(exponent))
+            .and_then(|minor| minor.checked_add(fraction))
+            .ok_or_else(invalid)?;
+        Ok(Money::new(if negative { -minor } else { minor }, currency))
+    }
+
+    pub fn checked_add(&self, other: &Money) -> Result<Money, MoneyErrors> {
+        self.ensure_same_currency(other)?;
+        Ok(Money::new(self.amount + other.amount, &self.currency))
+    }
+
+    pub fn checked_sub(&self, other: &Money) -> Result<Money, MoneyErrors> {
+        self.ensure_same_currency(other)?;
+        Ok(Money::new(self.amount - other.amount, &self.currency))
+    }
+
+    pub fn ensure_same_currency(&self, other: &Money) -> Result<(), MoneyErrors> {
+        if self.currency != other.currency {
+            return Err(MoneyErrors::CurrencyMismatch {
+                expected: self.currency.clone(),
+                actual: other.currency.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Formats the amount in the currency's major unit, e.g. 123456 PHP as "1234.56".
+    /// Done on integers so providers get exactly what we charge.
+    pub fn to_decimal_string(&self) -> String {
+        let exponent = minor_unit_exponent(&self.currency);
+        let sign = if self.amount < 0 { "-" } else { "" };
+        let amount = self.amount.unsigned_abs();
+        if exponent == 0 {
+            return format!("{}{}", sign, amount);
+        }
+
+        let divisor = 10u64.pow(exponent);
+        format!(
+            "{}{}.{:0width$}",
+            sign,
+            amount / divisor,
+            amount % divisor,
+            width = exponent as usize
+        )
+    }
+}
+
+/// Reads amounts stored before `Money` existed, back then they were bare `i32` centavos and always in PHP.
+pub fn deserialize_legacy_money<'de, D>(deserializer: D) -> Result<Money, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StoredMoney {
+        Money(Money),
+        Legacy(i64),
+    }
+
+    Ok(match StoredMoney::deserialize(deserializer)? {
+        StoredMoney::Money(money) => money,
+        StoredMoney::Legacy(amount) => Money::new(amount, DEFAULT_CURRENCY),
+    })
+}
+
+/// Default for `Money` fields documents stored before they were added do not have.
+pub fn default_legacy_money() -> Money {
+    Money::zero(DEFAULT_CURRENCY)
+}
+
+/// Number of decimal places of the currency's minor unit as per ISO 4217.
+pub fn minor_unit_exponent(currency: &str) -> u32 {
+    match currency {
+        "BIF" | "CLP" | "DJF" | "GNF" | "ISK" | "JPY" | "KMF" | "KRW" | "PYG" | "RWF" | "UGX"
+        | "UYI" | "VND" | "VUV" | "XAF" | "XOF" | "XPF" => 0,
+        "BHD" | "IQD" | "JOD" | "KWD" | "LYD" | "OMR" | "TND" => 3,
+        _ => 2,
+    }
+}
+
+pub fn is_valid_currency(currency: &str) -> bool {
+    currency.len() == 3 && currency.chars().all(|c| c.is_ascii_uppercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_decimal_string() {
+        assert_eq!(Money::new(123456, "PHP").to_decimal_string(), "1234.56");
+        assert_eq!(Money::new(5, "PHP").to_decimal_string(), "0.05");
+        assert_eq!(Money::new(-1999, "USD").to_decimal_string(), "-19.99");
+        assert_eq!(Money::new(1500, "JPY").to_decimal_string(), "1500");
+        assert_eq!(Money::new(1005, "KWD").to_decimal_string(), "1.005");
+    }
+
+    #[test]
+    fn test_from_decimal_str() {
+        assert_eq!(Money::from_decimal_str("1234.56", "PHP").unwrap(), Money::new(123456, "PHP"));
+        assert_eq!(Money::from_decimal_str("1234.5", "PHP").unwrap(), Money::new(123450, "PHP"));
+        assert_eq!(Money::from_decimal_str("1234", "PHP").unwrap(), Money::new(123400, "PHP"));
+        assert_eq!(Money::from_decimal_str("-19.99", "USD").unwrap(), Money::new(-1999, "USD"));
+        assert_eq!(Money::from_decimal_str("1500", "JPY").unwrap(), Money::new(1500, "JPY"));
+        assert_eq!(Money::from_decimal_str("1.005", "KWD").unwrap(), Money::new(1005, "KWD"));
+        assert!(Money::from_decimal_str("1.005", "PHP").is_err());
+        assert!(Money::from_decimal_str("1500.5", "JPY").is_err());
+        assert!(Money::from_decimal_str("", "PHP").is_err());
+        assert!(Money::from_decimal_str("12a", "PHP").is_err());
+    }
+
+    #[test]
+    fn test_checked_add_rejects_mixed_currencies() {
+        let php = Money::new(100, "PHP");
+
+        assert_eq!(php.checked_add(&Money::new(50, "PHP")).unwrap(), Money::new(150, "PHP"));
+        assert!(php.checked_add(&Money::new(50, "USD")).is_err());
+        assert!(php.checked_sub(&Money::new(50, "USD")).is_err());
+    }
+
+    #[derive(Deserialize)]
+    struct StoredPrice {
+        #[serde(deserialize_with = "deserialize_legacy_money")]
+        price: Money,
+        #[serde(default = "default_legacy_money")]
+        fee: Money,
+    }
+
+    #[test]
+    fn test_deserialize_legacy_money() {
+        let legacy: StoredPrice = bson::from_document(bson::doc! { "price": 10000_i32, "currency": "PHP" }).unwrap();
+        assert_eq!(legacy.price, Money::new(10000, "PHP"));
+        assert_eq!(legacy.fee, Money::new(0, "PHP"));
+
+        let current: StoredPrice = bson::from_document(bson::doc! { "price": { "amount": 1999_i64, "currency": "USD" }, "fee": { "amount": 100_i64, "currency": "USD" } }).unwrap();
+        assert_eq!(current.price, Money::new(1999, "USD"));
+        assert_eq!(current.fee, Money::new(100, "USD"));
+    }
+
+    #[test]
+    fn test_is_valid_currency() {
+        assert!(is_valid_currency("PHP"));
+        assert!(!is_valid_currency("php"));
+        assert!(!is_valid_currency("PESO"));
+    }
+}
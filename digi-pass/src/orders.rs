@@ -1,5 +1,7 @@
 pub mod application;
 pub mod data_transfer_objects;
+pub mod controller;
+mod constants;
 mod data_models;
 mod persistence;
 mod errors;
\ No newline at end of file
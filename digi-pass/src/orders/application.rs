@@ -1,13 +1,13 @@
-use std::sync::Arc;
+use std::{collections::HashSet, sync::Arc};
 
 use anyhow::anyhow;
 use bson::oid::ObjectId;
 use chrono::Utc;
 use mongodb::Client;
 
-use crate::{baskets::data_transfer_objects::Basket, orders::data_models::{OrderTransaction, OrderTransactionItem, OrderTransactionItemInventory, OrderTransactionPayment}};
+use crate::{baskets::data_transfer_objects::{self as basket_dtos, Basket}, inventories::application::InventoryService, money::Money, orders::data_models::{OrderTransaction, OrderTransactionAttendee, OrderTransactionBundle, OrderTransactionAttendeeAnswer, OrderTransactionItem, OrderTransactionDiscount, OrderTransactionItemInventory, OrderTransactionPayment, OrderTransactionRefund}, payments::{application::PaymentService, constants::PAYMENT_TYPE_CHECKOUT, data_transfer_objects::PaymentStatus, errors::PaymentErrors}};
 
-use super::{data_transfer_objects, persistence::{MongoDbOrderTransactionRepository, OrderTransactionRepository}};
+use super::{constants::{ORDER_TRANSACTION_TYPE_REFUND, ORDER_TRANSACTION_TYPE_SALE}, data_transfer_objects::{self, RefundOrderRequest, RefundOrderResult}, errors::OrderErrors, persistence::{MongoDbOrderTransactionRepository, OrderTransactionRepository}};
 
 pub struct  OrderService {
     order_transaction_repository: Arc<dyn OrderTransactionRepository>,
@@ -44,11 +44,14 @@ impl OrderService {
         Ok(Some(mpa_order_transaction_to_dto(order_transaction)))
     }
 
-    pub async fn create_order(&self, basket: Basket) -> anyhow::Result<String> {
-        let order_id = match basket.original_order_id {
-            Some(id) => ObjectId::parse_str(id).map_err(|_| anyhow!("Invalid Original Order Id"))?,
-            None => ObjectId::new(),
-        }; 
+    pub async fn get_order_id_by_basket_id(&self, basket_id: &str) -> anyhow::Result<Option<String>> {
+        let order_transaction = self.order_transaction_repository.find_one_by_basket_id(basket_id, ORDER_TRANSACTION_TYPE_SALE).await?;
+        Ok(order_transaction.map(|order_transaction| order_transaction.order_id.to_hex()))
+    }
+
+    /// `order_id` is decided by the caller so a retried purchase can be matched to its order.
+    pub async fn create_order(&self, basket: Basket, order_id: &str) -> anyhow::Result<String> {
+        let order_id = ObjectId::parse_str(order_id).map_err(|_| anyhow!("Invalid Order Id"))?;
         let order_transaction_id = ObjectId::new();
 
         let mut items = vec![];
@@ -64,6 +67,7 @@ impl OrderService {
                     event_id: ObjectId::parse_str(basketed_inventory.event_id)?,
                     name: basketed_inventory.name,
                     created_at: Utc::now(),
+                    attendee: basketed_inventory.attendee.map(map_basket_attendee),
                 };
 
                 inventories.push(inventory);
@@ -74,7 +78,14 @@ impl OrderService {
                 id: order_transaction_item_id,
                 created_at: Utc::now(),
                 price: basket_item.price,
-                inventories
+                discount: basket_item.discount,
+                fee: basket_item.fee,
+                tax: basket_item.tax,
+                inventories,
+                bundle: basket_item.bundle.map(|bundle| OrderTransactionBundle {
+                    bundle_id: bundle.bundle_id,
+                    name: bundle.name,
+                }),
             };
             items.push(item);
             index = index + 1;
@@ -86,10 +97,9 @@ impl OrderService {
             let payment = OrderTransactionPayment {
                 id: format!("{}-{}", order_transaction_id, index),
                 payment_id: ObjectId::parse_str(&payment_view.id)?,
-                amount: payment_view.amount,
-                currency: payment_view.currency.clone(),
+                amount: payment_view.amount.clone(),
                 provider: payment_view.provider.clone(),
-                status: payment_view.status.clone(),
+                status: payment_view.status.to_string(),
                 payment_type: payment_view.payment_type.clone(),
                 created_at: Utc::now(),
             };
@@ -98,20 +108,195 @@ impl OrderService {
             payment_index = payment_index + 1;
         }
         
+        let mut discounts = vec![];
+        if let Some(discount) = basket.discount {
+            discounts.push(OrderTransactionDiscount {
+                discount_id: ObjectId::parse_str(&discount.discount_id)?,
+                code: discount.code,
+                amount: discount.amount,
+            });
+        }
+        
         let order_transaction = OrderTransaction {
             id: order_transaction_id,
             order_id,
-            r#type: "sale".to_string(),
+            r#type: ORDER_TRANSACTION_TYPE_SALE.to_string(),
             basket_id: Some(basket.id.to_string()),
+            customer_id: Some(basket.owner_id),
             items,
             payments,
+            discounts,
             created_at: Utc::now(),
+            refund: None,
+            refund_claimed_item_ids: vec![],
         };
         
         self.order_transaction_repository.save(&order_transaction).await?;
 
         return Ok(order_id.to_hex());
     }
+
+    /// Refunds the given items of an order through the provider of its payment and records it as a refund transaction.
+    pub async fn refund_order(
+        &self,
+        payment_service: &PaymentService,
+        inventory_service: &InventoryService,
+        order_id: &str,
+        refund_request: RefundOrderRequest,
+    ) -> anyhow::Result<RefundOrderResult> {
+        let order_id = ObjectId::parse_str(order_id).map_err(|_| OrderErrors::OrderNotFound)?;
+        let order_transactions = self.order_transaction_repository.find_by_order_id(order_id).await?;
+        let sale = order_transactions
+            .iter()
+            .find(|order_transaction| order_transaction.r#type == ORDER_TRANSACTION_TYPE_SALE)
+            .ok_or(OrderErrors::OrderNotFound)?;
+        let refunds: Vec<&OrderTransaction> = order_transactions
+            .iter()
+            .filter(|order_transaction| order_transaction.r#type == ORDER_TRANSACTION_TYPE_REFUND)
+            .collect();
+
+        let items = select_refund_items(sale, &refunds, &refund_request.item_ids)?;
+        let amount = compute_refund_amount(&items);
+        let payment = sale
+            .payments
+            .iter()
+            .find(|payment| payment.status == PaymentStatus::Paid.as_str() && payment.payment_type == PAYMENT_TYPE_CHECKOUT)
+            .ok_or(OrderErrors::NoRefundablePayment)?;
+
+        let item_ids: Vec<String> = items.iter().map(|item| item.id.clone()).collect();
+        if !self.order_transaction_repository.claim_refund_items(sale.id, &item_ids).await? {
+            return Err(OrderErrors::RefundInProgress.into());
+        }
+
+        let refund_payment = match payment_service
+            .refund_payment(&payment.payment_id.to_hex(), &amount, &refund_request.reason)
+            .await
+        {
+            Ok(refund_payment) => refund_payment,
+            Err(err) => {
+                // Nothing was refunded, the items can be refunded again.
+                if let Err(release_err) = self.order_transaction_repository.release_refund_items(sale.id, &item_ids).await {
+                    tracing::error!("Failed releasing refund claim on order {}: {:?}", order_id, release_err);
+                }
+                return Err(match err.downcast::<PaymentErrors>() {
+                    Ok(PaymentErrors::PaymentNotRefundable(reason)) => OrderErrors::RefundRejected(reason).into(),
+                    Ok(err) => err.into(),
+                    Err(err) => err,
+                });
+            }
+        };
+
+        let order_transaction_id = ObjectId::new();
+        let inventory_ids: Vec<String> = items
+            .iter()
+            .flat_map(|item| item.inventories.iter())
+            .map(|inventory| inventory.inventory_id.to_hex())
+            .collect();
+        let order_transaction = OrderTransaction {
+            id: order_transaction_id,
+            order_id,
+            r#type: ORDER_TRANSACTION_TYPE_REFUND.to_string(),
+            basket_id: sale.basket_id.clone(),
+            customer_id: sale.customer_id.clone(),
+            items,
+            payments: vec![OrderTransactionPayment {
+                id: format!("{}-1", order_transaction_id),
+                payment_id: ObjectId::parse_str(&refund_payment.id)?,
+                amount: refund_payment.amount,
+                provider: refund_payment.provider,
+                status: refund_payment.status.to_string(),
+                payment_type: refund_payment.payment_type,
+                created_at: Utc::now(),
+            }],
+            discounts: vec![],
+            created_at: Utc::now(),
+            refund: Some(OrderTransactionRefund {
+                reason: refund_request.reason,
+                restock: refund_request.restock,
+            }),
+            refund_claimed_item_ids: vec![],
+        };
+        self.order_transaction_repository.save(&order_transaction).await?;
+
+        // The money is already back with the customer, so a failure here is left for the keeper and support.
+        if let Err(err) = inventory_service
+            .return_refunded_inventories(&inventory_ids, refund_request.restock)
+            .await
+        {
+            tracing::error!("Failed returning inventories of refund {}: {:?}", order_transaction_id, err);
+        }
+
+        Ok(RefundOrderResult {
+            order_transaction_id: order_transaction_id.to_hex(),
+            amount,
+        })
+    }
+}
+
+/// Picks the sale items to refund, an empty `item_ids` means everything not refunded yet.
+/// Items another refund has claimed count as refunded.
+fn select_refund_items(
+    sale: &OrderTransaction,
+    refunds: &[&OrderTransaction],
+    item_ids: &[String],
+) -> Result<Vec<OrderTransactionItem>, OrderErrors> {
+    let refunded_item_ids: HashSet<&str> = refunds
+        .iter()
+        .flat_map(|refund| refund.items.iter())
+        .map(|item| item.id.as_str())
+        .chain(sale.refund_claimed_item_ids.iter().map(String::as_str))
+        .collect();
+
+    if item_ids.is_empty() {
+        let items: Vec<OrderTransactionItem> = sale
+            .items
+            .iter()
+            .filter(|item| !refunded_item_ids.contains(item.id.as_str()))
+            .cloned()
+            .collect();
+        if items.is_empty() {
+            return Err(OrderErrors::NothingToRefund);
+        }
+        return Ok(items);
+    }
+
+    let mut items = vec![];
+    for item_id in item_ids {
+        let item = sale
+            .items
+            .iter()
+            .find(|item| &item.id == item_id)
+            .ok_or(OrderErrors::OrderItemNotFound(item_id.clone()))?;
+        if refunded_item_ids.contains(item_id.as_str()) || items.iter().any(|i: &OrderTransactionItem| &i.id == item_id) {
+            return Err(OrderErrors::OrderItemAlreadyRefunded(item_id.clone()));
+        }
+        items.push(item.clone());
+    }
+    Ok(items)
+}
+
+/// Items in one order share a currency.
+fn compute_refund_amount(items: &[OrderTransactionItem]) -> Money {
+    let amount = items
+        .iter()
+        .map(|item| item.price.amount - item.discount.amount + item.fee.amount + item.tax.amount)
+        .sum();
+    Money::new(amount, &items[0].price.currency)
+}
+
+fn map_basket_attendee(attendee: basket_dtos::Attendee) -> OrderTransactionAttendee {
+    OrderTransactionAttendee {
+        name: attendee.name,
+        email: attendee.email,
+        answers: attendee
+            .answers
+            .into_iter()
+            .map(|answer| OrderTransactionAttendeeAnswer {
+                question_id: answer.question_id,
+                answer: answer.answer,
+            })
+            .collect(),
+    }
 }
 
 fn mpa_order_transaction_to_dto(order_transaction: OrderTransaction) -> data_transfer_objects::OrderTransaction {
@@ -121,9 +306,15 @@ fn mpa_order_transaction_to_dto(order_transaction: OrderTransaction) -> data_tra
         order_id: order_transaction.order_id.to_hex(),
         r#type: order_transaction.r#type.clone(),
         basket_id: order_transaction.basket_id.clone(),
+        customer_id: order_transaction.customer_id.clone(),
         items: order_transaction.items.iter().map(mpa_order_transaction_item_to_dto).collect(),
         payments: order_transaction.payments.iter().map(mpa_order_transaction_payment_to_dto).collect(),
+        discounts: order_transaction.discounts.iter().map(mpa_order_transaction_discount_to_dto).collect(),
         created_at: order_transaction.created_at,
+        refund: order_transaction.refund.as_ref().map(|refund| data_transfer_objects::OrderTransactionRefund {
+            reason: refund.reason.clone(),
+            restock: refund.restock,
+        }),
     };
 
     dto
@@ -133,8 +324,15 @@ fn mpa_order_transaction_item_to_dto(order_transaction_item: &OrderTransactionIt
     data_transfer_objects::OrderTransactionItem {
         id: order_transaction_item.id.clone(),
         created_at: order_transaction_item.created_at,
-        price: order_transaction_item.price,
+        price: order_transaction_item.price.clone(),
+        discount: order_transaction_item.discount.clone(),
+        fee: order_transaction_item.fee.clone(),
+        tax: order_transaction_item.tax.clone(),
         inventories: order_transaction_item.inventories.iter().map(mpa_order_transaction_item_inventory_to_dto).collect(),
+        bundle: order_transaction_item.bundle.as_ref().map(|bundle| data_transfer_objects::OrderTransactionBundle {
+            bundle_id: bundle.bundle_id.clone(),
+            name: bundle.name.clone(),
+        }),
     }
 }
 
@@ -145,6 +343,7 @@ fn mpa_order_transaction_item_inventory_to_dto(order_transaction_item_inventory:
         event_id: order_transaction_item_inventory.event_id.to_hex(),
         name: order_transaction_item_inventory.name.clone(),
         created_at: order_transaction_item_inventory.created_at,
+        attendee: order_transaction_item_inventory.attendee.as_ref().map(mpa_order_transaction_attendee_to_dto),
     }
 }
 
@@ -152,11 +351,88 @@ fn mpa_order_transaction_payment_to_dto(order_transaction_payment: &OrderTransac
     data_transfer_objects::OrderTransactionPayment {
         id: order_transaction_payment.id.clone(),
         payment_id: order_transaction_payment.payment_id.to_hex(),
-        amount: order_transaction_payment.amount,
-        currency: order_transaction_payment.currency.clone(),
+        amount: order_transaction_payment.amount.clone(),
         provider: order_transaction_payment.provider.clone(),
         status: order_transaction_payment.status.clone(),
         payment_type: order_transaction_payment.payment_type.clone(),
         created_at: order_transaction_payment.created_at,
     }
-}
\ No newline at end of file
+}
+
+fn mpa_order_transaction_discount_to_dto(order_transaction_discount: &OrderTransactionDiscount) -> data_transfer_objects::OrderTransactionDiscount {
+    data_transfer_objects::OrderTransactionDiscount {
+        discount_id: order_transaction_discount.discount_id.to_hex(),
+        code: order_transaction_discount.code.clone(),
+        amount: order_transaction_discount.amount.clone(),
+    }
+}
+
+fn mpa_order_transaction_attendee_to_dto(attendee: &OrderTransactionAttendee) -> data_transfer_objects::OrderTransactionAttendee {
+    data_transfer_objects::OrderTransactionAttendee {
+        name: attendee.name.clone(),
+        email: attendee.email.clone(),
+        answers: attendee
+            .answers
+            .iter()
+            .map(|answer| data_transfer_objects::OrderTransactionAttendeeAnswer {
+                question_id: answer.question_id.clone(),
+                answer: answer.answer.clone(),
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_item(id: &str, price: i64) -> OrderTransactionItem {
+        OrderTransactionItem {
+            id: id.to_string(),
+            created_at: Utc::now(),
+            price: Money::new(price, "PHP"),
+            discount: Money::new(100, "PHP"),
+            fee: Money::new(50, "PHP"),
+            tax: Money::new(0, "PHP"),
+            inventories: vec![],
+            bundle: None,
+        }
+    }
+
+    fn create_order_transaction(r#type: &str, items: Vec<OrderTransactionItem>) -> OrderTransaction {
+        OrderTransaction {
+            id: ObjectId::new(),
+            order_id: ObjectId::new(),
+            r#type: r#type.to_string(),
+            basket_id: None,
+            customer_id: None,
+            items,
+            payments: vec![],
+            discounts: vec![],
+            created_at: Utc::now(),
+            refund: None,
+            refund_claimed_item_ids: vec![],
+        }
+    }
+
+    #[test]
+    fn test_select_refund_items() {
+        let sale = create_order_transaction(ORDER_TRANSACTION_TYPE_SALE, vec![create_item("sale-1", 1000), create_item("sale-2", 2000)]);
+        let refund = create_order_transaction(ORDER_TRANSACTION_TYPE_REFUND, vec![create_item("sale-1", 1000)]);
+
+        let all = select_refund_items(&sale, &[], &[]).unwrap();
+        assert_eq!(all.len(), 2);
+        assert_eq!(compute_refund_amount(&all), Money::new(2900, "PHP"));
+
+        let rest = select_refund_items(&sale, &[&refund], &[]).unwrap();
+        assert_eq!(rest.iter().map(|item| item.id.as_str()).collect::<Vec<_>>(), vec!["sale-2"]);
+
+        assert!(matches!(select_refund_items(&sale, &[&refund], &["sale-1".to_string()]), Err(OrderErrors::OrderItemAlreadyRefunded(_))));
+        assert!(matches!(select_refund_items(&sale, &[], &["sale-2".to_string(), "sale-2".to_string()]), Err(OrderErrors::OrderItemAlreadyRefunded(_))));
+        assert!(matches!(select_refund_items(&sale, &[], &["sale-3".to_string()]), Err(OrderErrors::OrderItemNotFound(_))));
+        assert!(matches!(select_refund_items(&sale, &[&refund, &create_order_transaction(ORDER_TRANSACTION_TYPE_REFUND, vec![create_item("sale-2", 2000)])], &[]), Err(OrderErrors::NothingToRefund)));
+
+        let claimed_sale = OrderTransaction { refund_claimed_item_ids: vec!["sale-2".to_string()], ..sale };
+        assert!(matches!(select_refund_items(&claimed_sale, &[], &["sale-2".to_string()]), Err(OrderErrors::OrderItemAlreadyRefunded(_))));
+    }
+}
@@ -0,0 +1,3 @@
+pub const ORDER_TRANSACTION_TYPE_SALE: &str = "sale";
+
+pub const ORDER_TRANSACTION_TYPE_REFUND: &str = "refund";
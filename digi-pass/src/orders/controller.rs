@@ -0,0 +1,22 @@
+use std::sync::Arc;
+use axum::{extract::{Path, State}, Json};
+
+use jwt_authorizer::JwtClaims;
+use serde_json::Value;
+
+use crate::{app_state::AppState, helpers, validation::ValidatedJson};
+
+use super::{data_transfer_objects::{RefundOrderRequest, RefundOrderResult}, errors::{OrderError, OrderErrors}};
+
+pub async fn post_refund(
+    State(state): State<Arc<AppState>>,
+    JwtClaims(claims): JwtClaims<Value>,
+    Path(order_id): Path<String>,
+    ValidatedJson(data): ValidatedJson<RefundOrderRequest>,
+) -> Result<Json<RefundOrderResult>, OrderError> {
+    if !helpers::is_staff(&claims) {
+        return Err(OrderError { error: OrderErrors::NotStaff });
+    }
+    let result = state.order_service.refund_order(&state.payment_service, &state.inventory_service, &order_id, data).await?;
+    Ok(Json(result))
+}
@@ -1,6 +1,8 @@
 use bson::oid::ObjectId;
 use serde::{Deserialize, Serialize};
 
+use crate::money::Money;
+
 #[derive(Serialize, Deserialize)]
 pub struct OrderTransaction {
     #[serde(rename = "_id")]
@@ -8,17 +10,36 @@ pub struct OrderTransaction {
     pub order_id: ObjectId,
     pub r#type: String,
     pub basket_id: Option<String>,
+    #[serde(default)]
+    pub customer_id: Option<String>,
     pub items : Vec<OrderTransactionItem>,
     pub payments: Vec<OrderTransactionPayment>,
+    #[serde(default)]
+    pub discounts: Vec<OrderTransactionDiscount>,
     pub created_at: chrono::DateTime<chrono::Utc>,
+    /// Only set on refund transactions.
+    #[serde(default)]
+    pub refund: Option<OrderTransactionRefund>,
+    /// Only set on sale transactions, the items a refund was started for.
+    /// Claimed before the provider is asked for the money back so concurrent refunds cannot take the same item twice.
+    #[serde(default)]
+    pub refund_claimed_item_ids: Vec<String>,
+}
+
+/// Refund transactions carry copies of the refunded items under the ids they have in the sale.
+#[derive(Serialize, Deserialize)]
+pub struct OrderTransactionRefund {
+    pub reason: String,
+    /// Whether the refunded inventories went back on sale or were cancelled.
+    pub restock: bool,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct OrderTransactionPayment {
     pub id: String,
     pub payment_id: ObjectId,
-    pub amount: i32,
-    pub currency: String,
+    #[serde(deserialize_with = "crate::money::deserialize_legacy_money")]
+    pub amount: Money,
     pub provider: String,
     pub status: String,
     pub payment_type: String,
@@ -26,18 +47,55 @@ pub struct OrderTransactionPayment {
 }
 
 #[derive(Serialize, Deserialize)]
+pub struct OrderTransactionDiscount {
+    pub discount_id: ObjectId,
+    pub code: String,
+    pub amount: Money,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 pub struct OrderTransactionItem {
     pub id: String,
     pub created_at: chrono::DateTime<chrono::Utc>,
-    pub price: i32,
+    #[serde(deserialize_with = "crate::money::deserialize_legacy_money")]
+    pub price: Money,
+    #[serde(default = "crate::money::default_legacy_money")]
+    pub discount: Money,
+    #[serde(default = "crate::money::default_legacy_money")]
+    pub fee: Money,
+    #[serde(default = "crate::money::default_legacy_money")]
+    pub tax: Money,
     pub inventories: Vec<OrderTransactionItemInventory>,
+    #[serde(default)]
+    pub bundle: Option<OrderTransactionBundle>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
+pub struct OrderTransactionBundle {
+    pub bundle_id: String,
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 pub struct OrderTransactionItemInventory {
     pub id: String,
     pub inventory_id: ObjectId,
     pub event_id: ObjectId,
     pub name: String,
     pub created_at: chrono::DateTime<chrono::Utc>,
-}
\ No newline at end of file
+    #[serde(default)]
+    pub attendee: Option<OrderTransactionAttendee>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct OrderTransactionAttendee {
+    pub name: Option<String>,
+    pub email: Option<String>,
+    pub answers: Vec<OrderTransactionAttendeeAnswer>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct OrderTransactionAttendeeAnswer {
+    pub question_id: String,
+    pub answer: String,
+}
@@ -1,4 +1,7 @@
 use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use crate::money::Money;
 
 #[derive(Serialize, Deserialize)]
 pub struct OrderTransaction {
@@ -6,29 +9,57 @@ pub struct OrderTransaction {
     pub order_id: String,
     pub r#type: String,
     pub basket_id: Option<String>,
+    #[serde(default)]
+    pub customer_id: Option<String>,
     pub items : Vec<OrderTransactionItem>,
     pub payments: Vec<OrderTransactionPayment>,
+    pub discounts: Vec<OrderTransactionDiscount>,
     pub created_at: chrono::DateTime<chrono::Utc>,
+    #[serde(default)]
+    pub refund: Option<OrderTransactionRefund>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct OrderTransactionRefund {
+    pub reason: String,
+    pub restock: bool,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct OrderTransactionPayment {
     pub id: String,
     pub payment_id: String,
-    pub amount: i32,
-    pub currency: String,
+    pub amount: Money,
     pub provider: String,
     pub status: String,
     pub payment_type: String,
     pub created_at: chrono::DateTime<chrono::Utc>,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct OrderTransactionDiscount {
+    pub discount_id: String,
+    pub code: String,
+    pub amount: Money,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct OrderTransactionItem {
     pub id: String,
     pub created_at: chrono::DateTime<chrono::Utc>,
-    pub price: i32,
+    pub price: Money,
+    pub discount: Money,
+    pub fee: Money,
+    pub tax: Money,
     pub inventories: Vec<OrderTransactionItemInventory>,
+    #[serde(default)]
+    pub bundle: Option<OrderTransactionBundle>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct OrderTransactionBundle {
+    pub bundle_id: String,
+    pub name: String,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -38,4 +69,40 @@ pub struct OrderTransactionItemInventory {
     pub event_id: String,
     pub name: String,
     pub created_at: chrono::DateTime<chrono::Utc>,
-}
\ No newline at end of file
+    #[serde(default)]
+    pub attendee: Option<OrderTransactionAttendee>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct OrderTransactionAttendee {
+    pub name: Option<String>,
+    pub email: Option<String>,
+    pub answers: Vec<OrderTransactionAttendeeAnswer>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct OrderTransactionAttendeeAnswer {
+    pub question_id: String,
+    pub answer: String,
+}
+
+#[derive(Validate, Deserialize, Debug)]
+pub struct RefundOrderRequest {
+    /// Ids of the sale's items to refund, every item not refunded yet when empty.
+    #[serde(default)]
+    pub item_ids: Vec<String>,
+    #[validate(length(min = 1))]
+    pub reason: String,
+    #[serde(default = "default_restock")]
+    pub restock: bool,
+}
+
+fn default_restock() -> bool {
+    true
+}
+
+#[derive(Serialize, Debug)]
+pub struct RefundOrderResult {
+    pub order_transaction_id: String,
+    pub amount: Money,
+}
@@ -4,6 +4,22 @@ use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum OrderErrors {
+    #[error("Order not found.")]
+    OrderNotFound,
+    #[error("Order item {0} not found.")]
+    OrderItemNotFound(String),
+    #[error("Order item {0} is already refunded.")]
+    OrderItemAlreadyRefunded(String),
+    #[error("Order has nothing left to refund.")]
+    NothingToRefund,
+    #[error("Another refund of the same items is in progress.")]
+    RefundInProgress,
+    #[error("Order has no paid payment to refund.")]
+    NoRefundablePayment,
+    #[error("{0}")]
+    RefundRejected(String),
+    #[error("Only staff can manage orders.")]
+    NotStaff,
     #[error("{0}")]
     Unknown(#[from] anyhow::Error)
 }
@@ -17,6 +33,14 @@ pub struct OrderError {
 impl IntoResponse for OrderError {
     fn into_response(self) -> Response {
         let status_code = match &self.error {
+            OrderErrors::OrderNotFound => StatusCode::NOT_FOUND,
+            OrderErrors::OrderItemNotFound(_) => StatusCode::BAD_REQUEST,
+            OrderErrors::OrderItemAlreadyRefunded(_) => StatusCode::BAD_REQUEST,
+            OrderErrors::NothingToRefund => StatusCode::BAD_REQUEST,
+            OrderErrors::RefundInProgress => StatusCode::CONFLICT,
+            OrderErrors::NoRefundablePayment => StatusCode::BAD_REQUEST,
+            OrderErrors::RefundRejected(_) => StatusCode::BAD_REQUEST,
+            OrderErrors::NotStaff => StatusCode::FORBIDDEN,
             OrderErrors::Unknown(_) => StatusCode::INTERNAL_SERVER_ERROR,
         };
 
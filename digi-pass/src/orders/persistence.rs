@@ -9,6 +9,10 @@ use super::data_models::OrderTransaction;
 pub trait OrderTransactionRepository: Send + Sync {
     async fn get(&self, id: ObjectId) -> anyhow::Result<Option<OrderTransaction>>;
     async fn save(&self, order_transaction: &OrderTransaction) -> anyhow::Result<()>;
+    async fn find_one_by_basket_id(&self, basket_id: &str, r#type: &str) -> anyhow::Result<Option<OrderTransaction>>;
+    async fn find_by_order_id(&self, order_id: ObjectId) -> anyhow::Result<Vec<OrderTransaction>>;
+    async fn claim_refund_items(&self, id: ObjectId, item_ids: &[String]) -> anyhow::Result<bool>;
+    async fn release_refund_items(&self, id: ObjectId, item_ids: &[String]) -> anyhow::Result<()>;
 }
 
 pub struct MongoDbOrderTransactionRepository {
@@ -45,4 +49,41 @@ impl OrderTransactionRepository for MongoDbOrderTransactionRepository {
         let _ = self.get_collection().insert_one(order_transaction, None).await?;
         Ok(())
     }
-}
\ No newline at end of file
+    async fn find_one_by_basket_id(&self, basket_id: &str, r#type: &str) -> anyhow::Result<Option<OrderTransaction>>{
+        let order_transaction = self.get_collection().find_one(doc! {
+            "basket_id": basket_id,
+            "type": r#type
+        }, None).await?;
+        Ok(order_transaction)
+    }
+    async fn find_by_order_id(&self, order_id: ObjectId) -> anyhow::Result<Vec<OrderTransaction>>{
+        let mut cursor = self.get_collection().find(doc! {
+            "order_id": order_id
+        }, None).await?;
+        let mut order_transactions = vec![];
+        while cursor.advance().await? {
+            order_transactions.push(cursor.deserialize_current()?);
+        }
+        Ok(order_transactions)
+    }
+
+    /// Only one refund can claim an item, the claim fails if any of the items is already claimed.
+    async fn claim_refund_items(&self, id: ObjectId, item_ids: &[String]) -> anyhow::Result<bool> {
+        let update_result = self.get_collection().update_one(doc! {
+            "_id": id,
+            "refund_claimed_item_ids": {"$nin": item_ids}
+        }, doc! {
+            "$addToSet": {"refund_claimed_item_ids": {"$each": item_ids}}
+        }, None).await?;
+        Ok(update_result.modified_count == 1)
+    }
+
+    async fn release_refund_items(&self, id: ObjectId, item_ids: &[String]) -> anyhow::Result<()> {
+        let _ = self.get_collection().update_one(doc! {
+            "_id": id
+        }, doc! {
+            "$pullAll": {"refund_claimed_item_ids": item_ids}
+        }, None).await?;
+        Ok(())
+    }
+}
@@ -8,7 +8,7 @@ use mongodb::Client;
 
 use crate::orders::{application::OrderService, data_transfer_objects::OrderTransaction};
 
-use super::{data_models::{Pass, PassVerification}, data_transfer_objects::JwtPass, persistence::{MongoDbPassVerificationRepository, PassVerificationRepository}};
+use super::{data_models::{Pass, PassAttendee, PassAttendeeAnswer, PassVerification}, data_transfer_objects::JwtPass, persistence::{MongoDbPassVerificationRepository, PassVerificationRepository}};
 
 pub struct PassService{
     encoding_key: EncodingKey,
@@ -118,6 +118,18 @@ fn assemble_pass(order_transaction_item_inventory_id: &str, order_transaction: &
             inventory_id: inventory_item.inventory_id.clone(),
             event_id: inventory_item.event_id.clone(),
             event_name: inventory_item.name.clone(),
+            attendee: inventory_item.attendee.as_ref().map(|attendee| PassAttendee {
+                name: attendee.name.clone(),
+                email: attendee.email.clone(),
+                answers: attendee
+                    .answers
+                    .iter()
+                    .map(|answer| PassAttendeeAnswer {
+                        question_id: answer.question_id.clone(),
+                        answer: answer.answer.clone(),
+                    })
+                    .collect(),
+            }),
         };
 
         Some(pass)
@@ -12,6 +12,22 @@ pub struct Pass {
     pub inventory_id: String,
     pub event_id: String,
     pub event_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attendee: Option<PassAttendee>,
+}
+
+/// Lets gate staff match the pass against the holder's ID.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PassAttendee {
+    pub name: Option<String>,
+    pub email: Option<String>,
+    pub answers: Vec<PassAttendeeAnswer>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PassAttendeeAnswer {
+    pub question_id: String,
+    pub answer: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
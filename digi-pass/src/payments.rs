@@ -1,8 +1,11 @@
- mod payment_providers;
+pub mod payment_providers;
  mod data_models;
- mod constants;
  mod persistence;
+pub mod constants;
+pub mod errors;
 pub mod application;
 pub mod webhook_handlers;
+pub mod webhook_inbox;
+pub mod reconciliation;
 pub mod data_transfer_objects;
 pub mod controller;
\ No newline at end of file
@@ -1,109 +1,755 @@
-use std::{str::FromStr, sync::Arc};
+use std::{collections::HashMap, str::FromStr, sync::Arc};
 
+use axum::http::HeaderMap;
 use bson::oid::ObjectId;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use mongodb::Client;
 
 use crate::{
-    baskets::application::BasketService, payments::constants::{PAYMENT_STATUS_PENDING, PAYMENT_TYPE_CHECKOUT}}
+    baskets::{application::BasketService, data_transfer_objects::Basket}, money::Money, payments::constants::PAYMENT_TYPE_CHECKOUT}
 ;
 
 use super::{
-    constants::{CURRENCY, PAYMENT_STATUS_PAID},
-    data_models::Payment,
-    data_transfer_objects::{CheckoutRequest, CheckoutResponse, PaymentView},
-    payment_providers::{maya_provider::MayaProvider, PaymentProvider}, persistence::{MongoDbPaymentRepository, PaymentRepository},
+    constants::{CHECKOUT_REUSE_MINUTES, IDEMPOTENCY_KEY_MAX_LENGTH, PAYMENT_PROVIDER_FREE, PAYMENT_TYPE_REFUND, RECONCILE_PAGE_SIZE, WEBHOOK_LEASE_SECONDS, WEBHOOK_MAX_ATTEMPTS, WEBHOOK_RETRY_BASE_SECONDS},
+    data_models::{CheckoutData, Payment, RefundData, WebhookInboxEntry},
+    data_transfer_objects::{CaptureMode, CheckoutBuyer, CheckoutRequest, CheckoutResponse, RedirectUrls, PaymentMismatch, PaymentReview, PaymentStatus, PaymentView, ReconciliationReport, WebhookStatus, WebhookView},
+    errors::PaymentErrors,
+    payment_providers::{CheckoutOptions, CheckoutStatus, PaymentProvider},
+    persistence::{MongoDbPaymentRepository, MongoDbWebhookInboxRepository, PaymentRepository, WebhookInboxRepository},
 };
 
 #[derive(Clone)]
 pub struct PaymentService {
     basket_service: BasketService,
-    payment_provider: Arc<dyn PaymentProvider>,
+    /// Checkouts use this provider unless the request names another one.
+    default_provider: String,
+    payment_providers: HashMap<String, Arc<dyn PaymentProvider>>,
     payment_repository: Arc<dyn PaymentRepository>,
+    webhook_inbox_repository: Arc<dyn WebhookInboxRepository>,
+    /// Origins customers may be redirected to after checkout, e.g. "https://shop.example.com".
+    redirect_url_allowed_origins: Vec<String>,
 }
 
 impl PaymentService {
+    /// The first provider is the default.
     pub fn new(
         basket_service: BasketService,
-        provider_base_url: String,
-        secret_base64: String,
+        payment_providers: Vec<Arc<dyn PaymentProvider>>,
+        redirect_url_allowed_origins: Vec<String>,
         client: Client, 
         database: String
     ) -> Self {
-        let payment_provider = Arc::new(MayaProvider::new(
-            provider_base_url,
-            secret_base64,
-        ));
+        let default_provider = payment_providers
+            .first()
+            .map(|provider| provider.get_name())
+            .expect("At least one payment provider is required");
+        let payment_providers = payment_providers
+            .into_iter()
+            .map(|provider| (provider.get_name(), provider))
+            .collect();
 
         let payment_repository = Arc::new(
             MongoDbPaymentRepository::new(
-                client,
-                database,
+                client.clone(),
+                database.clone(),
         ));
+        let webhook_inbox_repository = Arc::new(MongoDbWebhookInboxRepository::new(client, database));
         Self {
             basket_service,
-            payment_provider,
+            default_provider,
+            payment_providers,
             payment_repository,
+            webhook_inbox_repository,
+            redirect_url_allowed_origins,
         }
     }
 
+    /// The inbox relies on a unique index to drop redelivered webhooks.
+    pub async fn ensure_webhook_inbox_indexes(&self) -> anyhow::Result<()> {
+        self.webhook_inbox_repository.ensure_indexes().await
+    }
+
+    /// Hands out the basket's open checkout instead of creating another one, so double submits do not end up
+    /// with several checkouts that could all be paid. A retried request with the same `idempotency_key`
+    /// gets its checkout back.
     pub async fn create_checkout(
         &self,
         checkout_request: CheckoutRequest,
+        customer_id: &str,
+        idempotency_key: Option<String>,
     ) -> anyhow::Result<CheckoutResponse> {
 
+        let payment_provider = self.get_provider(
+            checkout_request.provider.as_deref().unwrap_or(&self.default_provider),
+        )?;
+
         tracing::info!("Getting Basekt {}", &checkout_request.basket_id);
         let basket = self
             .basket_service
             .get_valid_basket(&checkout_request.basket_id)
             .await?
-            .ok_or(anyhow::anyhow!(
-                "Basket not found: {:?}",
-                &checkout_request.basket_id
-            ))?;
+            .ok_or(PaymentErrors::BasketNotFound)?;
+
+        if basket.owner_id.is_empty() || basket.owner_id != customer_id {
+            return Err(PaymentErrors::BasketNotOwned.into());
+        }
+
+        if let Some(redirect_urls) = &checkout_request.redirect_urls {
+            check_redirect_urls(redirect_urls, &self.redirect_url_allowed_origins)?;
+        }
+
+        let basket_payments = self.payment_repository.find_by_basket_id(&basket.id).await?;
+        if let Some(payment) = idempotency_key
+            .as_deref()
+            .and_then(|idempotency_key| find_payment_by_idempotency_key(&basket_payments, idempotency_key))
+        {
+            tracing::info!("Returning checkout of payment {:?} for its Idempotency-Key", payment.id);
+            return map_payment_to_checkout_response(payment);
+        }
+
+        if is_free_basket(&basket) {
+            return self.create_free_checkout(&basket, idempotency_key).await;
+        }
+
+        let provider_name = payment_provider.get_name();
+        let now = Utc::now();
+        let (reusable, stale): (Vec<Payment>, Vec<Payment>) = basket_payments
+            .into_iter()
+            .filter(is_open_checkout)
+            .partition(|payment| is_checkout_reusable(payment, &provider_name, checkout_request.capture_mode, &basket.price, now));
+        for payment in stale {
+            self.expire_checkout(payment, "The checkout was replaced by a newer one.").await;
+        }
+        if !reusable.is_empty() {
+            tracing::info!("Reusing open checkout of basket {}", basket.id);
+            return self.settle_open_checkouts(&basket.id).await;
+        }
 
         tracing::info!("Creating Checkout");
-        let checkout_data = self.payment_provider.prepare_checkout(&basket).await?;
+        let options = CheckoutOptions {
+            redirect_urls: checkout_request.redirect_urls,
+            buyer: checkout_request.buyer,
+            capture_mode: checkout_request.capture_mode,
+        };
+        let checkout_data = payment_provider.prepare_checkout(&basket, &options).await?;
 
         let payment = Payment::new(
             Some(ObjectId::from_str(&basket.id)?),
-            basket.price,
-            CURRENCY.to_string(),
-            self.payment_provider.get_name(),
-            PAYMENT_STATUS_PENDING.to_string(),
+            basket.price.clone(),
+            provider_name,
+            PaymentStatus::Pending,
             Utc::now(),
             PAYMENT_TYPE_CHECKOUT.to_string(),
-            Some(checkout_data.clone()),
-        );
+            Some(checkout_data),
+        )
+        .with_capture_mode(checkout_request.capture_mode)
+        .with_idempotency_key(idempotency_key);
+
+        self.save_checkout_payment(&basket, payment).await?;
+
+        self.settle_open_checkouts(&basket.id).await
+    }
+
+    /// The payment is stored before the basket is claimed, so a change to the basket either fails because of
+    /// the claim or sees the payment and is refused. A payment for a basket that changed since it was priced
+    /// is expired again.
+    async fn save_checkout_payment(&self, basket: &Basket, mut payment: Payment) -> anyhow::Result<()> {
+        let id = self.payment_repository.save(&payment).await?;
+        payment.id = Some(ObjectId::parse_str(&id)?);
+
+        if self.basket_service.claim_for_checkout(&basket.id, &basket.concurrency_stamp).await? {
+            return Ok(());
+        }
+        tracing::info!("Basket {} changed during checkout", basket.id);
+        self.expire_checkout(payment, "The basket changed during checkout.").await;
+        Err(PaymentErrors::BasketChanged.into())
+    }
+
+    /// Another request for the basket may have created a checkout at the same time. Every request keeps
+    /// the oldest open checkout and expires the rest, so they all hand out the same one.
+    async fn settle_open_checkouts(&self, basket_id: &str) -> anyhow::Result<CheckoutResponse> {
+        let mut open_checkouts: Vec<Payment> = self
+            .payment_repository
+            .find_by_basket_id(basket_id)
+            .await?
+            .into_iter()
+            .filter(is_open_checkout)
+            .collect();
+        open_checkouts.sort_by_key(|payment| (payment.created_at, payment.id));
+        let mut open_checkouts = open_checkouts.into_iter();
+        let payment = open_checkouts
+            .next()
+            .ok_or(anyhow::anyhow!("Checkout of basket {} was closed before it was handed out", basket_id))?;
+        for newer_payment in open_checkouts {
+            self.expire_checkout(newer_payment, "The checkout was replaced by a newer one.").await;
+        }
+        map_payment_to_checkout_response(&payment)
+    }
+
+    /// Best effort, should the customer still pay the checkout the payment is recorded anyway since the money arrived.
+    async fn expire_checkout(&self, mut payment: Payment, reason: &str) {
+        let Some(checkout_id) = payment.checkout_data.as_ref().map(|data| data.checkout_id.clone()) else {
+            return;
+        };
+        tracing::info!("Expiring {} checkout {}", payment.provider, checkout_id);
+        // Free checkouts never reached a provider.
+        let expired = match self.get_provider(&payment.provider) {
+            _ if payment.provider == PAYMENT_PROVIDER_FREE => Ok(()),
+            Ok(payment_provider) => payment_provider.expire_checkout(&checkout_id).await,
+            Err(err) => Err(err),
+        };
+        if let Err(err) = expired {
+            tracing::warn!("Failed expiring {} checkout {}: {:?}", payment.provider, checkout_id, err);
+        }
+
+        payment.status = PaymentStatus::Expired;
+        payment.failure_reason = Some(reason.to_string());
+        if let Err(err) = self.payment_repository.update(&mut payment).await {
+            // Most likely a webhook updated the payment in the meantime.
+            tracing::warn!("Failed marking checkout {} expired: {:?}", checkout_id, err);
+        }
+    }
+
+    /// Providers reject a total of zero, so baskets with nothing to pay get a payment that is paid right away.
+    async fn create_free_checkout(&self, basket: &Basket, idempotency_key: Option<String>) -> anyhow::Result<CheckoutResponse> {
+        tracing::info!("Creating free Checkout");
+        let checkout_id = ObjectId::new().to_hex();
+        let payment = Payment::new(
+            Some(ObjectId::from_str(&basket.id)?),
+            basket.price.clone(),
+            PAYMENT_PROVIDER_FREE.to_string(),
+            PaymentStatus::Paid,
+            Utc::now(),
+            PAYMENT_TYPE_CHECKOUT.to_string(),
+            Some(CheckoutData::new(checkout_id.clone(), String::new())),
+        )
+        .with_idempotency_key(idempotency_key);
 
-        self.payment_repository.save(&payment).await?;
+        self.save_checkout_payment(basket, payment).await?;
 
-        Ok(CheckoutResponse::new(
-            checkout_data.checkout_id,
-            checkout_data.checkout_url,
-        ))
+        Ok(CheckoutResponse::free(checkout_id))
     }
 
-    pub async fn mark_payment_as_paid(&self, checkout_id: &str) -> anyhow::Result<()> {
+    pub fn verify_webhook(&self, provider: &str, headers: &HeaderMap, body: &[u8]) -> anyhow::Result<()> {
+        self.get_provider(provider)?
+            .verify_webhook(headers, body)
+            .map_err(|err| {
+                tracing::error!("Invalid {} webhook: {:?}", provider, err);
+                PaymentErrors::InvalidWebhook.into()
+            })
+    }
+
+    pub async fn get_status(&self, provider: &str, checkout_id: &str) -> anyhow::Result<CheckoutStatus> {
+        self.get_provider(provider)?.get_status(checkout_id).await
+    }
+
+    /// Returns the id of the basket the payment was made for, or `None` when the payment was flagged for review
+    /// instead, because `paid_amount` differs from what we charged or the payment was no longer pending or authorized.
+    pub async fn mark_payment_as_paid(&self, provider: &str, checkout_id: &str, paid_amount: &Money) -> anyhow::Result<Option<String>> {
+        let mut payment = self.get_checkout_payment(provider, checkout_id).await?;
         
-        let mut payment = self
+        match payment.status {
+            PaymentStatus::ReviewRequired => {
+                tracing::warn!("Payment {:?} is awaiting review, not marking it paid", checkout_id);
+                return Ok(None);
+            }
+            // Retried after the payment was recorded, the basket may still have to be purchased.
+            PaymentStatus::Paid => {}
+            status if !can_become_paid(status) => {
+                // The money arrived for a payment we gave up on, someone has to decide what happens to it.
+                tracing::error!("Payment {:?} is {} but {} reported it paid, flagging it for review", checkout_id, status, provider);
+                payment.status = PaymentStatus::ReviewRequired;
+                payment.paid_amount = Some(paid_amount.clone());
+                payment.failure_reason = Some(format!("{} reported the payment paid after it was {}.", provider, status));
+                self.payment_repository.update(&mut payment).await?;
+                return Ok(None);
+            }
+            _ => {
+                if self.flag_amount_mismatch(&mut payment, paid_amount).await? {
+                    return Ok(None);
+                }
+
+                payment.status = PaymentStatus::Paid;
+                self.payment_repository.update(&mut payment).await?;
+            }
+        }
+
+        let basket_id = payment
+            .basket_id
+            .ok_or(anyhow::anyhow!("Payment has no basket: checkout_id:{:?}", checkout_id))?;
+        Ok(Some(basket_id.to_hex()))
+    }
+
+    /// Records that the money of a manual capture checkout is held. Returns the id of the basket the payment
+    /// was made for, or `None` when nothing changed or the amount differs and the payment was flagged for review.
+    pub async fn mark_payment_as_authorized(&self, provider: &str, checkout_id: &str, authorized_amount: &Money) -> anyhow::Result<Option<String>> {
+        let mut payment = self.get_checkout_payment(provider, checkout_id).await?;
+        if payment.status != PaymentStatus::Pending {
+            tracing::warn!("Payment {:?} is {}, not marking it authorized", checkout_id, payment.status);
+            return Ok(None);
+        }
+
+        if self.flag_amount_mismatch(&mut payment, authorized_amount).await? {
+            return Ok(None);
+        }
+
+        payment.status = PaymentStatus::Authorized;
+        self.payment_repository.update(&mut payment).await?;
+        let basket_id = payment
+            .basket_id
+            .ok_or(anyhow::anyhow!("Payment has no basket: checkout_id:{:?}", checkout_id))?;
+        Ok(Some(basket_id.to_hex()))
+    }
+
+    /// Takes the held money of an authorized payment. The payment is paid afterwards, its basket can be purchased.
+    pub async fn capture_payment(&self, payment_id: &str) -> anyhow::Result<PaymentView> {
+        let (mut payment, checkout_id) = self.get_authorized_payment(payment_id).await?;
+        self.get_provider(&payment.provider)?
+            .capture(&checkout_id, &payment.amount)
+            .await?;
+
+        payment.status = PaymentStatus::Paid;
+        self.payment_repository.update(&mut payment).await?;
+        Ok(map_payment_to_payment_view(&payment))
+    }
+
+    /// Releases the held money of an authorized payment, nothing is charged.
+    pub async fn void_payment(&self, payment_id: &str, reason: &str) -> anyhow::Result<PaymentView> {
+        let (mut payment, checkout_id) = self.get_authorized_payment(payment_id).await?;
+        self.get_provider(&payment.provider)?
+            .void(&checkout_id, reason)
+            .await?;
+
+        payment.status = PaymentStatus::Voided;
+        payment.failure_reason = Some(get_failure_reason(&payment.provider, PaymentStatus::Voided));
+        self.payment_repository.update(&mut payment).await?;
+        Ok(map_payment_to_payment_view(&payment))
+    }
+
+    async fn get_authorized_payment(&self, payment_id: &str) -> anyhow::Result<(Payment, String)> {
+        let payment_id = ObjectId::parse_str(payment_id).map_err(|_| PaymentErrors::PaymentNotFound)?;
+        let payment = self
+            .payment_repository
+            .find_one_by_id(payment_id)
+            .await?
+            .ok_or(PaymentErrors::PaymentNotFound)?;
+        match (payment.status, &payment.checkout_data) {
+            (PaymentStatus::Authorized, Some(checkout_data)) => {
+                let checkout_id = checkout_data.checkout_id.clone();
+                Ok((payment, checkout_id))
+            }
+            _ => Err(PaymentErrors::PaymentNotAuthorized.into()),
+        }
+    }
+
+    async fn get_checkout_payment(&self, provider: &str, checkout_id: &str) -> anyhow::Result<Payment> {
+        let payment = self
             .payment_repository
             .find_one_by_checkout_id(checkout_id)
             .await?
             .ok_or(anyhow::anyhow!("Payment not found: checkout_id:{:?}", checkout_id))?;
-        
-        payment.status = PAYMENT_STATUS_PAID.to_string();
+        // Checkout ids are only unique per provider.
+        if payment.provider != provider {
+            return Err(anyhow::anyhow!("Payment {:?} was not made through {}", checkout_id, provider));
+        }
+        Ok(payment)
+    }
 
-        self.payment_repository.update(&mut payment).await?;
-        Ok(())
+    /// Flags the payment for review when the provider reported a different amount or currency than we charged.
+    async fn flag_amount_mismatch(&self, payment: &mut Payment, reported_amount: &Money) -> anyhow::Result<bool> {
+        if *reported_amount == payment.amount {
+            return Ok(false);
+        }
+        tracing::error!(
+            "Payment {:?} was charged {} {} but {} reported {} {}",
+            payment.checkout_data.as_ref().map(|data| &data.checkout_id),
+            payment.amount.to_decimal_string(),
+            payment.amount.currency,
+            payment.provider,
+            reported_amount.to_decimal_string(),
+            reported_amount.currency
+        );
+        payment.status = PaymentStatus::ReviewRequired;
+        payment.paid_amount = Some(reported_amount.clone());
+        self.payment_repository.update(payment).await?;
+        Ok(true)
     }
     
+    /// Stores the webhook before anything else is done with it. Returns `None` when the provider already sent this event.
+    pub async fn store_webhook(&self, provider: &str, event_id: &str, body: &[u8]) -> anyhow::Result<Option<WebhookInboxEntry>> {
+        let mut entry = WebhookInboxEntry::new(
+            provider.to_string(),
+            event_id.to_string(),
+            String::from_utf8_lossy(body).into_owned(),
+            Utc::now(),
+        );
+        let Some(id) = self.webhook_inbox_repository.save_if_new(&entry).await? else {
+            tracing::info!("Dropping redelivered {} webhook {}", provider, event_id);
+            return Ok(None);
+        };
+        entry.id = Some(ObjectId::parse_str(&id)?);
+        Ok(Some(entry))
+    }
+
+    /// Counts the attempt and leases the webhook to it, so a crash midway is retried once the lease runs out.
+    /// Fails when someone else claimed the webhook first.
+    pub async fn claim_webhook(&self, entry: &mut WebhookInboxEntry) -> anyhow::Result<()> {
+        entry.attempts += 1;
+        entry.status = WebhookStatus::Processing;
+        entry.next_attempt_at = Utc::now() + chrono::Duration::seconds(WEBHOOK_LEASE_SECONDS);
+        self.webhook_inbox_repository.update(entry).await
+    }
+
+    pub async fn complete_webhook(&self, entry: &mut WebhookInboxEntry, result: &anyhow::Result<()>) -> anyhow::Result<()> {
+        match result {
+            Ok(()) => {
+                entry.status = WebhookStatus::Processed;
+                entry.last_error = None;
+            }
+            Err(err) => {
+                entry.last_error = Some(format!("{:#}", err));
+                if entry.attempts >= WEBHOOK_MAX_ATTEMPTS {
+                    entry.status = WebhookStatus::Failed;
+                } else {
+                    entry.status = WebhookStatus::Pending;
+                    entry.next_attempt_at = get_next_attempt_at(entry.attempts, Utc::now());
+                }
+            }
+        }
+        self.webhook_inbox_repository.update(entry).await
+    }
+
+    pub async fn get_due_webhooks(&self) -> anyhow::Result<Vec<WebhookInboxEntry>> {
+        self.webhook_inbox_repository.find_due(Utc::now()).await
+    }
+
+    pub async fn get_failed_webhooks(&self) -> anyhow::Result<Vec<WebhookView>> {
+        let entries = self.webhook_inbox_repository.find_failed().await?;
+        Ok(entries.iter().map(map_webhook_inbox_entry_to_webhook_view).collect())
+    }
+
+    /// Makes the webhook due again, it gets one more attempt even if it ran out of them.
+    pub async fn reset_webhook_for_replay(&self, webhook_id: &str) -> anyhow::Result<WebhookInboxEntry> {
+        let webhook_id = ObjectId::parse_str(webhook_id).map_err(|_| PaymentErrors::WebhookNotFound)?;
+        let mut entry = self
+            .webhook_inbox_repository
+            .find_one_by_id(webhook_id)
+            .await?
+            .ok_or(PaymentErrors::WebhookNotFound)?;
+        if entry.status == WebhookStatus::Processed {
+            return Err(PaymentErrors::WebhookAlreadyProcessed.into());
+        }
+
+        entry.status = WebhookStatus::Pending;
+        entry.attempts = entry.attempts.min(WEBHOOK_MAX_ATTEMPTS - 1);
+        entry.next_attempt_at = Utc::now();
+        self.webhook_inbox_repository.update(&mut entry).await?;
+        Ok(entry)
+    }
+
+    /// Records that the provider will not take the payment, the basket is no longer locked by it
+    /// and the customer can check out again. Returns the id of the basket when the payment changed.
+    pub async fn mark_payment_as_unsuccessful(&self, provider: &str, checkout_id: &str, status: PaymentStatus) -> anyhow::Result<Option<String>> {
+        let mut payment = self.get_checkout_payment(provider, checkout_id).await?;
+        if !can_become_unsuccessful(payment.status, status) {
+            tracing::warn!("Payment {:?} is {}, not marking it {}", checkout_id, payment.status, status);
+            return Ok(None);
+        }
+
+        payment.status = status;
+        payment.failure_reason = Some(get_failure_reason(provider, status));
+        self.payment_repository.update(&mut payment).await?;
+        Ok(payment.basket_id.map(|basket_id| basket_id.to_hex()))
+    }
+
+    /// Asks the providers about pending payments created before `created_before` and applies what they report.
+    /// Payments the provider still reports as pending are left alone, paid or authorized ones for a different
+    /// amount are flagged for review.
+    pub async fn reconcile_pending_payments(&self, created_before: DateTime<Utc>) -> anyhow::Result<ReconciliationReport> {
+        let mut report = ReconciliationReport::default();
+        let mut after_id = None;
+        loop {
+            let payments = self
+                .payment_repository
+                .find_pending_created_before(created_before, after_id, RECONCILE_PAGE_SIZE)
+                .await?;
+            let is_last_page = (payments.len() as i64) < RECONCILE_PAGE_SIZE;
+            after_id = payments.last().and_then(|payment| payment.id);
+            report.checked += payments.len();
+
+            for mut payment in payments {
+                let payment_id = payment.id.map(|id| id.to_hex()).unwrap_or_default();
+                let Some(checkout_id) = payment.checkout_data.as_ref().map(|data| data.checkout_id.clone()) else {
+                    report.errors.push(format!("Payment {} has no checkout", payment_id));
+                    continue;
+                };
+                let CheckoutStatus { status: provider_status, amount } = match self.get_status(&payment.provider, &checkout_id).await {
+                    Ok(checkout_status) => checkout_status,
+                    Err(err) => {
+                        tracing::error!("Failed getting status of payment {}: {:?}", payment_id, err);
+                        report.errors.push(format!("Payment {}: {}", payment_id, err));
+                        continue;
+                    }
+                };
+                if provider_status == PaymentStatus::Pending {
+                    continue;
+                }
+
+                // Checked like the webhooks do, a payment with a different amount is left for review instead of purchased.
+                if matches!(provider_status, PaymentStatus::Paid | PaymentStatus::Authorized) {
+                    let Some(amount) = amount else {
+                        report.errors.push(format!("Payment {}: {} reported no amount", payment_id, payment.provider));
+                        continue;
+                    };
+                    match self.flag_amount_mismatch(&mut payment, &amount).await {
+                        Ok(false) => {}
+                        Ok(true) => continue,
+                        Err(err) => {
+                            report.errors.push(format!("Payment {}: {}", payment_id, err));
+                            continue;
+                        }
+                    }
+                }
+
+                let local_status = std::mem::replace(&mut payment.status, provider_status);
+                if provider_status.is_unsuccessful() {
+                    payment.failure_reason = Some(get_failure_reason(&payment.provider, provider_status));
+                }
+                if let Err(err) = self.payment_repository.update(&mut payment).await {
+                    // Most likely a webhook updated the payment in the meantime.
+                    report.errors.push(format!("Payment {}: {}", payment_id, err));
+                    continue;
+                }
+                report.mismatches.push(PaymentMismatch {
+                    payment_id,
+                    basket_id: payment.basket_id.map(|id| id.to_hex()).unwrap_or_default(),
+                    provider: payment.provider.clone(),
+                    checkout_id,
+                    local_status,
+                    provider_status,
+                    order_id: None,
+                });
+            }
+
+            if is_last_page || after_id.is_none() {
+                break;
+            }
+        }
+
+        report.reviews_required = self
+            .payment_repository
+            .find_by_status(PaymentStatus::ReviewRequired)
+            .await?
+            .iter()
+            .filter_map(map_payment_to_payment_review)
+            .collect();
+
+        Ok(report)
+    }
+
+    /// Gives back `amount` of a paid checkout through its provider and records it as a refund payment.
+    pub async fn refund_payment(&self, payment_id: &str, amount: &Money, reason: &str) -> anyhow::Result<PaymentView> {
+        let payment_id = ObjectId::parse_str(payment_id).map_err(|_| PaymentErrors::PaymentNotFound)?;
+        let payment = self
+            .payment_repository
+            .find_one_by_id(payment_id)
+            .await?
+            .ok_or(PaymentErrors::PaymentNotFound)?;
+        let checkout_id = match (payment.status, &payment.checkout_data) {
+            (PaymentStatus::Paid, Some(checkout_data)) => checkout_data.checkout_id.clone(),
+            _ => return Err(PaymentErrors::PaymentNotRefundable("Only paid checkouts can be refunded.".to_string()).into()),
+        };
+        payment.amount.ensure_same_currency(amount)?;
+
+        let refunds = self.payment_repository.find_refunds(payment_id).await?;
+        let refundable = get_refundable_amount(&payment, &refunds);
+        // Free payments only have zero to give back, the refund is still recorded for the returned items.
+        let is_free = payment.provider == PAYMENT_PROVIDER_FREE;
+        if amount.amount < 0 || (amount.amount == 0 && !is_free) || amount.amount > refundable {
+            return Err(PaymentErrors::PaymentNotRefundable(format!(
+                "Refund of {} exceeds the refundable {}.",
+                amount.to_decimal_string(),
+                Money::new(refundable, &amount.currency).to_decimal_string()
+            )).into());
+        }
+
+        let refund_id = match is_free {
+            true => ObjectId::new().to_hex(),
+            false => {
+                self.get_provider(&payment.provider)?
+                    .refund(&checkout_id, amount, reason)
+                    .await?
+            }
+        };
+
+        let mut refund = Payment::new(
+            payment.basket_id,
+            amount.clone(),
+            payment.provider.clone(),
+            PaymentStatus::Refunded,
+            Utc::now(),
+            PAYMENT_TYPE_REFUND.to_string(),
+            None,
+        )
+        .with_refund_data(RefundData {
+            refund_id,
+            refunded_payment_id: payment_id,
+        });
+        let refund_payment_id = self.payment_repository.save(&refund).await?;
+        refund.id = Some(ObjectId::parse_str(refund_payment_id)?);
+
+        Ok(map_payment_to_payment_view(&refund))
+    }
+
     pub async fn get_basket_payments(&self, basket_id: &str) -> anyhow::Result<Vec<PaymentView>> {
         let payments = self.payment_repository.find_by_basket_id(basket_id).await?;
         let payment_views = payments.iter().map(map_payment_to_payment_view).collect();
 
         return Ok(payment_views);
     }
+
+    fn get_provider(&self, provider: &str) -> anyhow::Result<&Arc<dyn PaymentProvider>> {
+        self.payment_providers
+            .get(provider)
+            .ok_or(PaymentErrors::ProviderNotSupported(provider.to_string()).into())
+    }
+}
+
+fn get_refundable_amount(payment: &Payment, refunds: &[Payment]) -> i64 {
+    payment.amount.amount - refunds.iter().map(|refund| refund.amount.amount).sum::<i64>()
+}
+
+/// Fills in what the request left out from the caller's token, using the standard OpenID Connect claims.
+pub fn get_checkout_buyer(claims: &serde_json::Value, requested: Option<CheckoutBuyer>) -> Option<CheckoutBuyer> {
+    let claim = |name: &str| claims.get(name).and_then(|value| value.as_str()).map(|value| value.to_string());
+    let requested = requested.unwrap_or_default();
+    let buyer = CheckoutBuyer {
+        first_name: requested.first_name.or_else(|| claim("given_name")),
+        last_name: requested.last_name.or_else(|| claim("family_name")),
+        email: requested.email.or_else(|| claim("email")),
+        phone: requested.phone.or_else(|| claim("phone_number")),
+    };
+    match buyer == CheckoutBuyer::default() {
+        true => None,
+        false => Some(buyer),
+    }
+}
+
+/// Reads the optional `Idempotency-Key` header.
+pub fn get_idempotency_key(headers: &HeaderMap) -> Result<Option<String>, PaymentErrors> {
+    let Some(value) = headers.get("Idempotency-Key") else {
+        return Ok(None);
+    };
+    let idempotency_key = value.to_str().map_err(|_| PaymentErrors::InvalidIdempotencyKey)?.trim();
+    if idempotency_key.is_empty() || idempotency_key.len() > IDEMPOTENCY_KEY_MAX_LENGTH {
+        return Err(PaymentErrors::InvalidIdempotencyKey);
+    }
+    Ok(Some(idempotency_key.to_string()))
+}
+
+/// Unsuccessful payments are skipped, the customer would only be sent to a dead checkout.
+fn find_payment_by_idempotency_key<'a>(payments: &'a [Payment], idempotency_key: &str) -> Option<&'a Payment> {
+    payments.iter().find(|payment| {
+        payment.payment_type == PAYMENT_TYPE_CHECKOUT
+            && payment.idempotency_key.as_deref() == Some(idempotency_key)
+            && !payment.status.is_unsuccessful()
+    })
+}
+
+fn is_open_checkout(payment: &Payment) -> bool {
+    payment.status == PaymentStatus::Pending && payment.payment_type == PAYMENT_TYPE_CHECKOUT && payment.checkout_data.is_some()
+}
+
+/// Only checkouts the customer would have gotten for this request are reused, and only while the provider still accepts them.
+fn is_checkout_reusable(payment: &Payment, provider: &str, capture_mode: CaptureMode, amount: &Money, now: DateTime<Utc>) -> bool {
+    payment.provider == provider
+        && payment.capture_mode == capture_mode
+        && payment.amount == *amount
+        && payment.created_at > now - chrono::Duration::minutes(CHECKOUT_REUSE_MINUTES)
+}
+
+fn map_payment_to_checkout_response(payment: &Payment) -> anyhow::Result<CheckoutResponse> {
+    let checkout_data = payment
+        .checkout_data
+        .as_ref()
+        .ok_or(anyhow::anyhow!("Payment {:?} has no checkout", payment.id))?;
+    let mut checkout_response = CheckoutResponse::new(checkout_data.checkout_id.clone(), checkout_data.checkout_url.clone());
+    checkout_response.paid = payment.status == PaymentStatus::Paid;
+    Ok(checkout_response)
+}
+
+/// Keeps checkouts from sending customers to someone else's site.
+fn check_redirect_urls(redirect_urls: &RedirectUrls, allowed_origins: &[String]) -> Result<(), PaymentErrors> {
+    for url in [&redirect_urls.success, &redirect_urls.failure, &redirect_urls.cancel] {
+        let origin = reqwest::Url::parse(url)
+            .map(|url| url.origin().ascii_serialization())
+            .map_err(|_| PaymentErrors::RedirectUrlNotAllowed(url.clone()))?;
+        if !allowed_origins.iter().any(|allowed_origin| allowed_origin.trim_end_matches('/') == origin) {
+            return Err(PaymentErrors::RedirectUrlNotAllowed(url.clone()));
+        }
+    }
+    Ok(())
+}
+
+fn is_free_basket(basket: &Basket) -> bool {
+    basket.price.amount == 0
+}
+
+pub fn map_webhook_inbox_entry_to_webhook_view(entry: &WebhookInboxEntry) -> WebhookView {
+    WebhookView {
+        id: entry.id.map(|id| id.to_hex()).unwrap_or_default(),
+        provider: entry.provider.clone(),
+        event_id: entry.event_id.clone(),
+        status: entry.status,
+        attempts: entry.attempts,
+        last_error: entry.last_error.clone(),
+        received_at: entry.received_at,
+        next_attempt_at: entry.next_attempt_at,
+    }
+}
+
+/// Backs off exponentially, capped at a day.
+fn get_next_attempt_at(attempts: i32, now: DateTime<Utc>) -> DateTime<Utc> {
+    let exponent = (attempts - 1).clamp(0, 20) as u32;
+    let seconds = WEBHOOK_RETRY_BASE_SECONDS.saturating_mul(2i64.pow(exponent)).min(24 * 60 * 60);
+    now + chrono::Duration::seconds(seconds)
+}
+
+fn map_payment_to_payment_review(payment: &Payment) -> Option<PaymentReview> {
+    let paid_amount = payment.paid_amount.clone()?;
+    Some(PaymentReview {
+        payment_id: payment.id.map(|id| id.to_hex()).unwrap_or_default(),
+        basket_id: payment.basket_id.map(|id| id.to_hex()).unwrap_or_default(),
+        provider: payment.provider.clone(),
+        checkout_id: payment.checkout_data.as_ref().map(|data| data.checkout_id.clone()).unwrap_or_default(),
+        difference: paid_amount.checked_sub(&payment.amount).ok().map(|difference| difference.amount),
+        expected_amount: payment.amount.clone(),
+        paid_amount,
+    })
+}
+
+/// Only pending payments and authorizations get paid, anything else needs a review first.
+fn can_become_paid(current: PaymentStatus) -> bool {
+    matches!(current, PaymentStatus::Pending | PaymentStatus::Authorized)
+}
+
+/// Only pending payments fail, expire or get cancelled. Authorizations can only be voided.
+fn can_become_unsuccessful(current: PaymentStatus, status: PaymentStatus) -> bool {
+    match current {
+        PaymentStatus::Pending => status.is_unsuccessful(),
+        PaymentStatus::Authorized => status == PaymentStatus::Voided,
+        _ => false,
+    }
+}
+
+/// Shown to the customer on their basket.
+fn get_failure_reason(provider: &str, status: PaymentStatus) -> String {
+    match status {
+        PaymentStatus::Voided => format!("The {} authorization was voided.", provider),
+        PaymentStatus::Expired => format!("The {} checkout expired before it was paid.", provider),
+        PaymentStatus::Cancelled => format!("The {} checkout was cancelled.", provider),
+        _ => format!("{} declined the payment.", provider),
+    }
 }
 
 fn map_payment_to_payment_view(payment: &Payment) -> PaymentView {
@@ -111,13 +757,166 @@ fn map_payment_to_payment_view(payment: &Payment) -> PaymentView {
     let basket_id = payment.basket_id.ok_or(anyhow::anyhow!("Failed to get object id for basket id")).unwrap().to_hex();
     PaymentView {
         id: id,
-        status: payment.status.clone(),
+        status: payment.status,
+        failure_reason: payment.failure_reason.clone(),
+        paid_amount: payment.paid_amount.clone(),
         created_at: payment.created_at,
-        amount: payment.amount,
-        currency: payment.currency.clone(),
+        amount: payment.amount.clone(),
         payment_type: payment.payment_type.clone(),
         basket_id: basket_id,
         concurrency_stamp: payment.concurrency_stamp.clone(),
         provider: payment.provider.clone(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_payment(amount: i64, payment_type: &str) -> Payment {
+        Payment::new(None, Money::new(amount, "PHP"), "Maya".to_string(), PaymentStatus::Paid, Utc::now(), payment_type.to_string(), None)
+    }
+
+    #[test]
+    fn test_get_refundable_amount() {
+        let payment = create_payment(1000, PAYMENT_TYPE_CHECKOUT);
+
+        assert_eq!(get_refundable_amount(&payment, &[]), 1000);
+        assert_eq!(get_refundable_amount(&payment, &[create_payment(300, PAYMENT_TYPE_REFUND), create_payment(200, PAYMENT_TYPE_REFUND)]), 500);
+    }
+
+    fn create_checkout_payment(status: PaymentStatus, created_at: DateTime<Utc>) -> Payment {
+        Payment::new(None, Money::new(1000, "PHP"), "Maya".to_string(), status, created_at, PAYMENT_TYPE_CHECKOUT.to_string(), Some(CheckoutData::new("checkout".to_string(), "https://pay.example.com/checkout".to_string())))
+    }
+
+    #[test]
+    fn test_get_idempotency_key() {
+        let headers = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert("Idempotency-Key", value.parse().unwrap());
+            headers
+        };
+
+        assert_eq!(get_idempotency_key(&HeaderMap::new()).unwrap(), None);
+        assert_eq!(get_idempotency_key(&headers(" key-1 ")).unwrap().as_deref(), Some("key-1"));
+        assert!(get_idempotency_key(&headers("")).is_err());
+        assert!(get_idempotency_key(&headers(&"k".repeat(IDEMPOTENCY_KEY_MAX_LENGTH + 1))).is_err());
+    }
+
+    #[test]
+    fn test_find_payment_by_idempotency_key() {
+        let now = Utc::now();
+        let payments = vec![
+            create_checkout_payment(PaymentStatus::Failed, now).with_idempotency_key(Some("key-1".to_string())),
+            create_checkout_payment(PaymentStatus::Pending, now).with_idempotency_key(Some("key-2".to_string())),
+        ];
+
+        assert!(find_payment_by_idempotency_key(&payments, "key-1").is_none());
+        assert_eq!(find_payment_by_idempotency_key(&payments, "key-2").unwrap().status, PaymentStatus::Pending);
+        assert!(find_payment_by_idempotency_key(&payments, "key-3").is_none());
+    }
+
+    #[test]
+    fn test_is_checkout_reusable() {
+        let now = Utc::now();
+        let payment = create_checkout_payment(PaymentStatus::Pending, now - chrono::Duration::minutes(5));
+        let amount = Money::new(1000, "PHP");
+
+        assert!(is_open_checkout(&payment));
+        assert!(!is_open_checkout(&create_checkout_payment(PaymentStatus::Expired, now)));
+        assert!(is_checkout_reusable(&payment, "Maya", CaptureMode::Automatic, &amount, now));
+        assert!(!is_checkout_reusable(&payment, "Stripe", CaptureMode::Automatic, &amount, now));
+        assert!(!is_checkout_reusable(&payment, "Maya", CaptureMode::Manual, &amount, now));
+        assert!(!is_checkout_reusable(&payment, "Maya", CaptureMode::Automatic, &Money::new(900, "PHP"), now));
+        assert!(!is_checkout_reusable(&payment, "Maya", CaptureMode::Automatic, &amount, now + chrono::Duration::minutes(CHECKOUT_REUSE_MINUTES)));
+    }
+
+    #[test]
+    fn test_map_payment_to_checkout_response() {
+        let checkout_response = map_payment_to_checkout_response(&create_checkout_payment(PaymentStatus::Pending, Utc::now())).unwrap();
+        assert_eq!(checkout_response.checkout_url, "https://pay.example.com/checkout");
+        assert!(!checkout_response.paid);
+
+        assert!(map_payment_to_checkout_response(&create_checkout_payment(PaymentStatus::Paid, Utc::now())).unwrap().paid);
+    }
+
+    #[test]
+    fn test_map_payment_to_payment_review() {
+        let mut payment = create_payment(1000, PAYMENT_TYPE_CHECKOUT);
+        assert!(map_payment_to_payment_review(&payment).is_none());
+
+        payment.paid_amount = Some(Money::new(900, "PHP"));
+        assert_eq!(map_payment_to_payment_review(&payment).unwrap().difference, Some(-100));
+
+        payment.paid_amount = Some(Money::new(1000, "USD"));
+        assert_eq!(map_payment_to_payment_review(&payment).unwrap().difference, None);
+    }
+
+    #[test]
+    fn test_check_redirect_urls() {
+        let allowed_origins = vec!["https://shop.example.com".to_string(), "http://localhost:5173/".to_string()];
+        let redirect_urls = |base: &str| RedirectUrls {
+            success: format!("{}/success", base),
+            failure: format!("{}/failure", base),
+            cancel: format!("{}/cancel?basket=1", base),
+        };
+
+        assert!(check_redirect_urls(&redirect_urls("https://shop.example.com"), &allowed_origins).is_ok());
+        assert!(check_redirect_urls(&redirect_urls("http://localhost:5173"), &allowed_origins).is_ok());
+        assert!(check_redirect_urls(&redirect_urls("https://evil.example.com"), &allowed_origins).is_err());
+        assert!(check_redirect_urls(&redirect_urls("http://shop.example.com"), &allowed_origins).is_err());
+        assert!(check_redirect_urls(&redirect_urls("https://shop.example.com.evil.com"), &allowed_origins).is_err());
+        assert!(check_redirect_urls(&redirect_urls("not a url"), &allowed_origins).is_err());
+        assert!(check_redirect_urls(&redirect_urls("https://shop.example.com"), &[]).is_err());
+    }
+
+    #[test]
+    fn test_get_checkout_buyer() {
+        let claims = serde_json::json!({"sub": "customer", "given_name": "Juan", "family_name": "Dela Cruz", "email": "juan@example.com"});
+
+        let buyer = get_checkout_buyer(&claims, Some(CheckoutBuyer { phone: Some("+639171234567".to_string()), email: Some("other@example.com".to_string()), ..Default::default() })).unwrap();
+        assert_eq!(buyer.first_name.as_deref(), Some("Juan"));
+        assert_eq!(buyer.last_name.as_deref(), Some("Dela Cruz"));
+        assert_eq!(buyer.email.as_deref(), Some("other@example.com"));
+        assert_eq!(buyer.phone.as_deref(), Some("+639171234567"));
+
+        assert_eq!(get_checkout_buyer(&serde_json::json!({"sub": "customer"}), None), None);
+    }
+
+    #[test]
+    fn test_get_next_attempt_at() {
+        let now = Utc::now();
+        assert_eq!(get_next_attempt_at(1, now), now + chrono::Duration::seconds(60));
+        assert_eq!(get_next_attempt_at(2, now), now + chrono::Duration::seconds(120));
+        assert_eq!(get_next_attempt_at(4, now), now + chrono::Duration::seconds(480));
+        assert_eq!(get_next_attempt_at(30, now), now + chrono::Duration::days(1));
+    }
+
+    #[test]
+    fn test_get_failure_reason() {
+        assert_eq!(get_failure_reason("Maya", PaymentStatus::Expired), "The Maya checkout expired before it was paid.");
+        assert_eq!(get_failure_reason("Maya", PaymentStatus::Cancelled), "The Maya checkout was cancelled.");
+        assert_eq!(get_failure_reason("Stripe", PaymentStatus::Failed), "Stripe declined the payment.");
+        assert_eq!(get_failure_reason("Maya", PaymentStatus::Voided), "The Maya authorization was voided.");
+    }
+
+    #[test]
+    fn test_can_become_unsuccessful() {
+        assert!(can_become_unsuccessful(PaymentStatus::Pending, PaymentStatus::Failed));
+        assert!(can_become_unsuccessful(PaymentStatus::Pending, PaymentStatus::Expired));
+        assert!(can_become_unsuccessful(PaymentStatus::Authorized, PaymentStatus::Voided));
+        assert!(!can_become_unsuccessful(PaymentStatus::Authorized, PaymentStatus::Expired));
+        assert!(!can_become_unsuccessful(PaymentStatus::Paid, PaymentStatus::Failed));
+        assert!(!can_become_unsuccessful(PaymentStatus::Paid, PaymentStatus::Voided));
+    }
+
+    #[test]
+    fn test_can_become_paid() {
+        assert!(can_become_paid(PaymentStatus::Pending));
+        assert!(can_become_paid(PaymentStatus::Authorized));
+        assert!(!can_become_paid(PaymentStatus::Voided));
+        assert!(!can_become_paid(PaymentStatus::Expired));
+        assert!(!can_become_paid(PaymentStatus::Failed));
+        assert!(!can_become_paid(PaymentStatus::Refunded));
+    }
+}
@@ -1,7 +1,31 @@
-pub const CURRENCY: &str = "PHP";
+/// Pending payments younger than this are likely still waiting on their webhook.
+pub const DEFAULT_RECONCILE_AFTER_MINUTES: i64 = 15;
+/// Pending payments are read in pages of this size, a reconciliation goes through all of them.
+pub const RECONCILE_PAGE_SIZE: i64 = 100;
 
-pub const PAYMENT_STATUS_PENDING: &str = "pending";
-pub const PAYMENT_STATUS_PAID: &str = "paid";
-//pub const PAYMENT_STATUS_EXPIRED: &str = "expired";
+/// Providers keep checkouts open for at least an hour, younger pending checkouts of a basket are handed out again.
+pub const CHECKOUT_REUSE_MINUTES: i64 = 30;
 
-pub const PAYMENT_TYPE_CHECKOUT: &str = "checkout";
\ No newline at end of file
+/// Longer keys are rejected.
+pub const IDEMPOTENCY_KEY_MAX_LENGTH: usize = 255;
+
+/// Card authorizations lapse after about a week, the basket's inventories are held as long.
+pub const AUTHORIZATION_HOLD_DAYS: i64 = 7;
+
+/// Webhooks that still fail after this many attempts wait for someone to replay them.
+pub const WEBHOOK_MAX_ATTEMPTS: i32 = 8;
+/// Doubled on every failed attempt.
+pub const WEBHOOK_RETRY_BASE_SECONDS: i64 = 60;
+/// How long an attempt holds a webhook, one still processing after this is assumed to have crashed.
+pub const WEBHOOK_LEASE_SECONDS: i64 = 5 * 60;
+
+pub const PAYMENT_TYPE_CHECKOUT: &str = "checkout";
+pub const PAYMENT_TYPE_REFUND: &str = "refund";
+pub const PAYMENT_PROVIDER_MAYA: &str = "Maya";
+/// Put in `Maya__WebhookAllowedIps` to accept Maya webhooks from any source, only for sandbox testing.
+pub const MAYA_ANY_WEBHOOK_IP: &str = "*";
+pub const PAYMENT_PROVIDER_STRIPE: &str = "Stripe";
+/// Only for local runs, see `FakeProvider`.
+pub const PAYMENT_PROVIDER_FAKE: &str = "Fake";
+/// Recorded for baskets with nothing to pay, no provider is involved.
+pub const PAYMENT_PROVIDER_FREE: &str = "Free";
@@ -1,29 +1,200 @@
 use std::sync::Arc;
-use crate::error::AppError;
-use axum::{extract::State, Json};
+use axum::{body::Bytes, extract::{Path, State}, http::HeaderMap, response::Html, Form, Json};
 
-use crate::{app_state::AppState, validation::ValidatedJson};
+use jwt_authorizer::JwtClaims;
+use serde_json::Value;
 
-use super::{data_transfer_objects::{maya_webhook::MayaWebhookRequest, CheckoutRequest, CheckoutResponse}, webhook_handlers};
+use crate::{app_state::AppState, helpers, validation::ValidatedJson};
+
+use super::{application, constants::{DEFAULT_RECONCILE_AFTER_MINUTES, PAYMENT_PROVIDER_FAKE, PAYMENT_PROVIDER_MAYA, PAYMENT_PROVIDER_STRIPE}, data_transfer_objects::{CheckoutRequest, FakeCheckoutRequest, CheckoutResponse, PaymentActionResponse, ReconcilePaymentsRequest, ReconciliationReport, VoidPaymentRequest, WebhookRetryReport, WebhookView}, errors::{PaymentError, PaymentErrors}, payment_providers::fake_provider::{self, FakeCheckouts, FakeWebhookRequest}, reconciliation, webhook_inbox};
 
 pub async fn checkout(
     State(state): State<Arc<AppState>>,
-    ValidatedJson(data): ValidatedJson<CheckoutRequest>,
-) ->  Result<Json<CheckoutResponse>, AppError>  {
-    let result = state.payment_service.create_checkout(data).await?;
+    JwtClaims(claims): JwtClaims<Value>,
+    headers: HeaderMap,
+    ValidatedJson(mut data): ValidatedJson<CheckoutRequest>,
+) ->  Result<Json<CheckoutResponse>, PaymentError>  {
+    let customer_id = helpers::get_subject(&claims)?;
+    let idempotency_key = application::get_idempotency_key(&headers).map_err(|error| PaymentError { error })?;
+    let basket_id = data.basket_id.clone();
+    data.buyer = application::get_checkout_buyer(&claims, data.buyer.take());
+    let mut result = state.payment_service.create_checkout(data, &customer_id, idempotency_key).await?;
+
+    // Free checkouts are paid already, nothing will call back so the basket is purchased here.
+    // The purchase endpoint stays available as a fallback, e.g. when attendee details are still missing.
+    if result.paid {
+        match state.basket_service.purchase_paid_basket(&state.payment_service, &state.order_service, &basket_id).await {
+            Ok(order_id) => result.order_id = order_id,
+            Err(err) => tracing::warn!("Free checkout of basket {} not purchased: {:?}", basket_id, err),
+        }
+    }
 
     Ok(Json(result))
 }
 
-pub async fn maya_webhook(
+pub async fn reconcile(
+    State(state): State<Arc<AppState>>,
+    JwtClaims(claims): JwtClaims<Value>,
+    ValidatedJson(data): ValidatedJson<ReconcilePaymentsRequest>,
+) ->  Result<Json<ReconciliationReport>, PaymentError>  {
+    if !helpers::is_staff(&claims) {
+        return Err(PaymentError { error: PaymentErrors::NotStaff });
+    }
+    let older_than = chrono::Duration::minutes(data.older_than_minutes.unwrap_or(DEFAULT_RECONCILE_AFTER_MINUTES));
+    let report = reconciliation::reconcile_pending_payments(older_than, &state.payment_service, &state.basket_service, &state.order_service).await?;
+
+    Ok(Json(report))
+}
+
+/// Takes the money of an authorized manual capture checkout and purchases its basket.
+pub async fn capture_payment(
+    State(state): State<Arc<AppState>>,
+    JwtClaims(claims): JwtClaims<Value>,
+    Path(payment_id): Path<String>,
+) ->  Result<Json<PaymentActionResponse>, PaymentError>  {
+    if !helpers::is_staff(&claims) {
+        return Err(PaymentError { error: PaymentErrors::NotStaff });
+    }
+    let payment = state.payment_service.capture_payment(&payment_id).await?;
+
+    // The capture went through either way, a purchase that fails here can still be retried through the purchase endpoint.
+    let order_id = match state.basket_service.purchase_paid_basket(&state.payment_service, &state.order_service, &payment.basket_id).await {
+        Ok(order_id) => order_id,
+        Err(err) => {
+            tracing::warn!("Captured basket {} not purchased: {:?}", payment.basket_id, err);
+            None
+        }
+    };
+
+    Ok(Json(PaymentActionResponse {
+        payment_id: payment.id,
+        status: payment.status,
+        order_id,
+    }))
+}
+
+/// Gives up an authorized manual capture checkout, its basket's inventories go back on sale.
+pub async fn void_payment(
+    State(state): State<Arc<AppState>>,
+    JwtClaims(claims): JwtClaims<Value>,
+    Path(payment_id): Path<String>,
+    ValidatedJson(data): ValidatedJson<VoidPaymentRequest>,
+) ->  Result<Json<PaymentActionResponse>, PaymentError>  {
+    if !helpers::is_staff(&claims) {
+        return Err(PaymentError { error: PaymentErrors::NotStaff });
+    }
+    let payment = state.payment_service.void_payment(&payment_id, &data.reason).await?;
+
+    if let Err(err) = state.basket_service.release_basket(&payment.basket_id).await {
+        tracing::warn!("Voided basket {} not released, its reservations lapse on their own: {:?}", payment.basket_id, err);
+    }
+
+    Ok(Json(PaymentActionResponse {
+        payment_id: payment.id,
+        status: payment.status,
+        order_id: None,
+    }))
+}
+
+pub async fn get_failed_webhooks(
+    State(state): State<Arc<AppState>>,
+    JwtClaims(claims): JwtClaims<Value>,
+) ->  Result<Json<Vec<WebhookView>>, PaymentError>  {
+    if !helpers::is_staff(&claims) {
+        return Err(PaymentError { error: PaymentErrors::NotStaff });
+    }
+    let webhooks = state.payment_service.get_failed_webhooks().await?;
+
+    Ok(Json(webhooks))
+}
+
+pub async fn replay_webhook(
     State(state): State<Arc<AppState>>,
-    Json(webhook): Json<MayaWebhookRequest>,
-) ->  Result<(), AppError>  {
+    JwtClaims(claims): JwtClaims<Value>,
+    Path(webhook_id): Path<String>,
+) ->  Result<Json<WebhookView>, PaymentError>  {
+    if !helpers::is_staff(&claims) {
+        return Err(PaymentError { error: PaymentErrors::NotStaff });
+    }
+    let webhook = webhook_inbox::replay_webhook(&webhook_id, &state.payment_service, &state.basket_service, &state.order_service).await?;
+
+    Ok(Json(webhook))
+}
 
-    let result = webhook_handlers::handle_maya_checkout_webhook(webhook, &state.payment_service).await;
-    if let Err(err) = result {
-        tracing::error!("Error handling webhook: {:?}", err);
+pub async fn retry_webhooks(
+    State(state): State<Arc<AppState>>,
+    JwtClaims(claims): JwtClaims<Value>,
+) ->  Result<Json<WebhookRetryReport>, PaymentError>  {
+    if !helpers::is_staff(&claims) {
+        return Err(PaymentError { error: PaymentErrors::NotStaff });
     }
+    let report = webhook_inbox::retry_due_webhooks(&state.payment_service, &state.basket_service, &state.order_service).await?;
+
+    Ok(Json(report))
+}
+
+/// Responds with an error only when the webhook could not be stored, so Maya sends it again.
+pub async fn maya_webhook(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) ->  Result<(), PaymentError>  {
+    state.payment_service.verify_webhook(PAYMENT_PROVIDER_MAYA, &headers, &body)?;
+
+    webhook_inbox::receive_webhook(PAYMENT_PROVIDER_MAYA, &body, &state.payment_service, &state.basket_service, &state.order_service).await?;
 
     Ok(())
 }
+
+/// Takes the raw body since the signature is computed over the exact bytes Stripe sent.
+pub async fn stripe_webhook(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) ->  Result<(), PaymentError>  {
+    // Rejected so Stripe reports the failed delivery instead of us silently dropping it.
+    state.payment_service.verify_webhook(PAYMENT_PROVIDER_STRIPE, &headers, &body)?;
+
+    webhook_inbox::receive_webhook(PAYMENT_PROVIDER_STRIPE, &body, &state.payment_service, &state.basket_service, &state.order_service).await?;
+
+    Ok(())
+}
+
+pub async fn get_fake_checkout(
+    State(state): State<Arc<AppState>>,
+    Path(checkout_id): Path<String>,
+) ->  Result<Html<String>, PaymentError>  {
+    let fake_checkouts = get_fake_checkouts(&state)?;
+    let checkout = fake_checkouts.get(&checkout_id).ok_or(PaymentError { error: PaymentErrors::PaymentNotFound })?;
+
+    Ok(Html(fake_provider::render_checkout_page(&checkout_id, &checkout)))
+}
+
+/// Goes through the same webhook handling as the real providers, so the purchase completes like it would in production.
+pub async fn post_fake_checkout(
+    State(state): State<Arc<AppState>>,
+    Path(checkout_id): Path<String>,
+    Form(data): Form<FakeCheckoutRequest>,
+) ->  Result<Html<String>, PaymentError>  {
+    let fake_checkouts = get_fake_checkouts(&state)?;
+    let checkout = fake_checkouts
+        .set_status(&checkout_id, data.status)
+        .ok_or(PaymentError { error: PaymentErrors::PaymentNotFound })?;
+
+    let webhook = FakeWebhookRequest {
+        id: checkout_id.clone(),
+        status: checkout.status,
+        amount: checkout.amount.clone(),
+    };
+    let body = serde_json::to_vec(&webhook).map_err(anyhow::Error::from)?;
+    webhook_inbox::receive_webhook(PAYMENT_PROVIDER_FAKE, &body, &state.payment_service, &state.basket_service, &state.order_service).await?;
+
+    Ok(Html(fake_provider::render_checkout_page(&checkout_id, &checkout)))
+}
+
+fn get_fake_checkouts(state: &AppState) -> Result<&FakeCheckouts, PaymentError> {
+    state
+        .fake_checkouts
+        .as_ref()
+        .ok_or(PaymentError { error: PaymentErrors::ProviderNotSupported(PAYMENT_PROVIDER_FAKE.to_string()) })
+}
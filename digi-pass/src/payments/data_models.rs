@@ -1,30 +1,45 @@
 use bson::{oid::ObjectId, Bson};
 use serde::{Deserialize, Serialize};
 
+use crate::money::Money;
+
+use super::data_transfer_objects::{CaptureMode, PaymentStatus, WebhookStatus};
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Payment {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<ObjectId>,
     pub concurrency_stamp: String,
     pub basket_id: Option<ObjectId>,
-    pub amount: i32,
-    pub currency: String,
+    #[serde(deserialize_with = "crate::money::deserialize_legacy_money")]
+    pub amount: Money,
     pub provider: String,
-    pub status: String,
+    pub status: PaymentStatus,
+    #[serde(default)]
+    pub failure_reason: Option<String>,
+    /// What the provider reported as paid, only set when it differed from `amount`.
+    #[serde(default)]
+    pub paid_amount: Option<Money>,
     #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub payment_type: String,
-    pub checkout_data : Option<CheckoutData>
+    pub checkout_data : Option<CheckoutData>,
+    #[serde(default)]
+    pub refund_data: Option<RefundData>,
+    #[serde(default)]
+    pub capture_mode: CaptureMode,
+    /// The `Idempotency-Key` the checkout was requested with, a retried request gets this payment's checkout back.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idempotency_key: Option<String>,
 }
 
 
 impl Payment {
     pub fn new(
         basket_id: Option<ObjectId>,
-        amount: i32,
-        currency: String,
+        amount: Money,
         provider: String,
-        status: String,
+        status: PaymentStatus,
         created_at: chrono::DateTime<chrono::Utc>,
         payment_type: String,
         checkout_data: Option<CheckoutData>,
@@ -34,14 +49,33 @@ impl Payment {
             concurrency_stamp: ObjectId::new().to_hex(),
             basket_id,
             amount,
-            currency,
             provider,
             status,
+            failure_reason: None,
+            paid_amount: None,
             created_at,
             payment_type,
             checkout_data,
+            refund_data: None,
+            capture_mode: CaptureMode::default(),
+            idempotency_key: None,
         }
     }
+
+    pub fn with_refund_data(mut self, refund_data: RefundData) -> Self {
+        self.refund_data = Some(refund_data);
+        self
+    }
+
+    pub fn with_capture_mode(mut self, capture_mode: CaptureMode) -> Self {
+        self.capture_mode = capture_mode;
+        self
+    }
+
+    pub fn with_idempotency_key(mut self, idempotency_key: Option<String>) -> Self {
+        self.idempotency_key = idempotency_key;
+        self
+    }
 }
 
 
@@ -71,3 +105,47 @@ impl CheckoutData {
     }
 }
 
+
+/// Kept on refund payments to tie them to the payment they gave money back for.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RefundData {
+    pub refund_id: String,
+    pub refunded_payment_id: ObjectId,
+}
+
+
+/// A webhook as the provider sent it, kept until it is processed so a failure does not lose it.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct WebhookInboxEntry {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub concurrency_stamp: String,
+    pub provider: String,
+    /// Unique per provider, redeliveries of the same event are dropped.
+    pub event_id: String,
+    pub body: String,
+    pub status: WebhookStatus,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub received_at: chrono::DateTime<chrono::Utc>,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub next_attempt_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl WebhookInboxEntry {
+    pub fn new(provider: String, event_id: String, body: String, received_at: chrono::DateTime<chrono::Utc>) -> Self {
+        WebhookInboxEntry {
+            id: None,
+            concurrency_stamp: ObjectId::new().to_hex(),
+            provider,
+            event_id,
+            body,
+            status: WebhookStatus::Pending,
+            attempts: 0,
+            last_error: None,
+            received_at,
+            next_attempt_at: received_at,
+        }
+    }
+}
@@ -2,16 +2,132 @@ pub mod maya_webhook;
 use serde::{Deserialize, Serialize};
 use validator::Validate;
 
+use crate::money::Money;
+
+/// Stored under its snake case name, e.g. `"review_required"`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PaymentStatus {
+    #[default]
+    Pending,
+    Paid,
+    Failed,
+    Expired,
+    Cancelled,
+    Refunded,
+    /// The provider reported a different amount or currency than we charged, someone has to look at it.
+    ReviewRequired,
+    /// Manual capture checkouts only, the money is held until someone captures or voids it.
+    Authorized,
+    /// An authorization given up before it was captured.
+    Voided,
+}
+
+impl PaymentStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PaymentStatus::Pending => "pending",
+            PaymentStatus::Paid => "paid",
+            PaymentStatus::Failed => "failed",
+            PaymentStatus::Expired => "expired",
+            PaymentStatus::Cancelled => "cancelled",
+            PaymentStatus::Refunded => "refunded",
+            PaymentStatus::ReviewRequired => "review_required",
+            PaymentStatus::Authorized => "authorized",
+            PaymentStatus::Voided => "voided",
+        }
+    }
+
+    /// The payment will not be paid anymore, the customer has to check out again.
+    pub fn is_unsuccessful(&self) -> bool {
+        matches!(self, PaymentStatus::Failed | PaymentStatus::Expired | PaymentStatus::Cancelled | PaymentStatus::Voided)
+    }
+}
+
+impl std::fmt::Display for PaymentStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookStatus {
+    /// Waiting for its first or next attempt.
+    #[default]
+    Pending,
+    /// Claimed by an attempt, due again once the lease runs out in case that attempt crashed.
+    Processing,
+    Processed,
+    /// Ran out of attempts, only processed again when replayed.
+    Failed,
+}
+
+impl WebhookStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookStatus::Pending => "pending",
+            WebhookStatus::Processing => "processing",
+            WebhookStatus::Processed => "processed",
+            WebhookStatus::Failed => "failed",
+        }
+    }
+}
+
 #[derive(Validate, Deserialize, Debug)]
 pub struct CheckoutRequest {
     #[validate(length(min = 1))]
     pub basket_id: String,
+    /// Falls back to the default provider when not given.
+    pub provider: Option<String>,
+    /// Where the provider sends the customer back to, each must be on an allowed origin.
+    pub redirect_urls: Option<RedirectUrls>,
+    /// Fields not given are taken from the caller's token.
+    #[validate(custom(function = "crate::validation::validate_optional"))]
+    pub buyer: Option<CheckoutBuyer>,
+    #[serde(default)]
+    pub capture_mode: CaptureMode,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptureMode {
+    /// The payment is taken as soon as the customer pays.
+    #[default]
+    Automatic,
+    /// The payment is only authorized, staff capture or void it later. The basket's inventories are held meanwhile.
+    Manual,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct RedirectUrls {
+    pub success: String,
+    pub failure: String,
+    pub cancel: String,
+}
+
+#[derive(Validate, Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct CheckoutBuyer {
+    #[validate(length(min = 1))]
+    pub first_name: Option<String>,
+    #[validate(length(min = 1))]
+    pub last_name: Option<String>,
+    #[validate(email)]
+    pub email: Option<String>,
+    #[validate(length(min = 1))]
+    pub phone: Option<String>,
 }
 
 #[derive(Serialize, Debug)]
 pub struct CheckoutResponse {
     pub checkout_id: String,
+    /// Empty for free checkouts, there is no page to send the customer to.
     pub checkout_url: String,
+    /// Set when nothing was left to pay, the checkout is paid right away.
+    pub paid: bool,
+    /// Set when the paid checkout was also purchased.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub order_id: Option<String>,
 }
 
 impl CheckoutResponse {
@@ -19,18 +135,125 @@ impl CheckoutResponse {
         CheckoutResponse {
             checkout_id,
             checkout_url,
+            paid: false,
+            order_id: None,
         }
     }
+
+    pub fn free(checkout_id: String) -> Self {
+        CheckoutResponse {
+            checkout_id,
+            checkout_url: String::new(),
+            paid: true,
+            order_id: None,
+        }
+    }
+}
+
+#[derive(Validate, Deserialize, Debug)]
+pub struct VoidPaymentRequest {
+    #[validate(length(min = 1))]
+    pub reason: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PaymentActionResponse {
+    pub payment_id: String,
+    pub status: PaymentStatus,
+    /// Set when capturing the payment also purchased the basket.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub order_id: Option<String>,
+}
+
+/// Posted by the fake checkout page.
+#[derive(Deserialize, Debug)]
+pub struct FakeCheckoutRequest {
+    pub status: PaymentStatus,
+}
+
+#[derive(Validate, Deserialize, Debug)]
+pub struct ReconcilePaymentsRequest {
+    /// Only pending payments older than this are checked, defaults to 15 minutes.
+    #[validate(range(min = 1))]
+    pub older_than_minutes: Option<i64>,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct ReconciliationReport {
+    pub checked: usize,
+    /// Payments whose local status disagreed with the provider, they now have the provider's status.
+    pub mismatches: Vec<PaymentMismatch>,
+    pub errors: Vec<String>,
+    /// Payments where the provider reported a different amount than we charged.
+    pub reviews_required: Vec<PaymentReview>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PaymentReview {
+    pub payment_id: String,
+    pub basket_id: String,
+    pub provider: String,
+    pub checkout_id: String,
+    pub expected_amount: Money,
+    pub paid_amount: Money,
+    /// Positive for overpayments, negative for underpayments. Not set when the currencies differ.
+    pub difference: Option<i64>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PaymentMismatch {
+    pub payment_id: String,
+    pub basket_id: String,
+    pub provider: String,
+    pub checkout_id: String,
+    pub local_status: PaymentStatus,
+    pub provider_status: PaymentStatus,
+    /// Set when the basket was purchased as a result of the payment being paid.
+    pub order_id: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct WebhookView {
+    pub id: String,
+    pub provider: String,
+    pub event_id: String,
+    pub status: WebhookStatus,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub received_at: chrono::DateTime<chrono::Utc>,
+    pub next_attempt_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct WebhookRetryReport {
+    pub processed: Vec<String>,
+    /// Webhooks that failed again, they are retried later unless they ran out of attempts.
+    pub failed: Vec<WebhookView>,
 }
 
 pub struct PaymentView {
     pub id: String,
     pub concurrency_stamp: String,
     pub basket_id: String,
-    pub amount: i32,
-    pub currency: String,
+    pub amount: Money,
     pub provider: String,
-    pub status: String,
+    pub status: PaymentStatus,
+    /// Why the provider did not take the payment, only set for unsuccessful payments.
+    pub failure_reason: Option<String>,
+    /// What the provider reported as paid, only set when it differed from `amount`.
+    pub paid_amount: Option<Money>,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub payment_type: String,
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_payment_status_as_str_matches_serde() {
+        for status in [PaymentStatus::Pending, PaymentStatus::Paid, PaymentStatus::Failed, PaymentStatus::Expired, PaymentStatus::Cancelled, PaymentStatus::Refunded, PaymentStatus::ReviewRequired, PaymentStatus::Authorized, PaymentStatus::Voided] {
+            assert_eq!(serde_json::to_value(status).unwrap(), serde_json::Value::String(status.as_str().to_string()));
+        }
+    }
+}
@@ -1,82 +1,297 @@
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::money::{Money, MoneyErrors};
+
+/// Maya sends payment webhooks (`PAYMENT_SUCCESS` and the like, `status` holds the event) and, for merchants
+/// still subscribed to them, the older checkout webhooks where the event is in `paymentStatus`.
+/// Only the checkout id and the event are required, payloads differ a lot between fund sources.
+#[derive(Debug, Clone)]
+pub enum MayaWebhookRequest {
+    Payment(Box<MayaPaymentWebhook>),
+    Checkout(Box<MayaCheckoutWebhook>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MayaWebhookEvent {
+    PaymentSuccess,
+    PaymentFailed,
+    PaymentExpired,
+    PaymentCancelled,
+    /// Manual capture checkouts only.
+    Authorized,
+    Captured,
+    Voided,
+    /// Events we do not act on, e.g. `PAYMENT_PROCESSING`.
+    Other(String),
+}
+
+impl MayaWebhookEvent {
+    pub fn from_status(status: &str) -> Self {
+        match status {
+            "PAYMENT_SUCCESS" => MayaWebhookEvent::PaymentSuccess,
+            "PAYMENT_FAILED" => MayaWebhookEvent::PaymentFailed,
+            "PAYMENT_EXPIRED" => MayaWebhookEvent::PaymentExpired,
+            "PAYMENT_CANCELLED" => MayaWebhookEvent::PaymentCancelled,
+            "AUTHORIZED" => MayaWebhookEvent::Authorized,
+            "CAPTURED" => MayaWebhookEvent::Captured,
+            "VOIDED" => MayaWebhookEvent::Voided,
+            other => MayaWebhookEvent::Other(other.to_string()),
+        }
+    }
+}
+
+impl MayaWebhookRequest {
+    pub fn parse(body: &[u8]) -> serde_json::Result<Self> {
+        let value: Value = serde_json::from_slice(body)?;
+        if value.get("paymentStatus").is_some() {
+            return Ok(MayaWebhookRequest::Checkout(Box::new(serde_json::from_value(value)?)));
+        }
+        Ok(MayaWebhookRequest::Payment(Box::new(serde_json::from_value(value)?)))
+    }
+
+    pub fn checkout_id(&self) -> &str {
+        match self {
+            MayaWebhookRequest::Payment(webhook) => &webhook.id,
+            MayaWebhookRequest::Checkout(webhook) => &webhook.id,
+        }
+    }
+
+    fn status(&self) -> &str {
+        match self {
+            MayaWebhookRequest::Payment(webhook) => &webhook.status,
+            MayaWebhookRequest::Checkout(webhook) => &webhook.payment_status,
+        }
+    }
+
+    pub fn event(&self) -> MayaWebhookEvent {
+        MayaWebhookEvent::from_status(self.status())
+    }
+
+    /// Maya has no event ids, it sends one webhook per status change of a checkout.
+    pub fn event_id(&self) -> String {
+        format!("{}:{}", self.checkout_id(), self.status())
+    }
+
+    /// What Maya says was paid, `None` when the payload does not say.
+    pub fn amount(&self) -> Result<Option<Money>, MoneyErrors> {
+        match self {
+            MayaWebhookRequest::Payment(webhook) => match (&webhook.amount, webhook.currency.as_deref()) {
+                (Some(amount), Some(currency)) => Ok(Some(Money::from_decimal_str(amount, currency)?)),
+                _ => Ok(None),
+            },
+            MayaWebhookRequest::Checkout(webhook) => match &webhook.total_amount {
+                Some(total_amount) => total_amount.to_money(),
+                None => Ok(None),
+            },
+        }
+    }
+}
+
+impl CheckoutTotalAmount {
+    /// `None` when the value or the currency is missing.
+    pub fn to_money(&self) -> Result<Option<Money>, MoneyErrors> {
+        let amount = self.value.as_ref().or(self.amount.as_ref()).and_then(value_to_decimal_string);
+        match (amount, self.currency.as_deref()) {
+            (Some(amount), Some(currency)) => Ok(Some(Money::from_decimal_str(&amount, currency)?)),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Amounts come as strings in payment webhooks and as either strings or numbers in checkout webhooks.
+fn value_to_decimal_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(value) => Some(value.clone()),
+        Value::Number(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct MayaWebhookRequest {
+pub struct MayaPaymentWebhook {
+    /// The checkout id.
     pub id: String,
 
-    pub is_paid: bool,
-
     pub status: String,
 
-    pub amount: String,
+    pub is_paid: Option<bool>,
+
+    /// Decimal string in the major unit, e.g. "100.50".
+    pub amount: Option<String>,
+
+    pub currency: Option<String>,
+
+    pub can_void: Option<bool>,
+
+    pub can_refund: Option<bool>,
+
+    pub can_capture: Option<bool>,
+
+    pub created_at: Option<String>,
+
+    pub updated_at: Option<String>,
 
-    pub currency: String,
+    pub description: Option<String>,
 
-    pub can_void: bool,
+    pub payment_token_id: Option<String>,
 
-    pub can_refund: bool,
+    pub fund_source: Option<FundSource>,
 
-    pub can_capture: bool,
+    pub receipt: Option<Receipt>,
 
-    pub created_at: String,
+    pub metadata: Option<Value>,
 
-    pub updated_at: String,
+    pub approval_code: Option<String>,
 
-    pub description: String,
+    pub receipt_number: Option<String>,
 
-    pub payment_token_id: String,
+    pub request_reference_number: Option<String>,
 
-    pub fund_source: FundSource,
+    /// Anything Maya added that we do not know about yet.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MayaCheckoutWebhook {
+    /// The checkout id.
+    pub id: String,
+
+    /// The checkout's own status, e.g. `COMPLETED`.
+    pub status: Option<String>,
+
+    pub payment_status: String,
 
-    pub receipt: Receipt,
+    pub total_amount: Option<CheckoutTotalAmount>,
 
-    pub metadata: Metadata,
+    pub request_reference_number: Option<String>,
+
+    pub receipt_number: Option<String>,
+
+    pub created_at: Option<String>,
+
+    pub updated_at: Option<String>,
+
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckoutTotalAmount {
+    pub value: Option<Value>,
 
-    pub approval_code: String,
+    /// Older payloads name the value `amount`.
+    pub amount: Option<Value>,
 
-    pub receipt_number: String,
+    pub currency: Option<String>,
 
-    pub request_reference_number: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FundSource {
     #[serde(rename = "type")]
-    pub fund_source_type: String,
+    pub fund_source_type: Option<String>,
 
-    pub id: Option<serde_json::Value>,
+    pub id: Option<Value>,
 
-    pub description: String,
+    pub description: Option<String>,
 
-    pub details: Details,
+    /// Only card payments have details.
+    pub details: Option<Details>,
+
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Details {
-    pub scheme: String,
+    pub scheme: Option<String>,
 
-    pub last4: String,
+    pub last4: Option<String>,
 
-    pub first6: String,
+    pub first6: Option<String>,
 
-    pub masked: String,
+    pub masked: Option<String>,
 
-    pub issuer: String,
-}
+    pub issuer: Option<String>,
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Metadata {
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Receipt {
-    pub transaction_id: String,
+    pub transaction_id: Option<String>,
 
-    pub receipt_no: String,
+    pub receipt_no: Option<String>,
 
     #[serde(rename = "approval_code")]
-    pub receipt_approval_code: String,
+    pub receipt_approval_code: Option<String>,
+
+    pub approval_code: Option<String>,
+
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    pub approval_code: String,
-}
\ No newline at end of file
+    const SAMPLES_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/sample_data/maya_webhooks");
+
+    fn parse_sample(name: &str) -> MayaWebhookRequest {
+        let body = std::fs::read(format!("{}/{}", SAMPLES_DIR, name)).unwrap();
+        MayaWebhookRequest::parse(&body).unwrap()
+    }
+
+    #[test]
+    fn test_parse_every_sample() {
+        for entry in std::fs::read_dir(SAMPLES_DIR).unwrap() {
+            let path = entry.unwrap().path();
+            let body = std::fs::read(&path).unwrap();
+            let webhook = MayaWebhookRequest::parse(&body).unwrap_or_else(|err| panic!("{:?}: {}", path, err));
+            assert!(!webhook.checkout_id().is_empty(), "{:?}", path);
+            assert!(webhook.amount().is_ok(), "{:?}", path);
+        }
+    }
+
+    #[test]
+    fn test_parse_samples() {
+        let cases = [
+            ("payment_success_card.json", MayaWebhookEvent::PaymentSuccess, Some(Money::new(1000, "PHP"))),
+            ("payment_success_ewallet.json", MayaWebhookEvent::PaymentSuccess, Some(Money::new(25050, "PHP"))),
+            ("payment_success_qrph.json", MayaWebhookEvent::PaymentSuccess, Some(Money::new(150000, "PHP"))),
+            ("payment_failed.json", MayaWebhookEvent::PaymentFailed, Some(Money::new(1000, "PHP"))),
+            ("payment_expired.json", MayaWebhookEvent::PaymentExpired, None),
+            ("authorized.json", MayaWebhookEvent::Authorized, Some(Money::new(1000, "PHP"))),
+            ("voided.json", MayaWebhookEvent::Voided, Some(Money::new(1000, "PHP"))),
+            ("checkout_success.json", MayaWebhookEvent::PaymentSuccess, Some(Money::new(10000, "PHP"))),
+        ];
+        for (name, event, amount) in cases {
+            let webhook = parse_sample(name);
+            assert_eq!(webhook.event(), event, "{}", name);
+            assert_eq!(webhook.amount().unwrap(), amount, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_keeps_unknown_fields() {
+        let MayaWebhookRequest::Payment(webhook) = parse_sample("payment_failed.json") else {
+            panic!("Expected a payment webhook");
+        };
+        assert_eq!(webhook.extra.get("errorCode"), Some(&Value::String("2553".to_string())));
+    }
+
+    #[test]
+    fn test_event_id() {
+        let webhook = parse_sample("checkout_success.json");
+        assert_eq!(webhook.event_id(), "b5e0a9a4-8d7f-4b6e-9c56-1f3e2d4c5b6a:PAYMENT_SUCCESS");
+    }
+}
@@ -0,0 +1,80 @@
+use axum::response::{IntoResponse, Response};
+use axum::http::StatusCode;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum PaymentErrors {
+    #[error("Basket not found.")]
+    BasketNotFound,
+    #[error("Basket does not belong to the caller.")]
+    BasketNotOwned,
+    #[error("Basket changed during checkout, please check out again.")]
+    BasketChanged,
+    #[error("Payment provider {0} is not supported.")]
+    ProviderNotSupported(String),
+    #[error("Webhook could not be verified.")]
+    InvalidWebhook,
+    #[error("Payment not found.")]
+    PaymentNotFound,
+    #[error("{0}")]
+    PaymentNotRefundable(String),
+    #[error("Only authorized payments can be captured or voided.")]
+    PaymentNotAuthorized,
+    #[error("Only staff can manage payments.")]
+    NotStaff,
+    #[error("Idempotency-Key is empty or too long.")]
+    InvalidIdempotencyKey,
+    #[error("Redirect url {0} is not allowed.")]
+    RedirectUrlNotAllowed(String),
+    #[error("Webhook not found.")]
+    WebhookNotFound,
+    #[error("Webhook was already processed.")]
+    WebhookAlreadyProcessed,
+    #[error("{0}")]
+    Unknown(#[from] anyhow::Error)
+}
+
+pub struct PaymentError {
+    pub error: PaymentErrors,
+}
+
+
+// Tell axum how to convert `PaymentErrors` into a response.
+impl IntoResponse for PaymentError {
+    fn into_response(self) -> Response {
+        let status_code = match &self.error {
+            PaymentErrors::BasketNotFound => StatusCode::BAD_REQUEST,
+            PaymentErrors::BasketNotOwned => StatusCode::FORBIDDEN,
+            PaymentErrors::BasketChanged => StatusCode::CONFLICT,
+            PaymentErrors::ProviderNotSupported(_) => StatusCode::BAD_REQUEST,
+            PaymentErrors::InvalidWebhook => StatusCode::BAD_REQUEST,
+            PaymentErrors::PaymentNotFound => StatusCode::NOT_FOUND,
+            PaymentErrors::PaymentNotRefundable(_) => StatusCode::BAD_REQUEST,
+            PaymentErrors::PaymentNotAuthorized => StatusCode::BAD_REQUEST,
+            PaymentErrors::NotStaff => StatusCode::FORBIDDEN,
+            PaymentErrors::InvalidIdempotencyKey => StatusCode::BAD_REQUEST,
+            PaymentErrors::RedirectUrlNotAllowed(_) => StatusCode::BAD_REQUEST,
+            PaymentErrors::WebhookNotFound => StatusCode::NOT_FOUND,
+            PaymentErrors::WebhookAlreadyProcessed => StatusCode::BAD_REQUEST,
+            PaymentErrors::Unknown(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        (status_code, format!("{}", self.error)).into_response()
+    }
+}
+
+impl From<anyhow::Error> for PaymentError
+{
+    fn from(err: anyhow::Error) -> Self {
+        match err.downcast::<PaymentErrors>() {
+            Ok(err) => 
+            Self {
+                error: err,
+            },
+            Err(err) => 
+            Self {
+                error: PaymentErrors::Unknown(err).into(),
+            },
+        }
+    }
+}
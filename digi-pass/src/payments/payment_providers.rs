@@ -1,11 +1,43 @@
+pub mod fake_provider;
 pub mod maya_provider;
+pub mod stripe_provider;
 use async_trait::async_trait;
-use crate::baskets::data_transfer_objects::Basket;
+use axum::http::HeaderMap;
+use crate::{baskets::data_transfer_objects::Basket, money::Money};
 
-use super::data_models::CheckoutData;
+use super::{data_models::CheckoutData, data_transfer_objects::{CaptureMode, CheckoutBuyer, PaymentStatus, RedirectUrls}};
+
+/// A checkout as the provider has it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckoutStatus {
+    pub status: PaymentStatus,
+    /// What the provider took or holds for the checkout, `None` when it does not say.
+    pub amount: Option<Money>,
+}
+
+/// What the customer asked for at checkout, passed on to the provider.
+#[derive(Debug, Clone, Default)]
+pub struct CheckoutOptions {
+    /// Already checked against the allowed origins.
+    pub redirect_urls: Option<RedirectUrls>,
+    pub buyer: Option<CheckoutBuyer>,
+    pub capture_mode: CaptureMode,
+}
 
 #[async_trait]
 pub trait PaymentProvider : Send + Sync {
     fn get_name(&self) -> String;
-    async fn prepare_checkout(&self, basket: &Basket) -> anyhow::Result<CheckoutData>;
+    async fn prepare_checkout(&self, basket: &Basket, options: &CheckoutOptions) -> anyhow::Result<CheckoutData>;
+    /// Closes an unpaid checkout so it can no longer be paid, e.g. when a newer one replaces it.
+    async fn expire_checkout(&self, checkout_id: &str) -> anyhow::Result<()>;
+    /// Checks that a webhook call really came from the provider before it is acted on.
+    fn verify_webhook(&self, headers: &HeaderMap, body: &[u8]) -> anyhow::Result<()>;
+    /// Asks the provider for the checkout's status and amount, webhook payloads are not trusted for these.
+    async fn get_status(&self, checkout_id: &str) -> anyhow::Result<CheckoutStatus>;
+    /// Refunds all or part of a paid checkout, returns the provider's refund id.
+    async fn refund(&self, checkout_id: &str, amount: &Money, reason: &str) -> anyhow::Result<String>;
+    /// Takes `amount` of an authorized manual capture checkout.
+    async fn capture(&self, checkout_id: &str, amount: &Money) -> anyhow::Result<()>;
+    /// Releases the hold on an authorized manual capture checkout, nothing is charged.
+    async fn void(&self, checkout_id: &str, reason: &str) -> anyhow::Result<()>;
 }
@@ -0,0 +1,178 @@
+use std::{collections::HashMap, sync::{Arc, Mutex}};
+
+use async_trait::async_trait;
+use axum::http::HeaderMap;
+use bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+
+use crate::{baskets::data_transfer_objects::Basket, money::Money, payments::{constants::PAYMENT_PROVIDER_FAKE, data_models::CheckoutData, data_transfer_objects::PaymentStatus}};
+
+use super::{CheckoutOptions, CheckoutStatus, PaymentProvider};
+
+/// Stands in for a real provider on local runs. Checkouts live in memory and are paid, authorized, failed or expired
+/// from a page the API serves itself, see [`render_checkout_page`].
+pub struct FakeProvider {
+    /// Where the API itself is reachable, e.g. "http://localhost:3000".
+    base_url: String,
+    checkouts: FakeCheckouts,
+}
+
+impl FakeProvider {
+    pub fn new(base_url: String, checkouts: FakeCheckouts) -> Self {
+        Self {
+            base_url,
+            checkouts,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct FakeCheckout {
+    pub amount: Money,
+    pub status: PaymentStatus,
+}
+
+/// Shared between the provider and the checkout page.
+#[derive(Clone, Default)]
+pub struct FakeCheckouts {
+    checkouts: Arc<Mutex<HashMap<String, FakeCheckout>>>,
+}
+
+impl FakeCheckouts {
+    pub fn get(&self, checkout_id: &str) -> Option<FakeCheckout> {
+        self.checkouts.lock().unwrap().get(checkout_id).cloned()
+    }
+
+    fn insert(&self, checkout_id: String, checkout: FakeCheckout) {
+        self.checkouts.lock().unwrap().insert(checkout_id, checkout);
+    }
+
+    pub fn set_status(&self, checkout_id: &str, status: PaymentStatus) -> Option<FakeCheckout> {
+        let mut checkouts = self.checkouts.lock().unwrap();
+        let checkout = checkouts.get_mut(checkout_id)?;
+        checkout.status = status;
+        Some(checkout.clone())
+    }
+}
+
+/// What the checkout page hands to the webhook handling, shaped like a provider webhook.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FakeWebhookRequest {
+    pub id: String,
+    pub status: PaymentStatus,
+    pub amount: Money,
+}
+
+#[async_trait]
+impl PaymentProvider for FakeProvider {
+    fn get_name(&self) -> String {
+        PAYMENT_PROVIDER_FAKE.to_string()
+    }
+
+    async fn prepare_checkout(&self, basket: &Basket, _options: &CheckoutOptions) -> anyhow::Result<CheckoutData> {
+        let checkout_id = ObjectId::new().to_hex();
+        self.checkouts.insert(
+            checkout_id.clone(),
+            FakeCheckout {
+                amount: basket.price.clone(),
+                status: PaymentStatus::Pending,
+            },
+        );
+        let checkout_url = format!("{}/payments/fake/checkouts/{}", self.base_url, checkout_id);
+        Ok(CheckoutData::new(checkout_id, checkout_url))
+    }
+
+    async fn expire_checkout(&self, checkout_id: &str) -> anyhow::Result<()> {
+        self.checkouts
+            .set_status(checkout_id, PaymentStatus::Expired)
+            .ok_or(anyhow::anyhow!("Fake checkout {} not found, checkouts are lost on restart", checkout_id))?;
+        Ok(())
+    }
+
+    /// The page is served by the API itself, there is nobody to impersonate.
+    fn verify_webhook(&self, _headers: &HeaderMap, _body: &[u8]) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn get_status(&self, checkout_id: &str) -> anyhow::Result<CheckoutStatus> {
+        let checkout = self
+            .checkouts
+            .get(checkout_id)
+            .ok_or(anyhow::anyhow!("Fake checkout {} not found, checkouts are lost on restart", checkout_id))?;
+        Ok(CheckoutStatus {
+            status: checkout.status,
+            amount: Some(checkout.amount),
+        })
+    }
+
+    async fn refund(&self, _checkout_id: &str, _amount: &Money, _reason: &str) -> anyhow::Result<String> {
+        Ok(ObjectId::new().to_hex())
+    }
+
+    async fn capture(&self, checkout_id: &str, _amount: &Money) -> anyhow::Result<()> {
+        self.checkouts
+            .set_status(checkout_id, PaymentStatus::Paid)
+            .ok_or(anyhow::anyhow!("Fake checkout {} not found, checkouts are lost on restart", checkout_id))?;
+        Ok(())
+    }
+
+    async fn void(&self, checkout_id: &str, _reason: &str) -> anyhow::Result<()> {
+        self.checkouts
+            .set_status(checkout_id, PaymentStatus::Voided)
+            .ok_or(anyhow::anyhow!("Fake checkout {} not found, checkouts are lost on restart", checkout_id))?;
+        Ok(())
+    }
+}
+
+/// The fake checkout page with a button per outcome, each posts back to the same url.
+pub fn render_checkout_page(checkout_id: &str, checkout: &FakeCheckout) -> String {
+    let buttons: String = [PaymentStatus::Paid, PaymentStatus::Authorized, PaymentStatus::Failed, PaymentStatus::Expired]
+        .iter()
+        .map(|status| {
+            format!(
+                r#"<form method="post"><input type="hidden" name="status" value="{status}"><button type="submit">Mark {status}</button></form>"#,
+                status = status
+            )
+        })
+        .collect();
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head><title>Fake checkout {checkout_id}</title></head>
+<body>
+<h1>Fake checkout</h1>
+<p>Checkout {checkout_id} for {amount} {currency} is {status}.</p>
+{buttons}
+</body>
+</html>"#,
+        checkout_id = checkout_id,
+        amount = checkout.amount.to_decimal_string(),
+        currency = checkout.amount.currency,
+        status = checkout.status,
+        buttons = buttons,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_status() {
+        let checkouts = FakeCheckouts::default();
+        assert!(checkouts.set_status("missing", PaymentStatus::Paid).is_none());
+
+        checkouts.insert("checkout".to_string(), FakeCheckout { amount: Money::new(1000, "PHP"), status: PaymentStatus::Pending });
+        assert_eq!(checkouts.set_status("checkout", PaymentStatus::Paid).unwrap().status, PaymentStatus::Paid);
+        assert_eq!(checkouts.get("checkout").unwrap().status, PaymentStatus::Paid);
+    }
+
+    #[test]
+    fn test_render_checkout_page() {
+        let page = render_checkout_page("checkout", &FakeCheckout { amount: Money::new(1000, "PHP"), status: PaymentStatus::Pending });
+        assert!(page.contains("10.00 PHP is pending"));
+        for status in ["paid", "authorized", "failed", "expired"] {
+            assert!(page.contains(&format!(r#"value="{}""#, status)));
+        }
+    }
+}
@@ -1,24 +1,28 @@
 mod data_transfer_objects;
 use async_trait::async_trait;
+use axum::http::HeaderMap;
 
-use crate::{baskets::data_transfer_objects::{Basket, BasketItem}, payments::data_models::CheckoutData};
+use crate::{baskets::data_transfer_objects::{Basket, BasketItem}, money::Money, payments::{constants::{MAYA_ANY_WEBHOOK_IP, PAYMENT_PROVIDER_MAYA}, data_models::CheckoutData, data_transfer_objects::{CaptureMode, CheckoutBuyer, PaymentStatus, RedirectUrls}}};
 
 
-use self::data_transfer_objects::checkout::{self, request::Item};
+use self::data_transfer_objects::{capture, checkout::{self, request::{AmountDetails, Buyer, Contact, Item, RedirectUrl}}, refund, void};
 
-use super::PaymentProvider;
+use super::{CheckoutOptions, CheckoutStatus, PaymentProvider};
 
 pub struct MayaProvider {
     base_url: String,
-    secret_base64: String
+    secret_base64: String,
+    /// Source ips Maya sends webhooks from, every webhook is rejected when empty and `*` accepts any source.
+    webhook_allowed_ips: Vec<String>,
 }
 
 
 impl MayaProvider {
-    pub fn new(base_url: String, secret_base64: String) -> Self {
+    pub fn new(base_url: String, secret_base64: String, webhook_allowed_ips: Vec<String>) -> Self {
         Self {
             base_url,
-            secret_base64
+            secret_base64,
+            webhook_allowed_ips,
         }
     }
 }
@@ -27,19 +31,29 @@ impl MayaProvider {
 #[async_trait]
 impl PaymentProvider for MayaProvider {
     fn get_name(&self) -> String {
-        "Maya".to_string()
+        PAYMENT_PROVIDER_MAYA.to_string()
     }
     
-    async fn prepare_checkout(&self, basket: &Basket) -> anyhow::Result<CheckoutData>{
+    async fn prepare_checkout(&self, basket: &Basket, options: &CheckoutOptions) -> anyhow::Result<CheckoutData>{
 
         let client = reqwest::Client::new();
         let items:Vec<Item> = basket.basket_items.iter().filter_map(basket_item_to_item).collect();
-        let request = checkout::request::CheckoutRequest::new(
-            basket.price as f64 / 100.0,
-            "PHP".to_string(),
+        let mut request = checkout::request::CheckoutRequest::new(
+            basket.price.to_decimal_string(),
+            basket.price.currency.clone(),
             basket.id.to_string(),
             items,
-        );
+        )
+        .with_total_amount_details(basket_to_amount_details(basket));
+        if let Some(redirect_urls) = &options.redirect_urls {
+            request = request.with_redirect_url(redirect_urls_to_redirect_url(redirect_urls));
+        }
+        if let Some(buyer) = &options.buyer {
+            request = request.with_buyer(checkout_buyer_to_buyer(buyer));
+        }
+        if options.capture_mode == CaptureMode::Manual {
+            request = request.with_authorization_type("NORMAL".to_string());
+        }
 
         let response  = client.post(format!("{}/{}", &self.base_url, "checkout/v1/checkouts"))
         .header("Content-Type", "application/json")
@@ -63,22 +77,304 @@ impl PaymentProvider for MayaProvider {
         }
         
     }
+
+    /// Maya has no api to expire a checkout, it lapses on its own. Should it still be paid,
+    /// the payment is recorded anyway since the money arrived.
+    async fn expire_checkout(&self, checkout_id: &str) -> anyhow::Result<()> {
+        tracing::info!("Maya checkout {} is left to lapse on its own", checkout_id);
+        Ok(())
+    }
+
+    /// Maya does not sign its webhooks, it publishes the ips they are sent from instead.
+    fn verify_webhook(&self, headers: &HeaderMap, _body: &[u8]) -> anyhow::Result<()> {
+        if self.webhook_allowed_ips.iter().any(|ip| ip == MAYA_ANY_WEBHOOK_IP) {
+            return Ok(());
+        }
+        let source_ip = get_source_ip(headers).ok_or(anyhow::anyhow!("Missing webhook source ip"))?;
+        if !self.webhook_allowed_ips.iter().any(|ip| ip == source_ip) {
+            return Err(anyhow::anyhow!("Webhook source ip {} is not allowed", source_ip));
+        }
+        Ok(())
+    }
+
+    async fn get_status(&self, checkout_id: &str) -> anyhow::Result<CheckoutStatus> {
+        let client = reqwest::Client::new();
+        let response = client.get(format!("{}/{}/{}", &self.base_url, "checkout/v1/checkouts", checkout_id))
+        .header("accept", "application/json")
+        .header("authorization", format!("Basic {}", self.secret_base64))
+            .send()
+            .await?;
+
+        match response.error_for_status_ref() {
+            Ok(_) => {
+                let data = response.json::<checkout::details::CheckoutDetailsResponse>().await?;
+                Ok(map_checkout_details(&data)?)
+            },
+            Err(err) => {
+                tracing::error!("Response Text: {:?}", response.text().await?);
+                tracing::error!("Error: {:?}", err);
+                Err(anyhow::anyhow!("Something went wrong getting checkout status."))
+            }
+        }
+    }
+
+    /// Maya uses the checkout id as the id of the payment made through it.
+    async fn refund(&self, checkout_id: &str, amount: &Money, reason: &str) -> anyhow::Result<String> {
+        let client = reqwest::Client::new();
+        let request = refund::request::RefundRequest {
+            total_amount: refund::request::RefundAmount {
+                amount: amount.to_decimal_string().parse()?,
+                currency: amount.currency.clone(),
+            },
+            reason: reason.to_string(),
+        };
+
+        let response = client.post(format!("{}/{}/{}/refunds", &self.base_url, "payments/v1/payments", checkout_id))
+        .header("Content-Type", "application/json")
+        .header("accept", "application/json")
+        .header("authorization", format!("Basic {}", self.secret_base64))
+            .json(&request)
+            .send()
+            .await?;
+
+        match response.error_for_status_ref() {
+            Ok(_) => {
+                let data = response.json::<refund::response::RefundResponse>().await?;
+                Ok(data.id)
+            },
+            Err(err) => {
+                tracing::error!("Response Text: {:?}", response.text().await?);
+                tracing::error!("Error: {:?}", err);
+                Err(anyhow::anyhow!("Something went wrong refunding payment."))
+            }
+        }
+    }
+
+    async fn capture(&self, checkout_id: &str, amount: &Money) -> anyhow::Result<()> {
+        let client = reqwest::Client::new();
+        let request = capture::request::CaptureRequest {
+            capture_amount: capture::request::CaptureAmount {
+                amount: amount.to_decimal_string().parse()?,
+                currency: amount.currency.clone(),
+            },
+        };
+
+        let response = client.post(format!("{}/{}/{}/capture", &self.base_url, "payments/v1/payments", checkout_id))
+        .header("Content-Type", "application/json")
+        .header("accept", "application/json")
+        .header("authorization", format!("Basic {}", self.secret_base64))
+            .json(&request)
+            .send()
+            .await?;
+
+        match response.error_for_status_ref() {
+            Ok(_) => Ok(()),
+            Err(err) => {
+                tracing::error!("Response Text: {:?}", response.text().await?);
+                tracing::error!("Error: {:?}", err);
+                Err(anyhow::anyhow!("Something went wrong capturing payment."))
+            }
+        }
+    }
+
+    async fn void(&self, checkout_id: &str, reason: &str) -> anyhow::Result<()> {
+        let client = reqwest::Client::new();
+        let request = void::request::VoidRequest {
+            reason: reason.to_string(),
+        };
+
+        let response = client.post(format!("{}/{}/{}/voids", &self.base_url, "payments/v1/payments", checkout_id))
+        .header("Content-Type", "application/json")
+        .header("accept", "application/json")
+        .header("authorization", format!("Basic {}", self.secret_base64))
+            .json(&request)
+            .send()
+            .await?;
+
+        match response.error_for_status_ref() {
+            Ok(_) => Ok(()),
+            Err(err) => {
+                tracing::error!("Response Text: {:?}", response.text().await?);
+                tracing::error!("Error: {:?}", err);
+                Err(anyhow::anyhow!("Something went wrong voiding payment."))
+            }
+        }
+    }
+}
+
+/// The api gateway appends the caller's ip to `X-Forwarded-For`, only that last entry can be trusted.
+/// Entries before it are whatever the caller sent.
+fn get_source_ip(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get("X-Forwarded-For")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.rsplit(',').next())
+        .map(str::trim)
+        .filter(|ip| !ip.is_empty())
+}
+
+fn redirect_urls_to_redirect_url(redirect_urls: &RedirectUrls) -> RedirectUrl {
+    RedirectUrl {
+        success: redirect_urls.success.clone(),
+        failure: redirect_urls.failure.clone(),
+        cancel: redirect_urls.cancel.clone(),
+    }
+}
+
+fn checkout_buyer_to_buyer(buyer: &CheckoutBuyer) -> Buyer {
+    let contact = match (&buyer.phone, &buyer.email) {
+        (None, None) => None,
+        (phone, email) => Some(Contact {
+            phone: phone.clone(),
+            email: email.clone(),
+        }),
+    };
+    Buyer {
+        first_name: buyer.first_name.clone(),
+        last_name: buyer.last_name.clone(),
+        contact,
+    }
+}
+
+/// Maya's checkout keeps the total it charged, unlike webhook payloads it cannot be forged.
+fn map_checkout_details(details: &checkout::details::CheckoutDetailsResponse) -> anyhow::Result<CheckoutStatus> {
+    let amount = match &details.total_amount {
+        Some(total_amount) => total_amount.to_money()?,
+        None => None,
+    };
+    Ok(CheckoutStatus {
+        status: map_payment_status(&details.payment_status),
+        amount,
+    })
+}
+
+fn map_payment_status(payment_status: &str) -> PaymentStatus {
+    match payment_status {
+        "PAYMENT_SUCCESS" => PaymentStatus::Paid,
+        "PAYMENT_FAILED" => PaymentStatus::Failed,
+        "PAYMENT_CANCELLED" => PaymentStatus::Cancelled,
+        "PAYMENT_EXPIRED" => PaymentStatus::Expired,
+        "AUTHORIZED" => PaymentStatus::Authorized,
+        "CAPTURED" => PaymentStatus::Paid,
+        "VOIDED" => PaymentStatus::Voided,
+        _ => PaymentStatus::Pending,
+    }
 }
 
 fn basket_item_to_item(basket_item: &BasketItem) -> Option<checkout::request::Item> {
     if basket_item.basketed_inventories.is_empty() {
         return None;
     }
+    let amount = Money::new(
+        basket_item.price.amount - basket_item.discount.amount + basket_item.fee.amount + basket_item.tax.amount,
+        &basket_item.price.currency,
+    )
+    .to_decimal_string();
+    let (name, code) = match &basket_item.bundle {
+        Some(bundle) => (bundle.name.to_string(), bundle.bundle_id.to_string()),
+        None => (
+            basket_item.basketed_inventories[0].name.to_string(),
+            basket_item.basketed_inventories[0].event_id.to_string(),
+        ),
+    };
     let line_item = Item::new(
-        basket_item.price as f64 / 100.00,
+        amount.clone(),
         1.to_string(),
-        basket_item.price as f64 / 100.00,
-        basket_item.basketed_inventories[0].name.to_string(),
-        basket_item.basketed_inventories[0].event_id.to_string(),
-        basket_item.basketed_inventories[0].name.to_string(),
-    );
+        amount,
+        name.clone(),
+        code,
+        name,
+    )
+    .with_amount_details(to_amount_details(
+        &basket_item.price,
+        &basket_item.discount,
+        &basket_item.fee,
+        &basket_item.tax,
+    ));
 
     Some(line_item)
 }
 
+fn basket_to_amount_details(basket: &Basket) -> AmountDetails {
+    let items = &basket.basket_items;
+    let sum = |amount: fn(&BasketItem) -> i64| Money::new(items.iter().map(amount).sum(), &basket.price.currency);
+    to_amount_details(
+        &sum(|bi| bi.price.amount),
+        &sum(|bi| bi.discount.amount),
+        &sum(|bi| bi.fee.amount),
+        &sum(|bi| bi.tax.amount),
+    )
+}
+
+fn to_amount_details(subtotal: &Money, discount: &Money, service_charge: &Money, tax: &Money) -> AmountDetails {
+    AmountDetails {
+        discount: Some(discount.to_decimal_string()),
+        service_charge: Some(service_charge.to_decimal_string()),
+        tax: Some(tax.to_decimal_string()),
+        subtotal: Some(subtotal.to_decimal_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_provider(webhook_allowed_ips: Vec<&str>) -> MayaProvider {
+        MayaProvider::new(
+            "https://pg-sandbox.paymaya.com".to_string(),
+            "secret".to_string(),
+            webhook_allowed_ips.into_iter().map(str::to_string).collect(),
+        )
+    }
+
+    #[test]
+    fn test_checkout_buyer_to_buyer() {
+        let buyer = checkout_buyer_to_buyer(&CheckoutBuyer { first_name: Some("Juan".to_string()), ..Default::default() });
+        assert_eq!(buyer.first_name.as_deref(), Some("Juan"));
+        assert!(buyer.contact.is_none());
+
+        let buyer = checkout_buyer_to_buyer(&CheckoutBuyer { email: Some("juan@example.com".to_string()), ..Default::default() });
+        assert_eq!(buyer.contact.unwrap().email.as_deref(), Some("juan@example.com"));
+    }
+
+    fn create_headers(forwarded_for: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Forwarded-For", forwarded_for.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_verify_webhook() {
+        let provider = create_provider(vec!["13.229.160.234", "3.1.199.75"]);
+
+        assert!(provider.verify_webhook(&create_headers("3.1.199.75"), b"").is_ok());
+        assert!(provider.verify_webhook(&create_headers("10.0.0.1, 13.229.160.234"), b"").is_ok());
+        assert!(provider.verify_webhook(&create_headers("13.229.160.234, 10.0.0.1"), b"").is_err());
+        assert!(provider.verify_webhook(&HeaderMap::new(), b"").is_err());
+        assert!(create_provider(vec![]).verify_webhook(&create_headers("3.1.199.75"), b"").is_err());
+        assert!(create_provider(vec!["*"]).verify_webhook(&HeaderMap::new(), b"").is_ok());
+    }
+
+    #[test]
+    fn test_map_checkout_details() {
+        let body = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/sample_data/maya_webhooks/checkout_success.json")).unwrap();
+        let details: checkout::details::CheckoutDetailsResponse = serde_json::from_slice(&body).unwrap();
 
+        assert_eq!(
+            map_checkout_details(&details).unwrap(),
+            CheckoutStatus { status: PaymentStatus::Paid, amount: Some(Money::new(10000, "PHP")) }
+        );
+    }
+
+    #[test]
+    fn test_map_payment_status() {
+        assert_eq!(map_payment_status("PAYMENT_SUCCESS"), PaymentStatus::Paid);
+        assert_eq!(map_payment_status("PAYMENT_FAILED"), PaymentStatus::Failed);
+        assert_eq!(map_payment_status("PAYMENT_EXPIRED"), PaymentStatus::Expired);
+        assert_eq!(map_payment_status("PAYMENT_CANCELLED"), PaymentStatus::Cancelled);
+        assert_eq!(map_payment_status("AUTHORIZED"), PaymentStatus::Authorized);
+        assert_eq!(map_payment_status("CAPTURED"), PaymentStatus::Paid);
+        assert_eq!(map_payment_status("VOIDED"), PaymentStatus::Voided);
+        assert_eq!(map_payment_status("PENDING_PAYMENT"), PaymentStatus::Pending);
+    }
+}
@@ -10,11 +10,21 @@ pub mod checkout {
             pub request_reference_number: String,
 
             pub items: Vec<Item>,
+
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub redirect_url: Option<RedirectUrl>,
+
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub buyer: Option<Buyer>,
+
+            /// `NORMAL` only authorizes card payments, they are captured or voided later.
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub authorization_type: Option<String>,
         }
 
         impl CheckoutRequest {
             pub fn new(
-                total_amount: f64,
+                total_amount: String,
                 currency: String,
                 request_reference_number: String,
                 items: Vec<Item>,
@@ -23,11 +33,35 @@ pub mod checkout {
                     total_amount: TotalAmount {
                         value: total_amount,
                         currency,
+                        details: None,
                     },
                     request_reference_number,
                     items,
+                    redirect_url: None,
+                    buyer: None,
+                    authorization_type: None,
                 }
             }
+
+            pub fn with_redirect_url(mut self, redirect_url: RedirectUrl) -> Self {
+                self.redirect_url = Some(redirect_url);
+                self
+            }
+
+            pub fn with_buyer(mut self, buyer: Buyer) -> Self {
+                self.buyer = Some(buyer);
+                self
+            }
+
+            pub fn with_authorization_type(mut self, authorization_type: String) -> Self {
+                self.authorization_type = Some(authorization_type);
+                self
+            }
+
+            pub fn with_total_amount_details(mut self, details: AmountDetails) -> Self {
+                self.total_amount.details = Some(details);
+                self
+            }
         }
 
         #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -48,36 +82,96 @@ pub mod checkout {
 
         impl Item {
             pub fn new(
-                amount: f64,
+                amount: String,
                 quantity: String,
-                total_amount: f64,
+                total_amount: String,
                 name: String,
                 code: String,
                 description: String,
             ) -> Self {
                 Item {
-                    amount: Amount { value: amount },
+                    amount: Amount { value: amount, details: None },
                     quantity,
                     total_amount: Amount {
                         value: total_amount,
+                        details: None,
                     },
                     name,
                     code,
                     description,
                 }
             }
+
+            pub fn with_amount_details(mut self, details: AmountDetails) -> Self {
+                self.amount.details = Some(details);
+                self
+            }
         }
 
+        /// Values are decimal strings in the major unit, e.g. "1234.50", so nothing is lost to floating point.
         #[derive(Debug, Clone, Serialize, Deserialize)]
         pub struct Amount {
-            pub value: f64,
+            pub value: String,
+
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub details: Option<AmountDetails>,
+        }
+
+        #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        pub struct AmountDetails {
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub discount: Option<String>,
+
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub service_charge: Option<String>,
+
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub tax: Option<String>,
+
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub subtotal: Option<String>,
+        }
+
+        #[derive(Debug, Clone, Serialize, Deserialize)]
+        pub struct RedirectUrl {
+            pub success: String,
+
+            pub failure: String,
+
+            pub cancel: String,
+        }
+
+        #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        pub struct Buyer {
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub first_name: Option<String>,
+
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub last_name: Option<String>,
+
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub contact: Option<Contact>,
+        }
+
+        #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+        pub struct Contact {
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub phone: Option<String>,
+
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub email: Option<String>,
         }
 
         #[derive(Debug, Clone, Serialize, Deserialize)]
         pub struct TotalAmount {
-            pub value: f64,
+            pub value: String,
 
             pub currency: String,
+
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub details: Option<AmountDetails>,
         }
     }
 
@@ -92,4 +186,87 @@ pub mod checkout {
             pub redirect_url: String,
         }
     }
+
+    pub mod details {
+        use serde::{Deserialize, Serialize};
+
+        use crate::payments::data_transfer_objects::maya_webhook::CheckoutTotalAmount;
+
+        /// Only the fields needed to confirm a payment are read from Maya's checkout details.
+        #[derive(Debug, Clone, Serialize, Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        pub struct CheckoutDetailsResponse {
+            pub id: String,
+
+            pub payment_status: String,
+
+            #[serde(default)]
+            pub total_amount: Option<CheckoutTotalAmount>,
+        }
+    }
+}
+
+pub mod refund {
+    pub mod request {
+        use serde::{Deserialize, Serialize};
+
+        #[derive(Debug, Clone, Serialize, Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        pub struct RefundRequest {
+            pub total_amount: RefundAmount,
+
+            pub reason: String,
+        }
+
+        /// Unlike checkouts, Maya takes the refund amount as a json number.
+        #[derive(Debug, Clone, Serialize, Deserialize)]
+        pub struct RefundAmount {
+            pub amount: serde_json::Number,
+
+            pub currency: String,
+        }
+    }
+
+    pub mod response {
+        use serde::{Deserialize, Serialize};
+
+        #[derive(Debug, Clone, Serialize, Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        pub struct RefundResponse {
+            pub id: String,
+
+            pub status: String,
+        }
+    }
+}
+
+pub mod capture {
+    pub mod request {
+        use serde::{Deserialize, Serialize};
+
+        #[derive(Debug, Clone, Serialize, Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        pub struct CaptureRequest {
+            pub capture_amount: CaptureAmount,
+        }
+
+        /// Like refunds, the amount is a json number.
+        #[derive(Debug, Clone, Serialize, Deserialize)]
+        pub struct CaptureAmount {
+            pub amount: serde_json::Number,
+
+            pub currency: String,
+        }
+    }
+}
+
+pub mod void {
+    pub mod request {
+        use serde::{Deserialize, Serialize};
+
+        #[derive(Debug, Clone, Serialize, Deserialize)]
+        pub struct VoidRequest {
+            pub reason: String,
+        }
+    }
 }
@@ -0,0 +1,366 @@
+pub mod data_transfer_objects;
+use async_trait::async_trait;
+use axum::http::HeaderMap;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::{baskets::data_transfer_objects::{Basket, BasketItem}, money::Money, payments::{constants::PAYMENT_PROVIDER_STRIPE, data_models::CheckoutData, data_transfer_objects::{CaptureMode, PaymentStatus}}};
+
+use self::data_transfer_objects::{checkout::{self, request::LineItem}, payment_intent::response::PaymentIntent, refund, webhook::CheckoutSession};
+
+use super::{CheckoutOptions, CheckoutStatus, PaymentProvider};
+
+/// How old a signed webhook may be before it is treated as a replay.
+const WEBHOOK_TOLERANCE_SECONDS: i64 = 300;
+
+pub struct StripeProvider {
+    base_url: String,
+    secret_key: String,
+    webhook_secret: String,
+    success_url: String,
+    cancel_url: String,
+}
+
+
+impl StripeProvider {
+    pub fn new(base_url: String, secret_key: String, webhook_secret: String, success_url: String, cancel_url: String) -> Self {
+        Self {
+            base_url,
+            secret_key,
+            webhook_secret,
+            success_url,
+            cancel_url,
+        }
+    }
+}
+
+
+#[async_trait]
+impl PaymentProvider for StripeProvider {
+    fn get_name(&self) -> String {
+        PAYMENT_PROVIDER_STRIPE.to_string()
+    }
+
+    async fn prepare_checkout(&self, basket: &Basket, options: &CheckoutOptions) -> anyhow::Result<CheckoutData> {
+
+        let client = reqwest::Client::new();
+        let line_items: Vec<LineItem> = basket.basket_items.iter().filter_map(basket_item_to_line_item).collect();
+        // Stripe has no failure url, failed payments stay on its checkout page.
+        let (success_url, cancel_url) = match &options.redirect_urls {
+            Some(redirect_urls) => (redirect_urls.success.clone(), redirect_urls.cancel.clone()),
+            None => (self.success_url.clone(), self.cancel_url.clone()),
+        };
+        let mut request = checkout::request::CheckoutSessionRequest::new(
+            success_url,
+            cancel_url,
+            basket.id.to_string(),
+            line_items,
+        );
+        if let Some(email) = options.buyer.as_ref().and_then(|buyer| buyer.email.clone()) {
+            request = request.with_customer_email(email);
+        }
+        if options.capture_mode == CaptureMode::Manual {
+            request = request.with_manual_capture();
+        }
+
+        let response = client.post(format!("{}/{}", &self.base_url, "v1/checkout/sessions"))
+            .bearer_auth(&self.secret_key)
+            .form(&request.to_form())
+            .send()
+            .await?;
+
+        match response.error_for_status_ref() {
+            Ok(_) => {
+                let data = response.json::<checkout::response::CheckoutSessionResponse>().await?;
+                Ok(CheckoutData::new(data.id, data.url))
+            },
+            Err(err) => {
+                tracing::error!("Response Text: {:?}", response.text().await?);
+                tracing::error!("Error: {:?}", err);
+                Err(anyhow::anyhow!("Something went wrong generating checkout data."))
+            }
+        }
+    }
+
+    async fn expire_checkout(&self, checkout_id: &str) -> anyhow::Result<()> {
+        let client = reqwest::Client::new();
+        let response = client.post(format!("{}/{}/{}/expire", &self.base_url, "v1/checkout/sessions", checkout_id))
+            .bearer_auth(&self.secret_key)
+            .send()
+            .await?;
+
+        match response.error_for_status_ref() {
+            Ok(_) => Ok(()),
+            Err(err) => {
+                tracing::error!("Response Text: {:?}", response.text().await?);
+                tracing::error!("Error: {:?}", err);
+                Err(anyhow::anyhow!("Something went wrong expiring checkout."))
+            }
+        }
+    }
+
+    fn verify_webhook(&self, headers: &HeaderMap, body: &[u8]) -> anyhow::Result<()> {
+        let signature = headers
+            .get("Stripe-Signature")
+            .and_then(|value| value.to_str().ok())
+            .ok_or(anyhow::anyhow!("Missing Stripe-Signature header"))?;
+        verify_signature(&self.webhook_secret, signature, body, chrono::Utc::now().timestamp())
+    }
+
+    async fn get_status(&self, checkout_id: &str) -> anyhow::Result<CheckoutStatus> {
+        let data = self.get_checkout_session(checkout_id).await?;
+        let status = match (data.status.as_deref(), data.payment_status.as_deref(), &data.payment_intent) {
+            (_, Some("paid"), _) => PaymentStatus::Paid,
+            (Some("expired"), _, _) => PaymentStatus::Expired,
+            // Manual capture sessions complete unpaid, the payment intent tells whether the money is held.
+            (Some("complete"), _, Some(payment_intent)) => {
+                return Ok(map_payment_intent(&self.get_payment_intent(payment_intent).await?));
+            }
+            _ => PaymentStatus::Pending,
+        };
+        Ok(CheckoutStatus {
+            status,
+            amount: to_money(data.amount_total, data.currency.as_deref()),
+        })
+    }
+
+    async fn refund(&self, checkout_id: &str, amount: &Money, reason: &str) -> anyhow::Result<String> {
+        let payment_intent = self.get_payment_intent_id(checkout_id).await?;
+
+        let client = reqwest::Client::new();
+        // Stripe's own `reason` only takes a few fixed values, ours goes in the metadata.
+        let form = [
+            ("payment_intent", payment_intent),
+            ("amount", amount.amount.to_string()),
+            ("metadata[reason]", reason.to_string()),
+        ];
+        let response = client.post(format!("{}/{}", &self.base_url, "v1/refunds"))
+            .bearer_auth(&self.secret_key)
+            .form(&form)
+            .send()
+            .await?;
+
+        match response.error_for_status_ref() {
+            Ok(_) => {
+                let data = response.json::<refund::response::RefundResponse>().await?;
+                Ok(data.id)
+            },
+            Err(err) => {
+                tracing::error!("Response Text: {:?}", response.text().await?);
+                tracing::error!("Error: {:?}", err);
+                Err(anyhow::anyhow!("Something went wrong refunding payment."))
+            }
+        }
+    }
+
+    async fn capture(&self, checkout_id: &str, amount: &Money) -> anyhow::Result<()> {
+        let payment_intent = self.get_payment_intent_id(checkout_id).await?;
+
+        let client = reqwest::Client::new();
+        let form = [("amount_to_capture", amount.amount.to_string())];
+        let response = client.post(format!("{}/{}/{}/capture", &self.base_url, "v1/payment_intents", payment_intent))
+            .bearer_auth(&self.secret_key)
+            .form(&form)
+            .send()
+            .await?;
+
+        match response.error_for_status_ref() {
+            Ok(_) => Ok(()),
+            Err(err) => {
+                tracing::error!("Response Text: {:?}", response.text().await?);
+                tracing::error!("Error: {:?}", err);
+                Err(anyhow::anyhow!("Something went wrong capturing payment."))
+            }
+        }
+    }
+
+    /// Stripe only takes a few fixed cancellation reasons, ours is only logged.
+    async fn void(&self, checkout_id: &str, reason: &str) -> anyhow::Result<()> {
+        let payment_intent = self.get_payment_intent_id(checkout_id).await?;
+        tracing::info!("Cancelling Stripe payment intent {}: {}", payment_intent, reason);
+
+        let client = reqwest::Client::new();
+        let response = client.post(format!("{}/{}/{}/cancel", &self.base_url, "v1/payment_intents", payment_intent))
+            .bearer_auth(&self.secret_key)
+            .send()
+            .await?;
+
+        match response.error_for_status_ref() {
+            Ok(_) => Ok(()),
+            Err(err) => {
+                tracing::error!("Response Text: {:?}", response.text().await?);
+                tracing::error!("Error: {:?}", err);
+                Err(anyhow::anyhow!("Something went wrong voiding payment."))
+            }
+        }
+    }
+}
+
+impl StripeProvider {
+    /// Refunds, captures and cancellations are made against the session's payment intent.
+    async fn get_payment_intent_id(&self, checkout_id: &str) -> anyhow::Result<String> {
+        let session = self.get_checkout_session(checkout_id).await?;
+        session
+            .payment_intent
+            .ok_or(anyhow::anyhow!("Checkout session {} has no payment", checkout_id))
+    }
+
+    async fn get_payment_intent(&self, payment_intent: &str) -> anyhow::Result<PaymentIntent> {
+        let client = reqwest::Client::new();
+        let response = client.get(format!("{}/{}/{}", &self.base_url, "v1/payment_intents", payment_intent))
+            .bearer_auth(&self.secret_key)
+            .send()
+            .await?;
+
+        match response.error_for_status_ref() {
+            Ok(_) => Ok(response.json::<PaymentIntent>().await?),
+            Err(err) => {
+                tracing::error!("Response Text: {:?}", response.text().await?);
+                tracing::error!("Error: {:?}", err);
+                Err(anyhow::anyhow!("Something went wrong getting payment intent."))
+            }
+        }
+    }
+
+    async fn get_checkout_session(&self, checkout_id: &str) -> anyhow::Result<CheckoutSession> {
+        let client = reqwest::Client::new();
+        let response = client.get(format!("{}/{}/{}", &self.base_url, "v1/checkout/sessions", checkout_id))
+            .bearer_auth(&self.secret_key)
+            .send()
+            .await?;
+
+        match response.error_for_status_ref() {
+            Ok(_) => Ok(response.json::<CheckoutSession>().await?),
+            Err(err) => {
+                tracing::error!("Response Text: {:?}", response.text().await?);
+                tracing::error!("Error: {:?}", err);
+                Err(anyhow::anyhow!("Something went wrong getting checkout status."))
+            }
+        }
+    }
+}
+
+/// Authorized intents report what is held, captured ones what was received.
+fn map_payment_intent(payment_intent: &PaymentIntent) -> CheckoutStatus {
+    let status = map_payment_intent_status(&payment_intent.status);
+    let amount = match status {
+        PaymentStatus::Authorized => payment_intent.amount_capturable,
+        PaymentStatus::Paid => payment_intent.amount_received,
+        _ => None,
+    };
+    CheckoutStatus {
+        status,
+        amount: to_money(amount, payment_intent.currency.as_deref()),
+    }
+}
+
+/// Stripe amounts are already in the minor unit, only the currency needs uppercasing.
+fn to_money(amount: Option<i64>, currency: Option<&str>) -> Option<Money> {
+    match (amount, currency) {
+        (Some(amount), Some(currency)) => Some(Money::new(amount, &currency.to_uppercase())),
+        _ => None,
+    }
+}
+
+fn map_payment_intent_status(status: &str) -> PaymentStatus {
+    match status {
+        "requires_capture" => PaymentStatus::Authorized,
+        "succeeded" => PaymentStatus::Paid,
+        "canceled" => PaymentStatus::Voided,
+        _ => PaymentStatus::Pending,
+    }
+}
+
+/// Stripe does not accept negative line items, so the discount, fee and tax are folded into the item's amount.
+fn basket_item_to_line_item(basket_item: &BasketItem) -> Option<LineItem> {
+    if basket_item.basketed_inventories.is_empty() {
+        return None;
+    }
+    let name = match &basket_item.bundle {
+        Some(bundle) => bundle.name.to_string(),
+        None => basket_item.basketed_inventories[0].name.to_string(),
+    };
+    Some(LineItem {
+        currency: basket_item.price.currency.to_lowercase(),
+        unit_amount: basket_item.price.amount - basket_item.discount.amount + basket_item.fee.amount + basket_item.tax.amount,
+        quantity: 1,
+        name,
+    })
+}
+
+/// Checks a `Stripe-Signature` header of the form `t=<timestamp>,v1=<hex hmac>[,v1=...]`.
+/// The signed payload is `<timestamp>.<body>`.
+fn verify_signature(webhook_secret: &str, signature: &str, body: &[u8], now: i64) -> anyhow::Result<()> {
+    let mut timestamp = None;
+    let mut signatures = vec![];
+    for part in signature.split(',') {
+        match part.trim().split_once('=') {
+            Some(("t", value)) => timestamp = value.parse::<i64>().ok(),
+            Some(("v1", value)) => signatures.push(value),
+            _ => {}
+        }
+    }
+    let timestamp = timestamp.ok_or(anyhow::anyhow!("Missing timestamp in Stripe-Signature"))?;
+    if (now - timestamp).abs() > WEBHOOK_TOLERANCE_SECONDS {
+        return Err(anyhow::anyhow!("Stripe-Signature timestamp is outside the tolerance"));
+    }
+
+    for signature in signatures {
+        let Ok(signature) = hex::decode(signature) else {
+            continue;
+        };
+        let mut mac = Hmac::<Sha256>::new_from_slice(webhook_secret.as_bytes())?;
+        mac.update(timestamp.to_string().as_bytes());
+        mac.update(b".");
+        mac.update(body);
+        if mac.verify_slice(&signature).is_ok() {
+            return Ok(());
+        }
+    }
+    Err(anyhow::anyhow!("No matching signature in Stripe-Signature"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map_payment_intent() {
+        let payment_intent: PaymentIntent = serde_json::from_str(
+            r#"{"id": "pi_1", "status": "requires_capture", "amount_capturable": 10000, "amount_received": 0, "currency": "php"}"#,
+        ).unwrap();
+
+        assert_eq!(
+            map_payment_intent(&payment_intent),
+            CheckoutStatus { status: PaymentStatus::Authorized, amount: Some(Money::new(10000, "PHP")) }
+        );
+    }
+
+    fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(format!("{}.", timestamp).as_bytes());
+        mac.update(body);
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    #[test]
+    fn test_map_payment_intent_status() {
+        assert_eq!(map_payment_intent_status("requires_capture"), PaymentStatus::Authorized);
+        assert_eq!(map_payment_intent_status("succeeded"), PaymentStatus::Paid);
+        assert_eq!(map_payment_intent_status("canceled"), PaymentStatus::Voided);
+        assert_eq!(map_payment_intent_status("processing"), PaymentStatus::Pending);
+    }
+
+    #[test]
+    fn test_verify_signature() {
+        let body = br#"{"id":"evt_1"}"#;
+        let timestamp = 1_700_000_000;
+        let header = format!("t={},v1={}", timestamp, sign("whsec_test", timestamp, body));
+
+        assert!(verify_signature("whsec_test", &header, body, timestamp + 10).is_ok());
+        assert!(verify_signature("whsec_other", &header, body, timestamp + 10).is_err());
+        assert!(verify_signature("whsec_test", &header, br#"{"id":"evt_2"}"#, timestamp + 10).is_err());
+        assert!(verify_signature("whsec_test", &header, body, timestamp + WEBHOOK_TOLERANCE_SECONDS + 1).is_err());
+        assert!(verify_signature("whsec_test", "v1=abc", body, timestamp).is_err());
+    }
+}
@@ -0,0 +1,156 @@
+pub mod checkout {
+    pub mod request {
+        /// Stripe takes form encoded requests with nested keys, e.g. `line_items[0][quantity]`.
+        #[derive(Debug, Clone)]
+        pub struct CheckoutSessionRequest {
+            pub success_url: String,
+            pub cancel_url: String,
+            pub client_reference_id: String,
+            pub line_items: Vec<LineItem>,
+            /// Prefills the email on the checkout page.
+            pub customer_email: Option<String>,
+            /// Only authorizes the payment, it is captured or cancelled later through its payment intent.
+            pub manual_capture: bool,
+        }
+
+        impl CheckoutSessionRequest {
+            pub fn new(
+                success_url: String,
+                cancel_url: String,
+                client_reference_id: String,
+                line_items: Vec<LineItem>,
+            ) -> Self {
+                CheckoutSessionRequest {
+                    success_url,
+                    cancel_url,
+                    client_reference_id,
+                    line_items,
+                    customer_email: None,
+                    manual_capture: false,
+                }
+            }
+
+            pub fn with_customer_email(mut self, customer_email: String) -> Self {
+                self.customer_email = Some(customer_email);
+                self
+            }
+
+            pub fn with_manual_capture(mut self) -> Self {
+                self.manual_capture = true;
+                self
+            }
+
+            pub fn to_form(&self) -> Vec<(String, String)> {
+                let mut form = vec![
+                    ("mode".to_string(), "payment".to_string()),
+                    ("success_url".to_string(), self.success_url.clone()),
+                    ("cancel_url".to_string(), self.cancel_url.clone()),
+                    ("client_reference_id".to_string(), self.client_reference_id.clone()),
+                    ("metadata[basket_id]".to_string(), self.client_reference_id.clone()),
+                ];
+                if let Some(customer_email) = &self.customer_email {
+                    form.push(("customer_email".to_string(), customer_email.clone()));
+                }
+                if self.manual_capture {
+                    form.push(("payment_intent_data[capture_method]".to_string(), "manual".to_string()));
+                }
+                for (index, line_item) in self.line_items.iter().enumerate() {
+                    let key = |name: &str| format!("line_items[{}]{}", index, name);
+                    form.push((key("[quantity]"), line_item.quantity.to_string()));
+                    form.push((key("[price_data][currency]"), line_item.currency.clone()));
+                    form.push((key("[price_data][unit_amount]"), line_item.unit_amount.to_string()));
+                    form.push((key("[price_data][product_data][name]"), line_item.name.clone()));
+                }
+                form
+            }
+        }
+
+        #[derive(Debug, Clone)]
+        pub struct LineItem {
+            /// Lowercase ISO 4217 code.
+            pub currency: String,
+            /// In the currency's minor unit.
+            pub unit_amount: i64,
+            pub quantity: i32,
+            pub name: String,
+        }
+    }
+
+    pub mod response {
+        use serde::{Deserialize, Serialize};
+
+        #[derive(Debug, Clone, Serialize, Deserialize)]
+        pub struct CheckoutSessionResponse {
+            pub id: String,
+            pub url: String,
+        }
+    }
+}
+
+pub mod webhook {
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct StripeEvent {
+        pub id: String,
+        pub r#type: String,
+        pub data: StripeEventData,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct StripeEventData {
+        pub object: CheckoutSession,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct CheckoutSession {
+        pub id: String,
+        /// `open`, `complete` or `expired`.
+        pub status: Option<String>,
+        pub payment_status: Option<String>,
+        /// In the currency's minor unit.
+        #[serde(default)]
+        pub amount_total: Option<i64>,
+        /// Lowercase ISO 4217 code.
+        #[serde(default)]
+        pub currency: Option<String>,
+        pub client_reference_id: Option<String>,
+        /// Set once the session is paid, refunds are made against it.
+        #[serde(default)]
+        pub payment_intent: Option<String>,
+    }
+}
+
+pub mod payment_intent {
+    pub mod response {
+        use serde::{Deserialize, Serialize};
+
+        #[derive(Debug, Clone, Serialize, Deserialize)]
+        pub struct PaymentIntent {
+            pub id: String,
+            /// e.g. `requires_capture`, `canceled` or `succeeded`.
+            pub status: String,
+            /// Held and not yet captured, in the currency's minor unit.
+            #[serde(default)]
+            pub amount_capturable: Option<i64>,
+            /// Captured so far, in the currency's minor unit.
+            #[serde(default)]
+            pub amount_received: Option<i64>,
+            /// Lowercase ISO 4217 code.
+            #[serde(default)]
+            pub currency: Option<String>,
+        }
+    }
+}
+
+pub mod refund {
+    pub mod response {
+        use serde::{Deserialize, Serialize};
+
+        #[derive(Debug, Clone, Serialize, Deserialize)]
+        pub struct RefundResponse {
+            pub id: String,
+            pub status: Option<String>,
+        }
+    }
+}
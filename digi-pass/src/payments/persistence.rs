@@ -2,9 +2,10 @@ use std::str::FromStr;
 
 use axum::async_trait;
 use bson::{doc, oid::ObjectId};
-use mongodb::{Client, Collection};
+use chrono::{DateTime, Utc};
+use mongodb::{error::{ErrorKind, WriteFailure}, options::{FindOptions, IndexOptions}, Client, Collection, IndexModel};
 
-use super::data_models::Payment;
+use super::{data_models::{Payment, WebhookInboxEntry}, data_transfer_objects::{PaymentStatus, WebhookStatus}};
 
 #[async_trait]
 pub trait PaymentRepository: Send + Sync {
@@ -13,6 +14,11 @@ pub trait PaymentRepository: Send + Sync {
     async fn find_one_by_checkout_id(&self, checkout_id: &str)
         -> anyhow::Result<Option<Payment>>;
     async fn find_by_basket_id(&self, basket_id: &str) -> anyhow::Result<Vec<Payment>>;
+    async fn find_one_by_id(&self, id: ObjectId) -> anyhow::Result<Option<Payment>>;
+    async fn find_refunds(&self, refunded_payment_id: ObjectId) -> anyhow::Result<Vec<Payment>>;
+    /// A page of at most `limit` payments in the order they were created, starting after the payment `after_id`.
+    async fn find_pending_created_before(&self, created_before: DateTime<Utc>, after_id: Option<ObjectId>, limit: i64) -> anyhow::Result<Vec<Payment>>;
+    async fn find_by_status(&self, status: PaymentStatus) -> anyhow::Result<Vec<Payment>>;
 }
 
 pub struct MongoDbPaymentRepository {
@@ -90,4 +96,175 @@ impl PaymentRepository for MongoDbPaymentRepository {
         }
         return Ok(payments);
     }
+
+    async fn find_pending_created_before(&self, created_before: DateTime<Utc>, after_id: Option<ObjectId>, limit: i64) -> anyhow::Result<Vec<Payment>> {
+        // Paged by id rather than created_at, so payments created at the same instant are not skipped.
+        let options = FindOptions::builder()
+            .sort(doc! {"_id": 1})
+            .limit(limit)
+            .build();
+        let mut filter = doc! {"status": PaymentStatus::Pending.as_str(), "created_at": {"$lt": created_before}};
+        if let Some(after_id) = after_id {
+            filter.insert("_id", doc! {"$gt": after_id});
+        }
+        let mut result = self
+            .get_collection()
+            .find(Some(filter), options)
+            .await?;
+        let mut payments = vec![];
+        while result.advance().await? {
+            payments.push(result.deserialize_current()?)
+        }
+        Ok(payments)
+    }
+
+    async fn find_by_status(&self, status: PaymentStatus) -> anyhow::Result<Vec<Payment>> {
+        let options = FindOptions::builder()
+            .sort(doc! {"created_at": 1})
+            .limit(100)
+            .build();
+        let mut result = self
+            .get_collection()
+            .find(Some(doc! {"status": status.as_str()}), options)
+            .await?;
+        let mut payments = vec![];
+        while result.advance().await? {
+            payments.push(result.deserialize_current()?)
+        }
+        Ok(payments)
+    }
+
+    async fn find_one_by_id(&self, id: ObjectId) -> anyhow::Result<Option<Payment>> {
+        let result = self
+            .get_collection()
+            .find_one(Some(doc! {"_id": id}), None)
+            .await?;
+        Ok(result)
+    }
+
+    async fn find_refunds(&self, refunded_payment_id: ObjectId) -> anyhow::Result<Vec<Payment>> {
+        let mut result = self
+            .get_collection()
+            .find(
+                Some(doc! {"refund_data.refunded_payment_id": refunded_payment_id}),
+                None,
+            )
+            .await?;
+        let mut payments = vec![];
+        while result.advance().await? {
+            payments.push(result.deserialize_current()?)
+        }
+        Ok(payments)
+    }
+}
+
+
+#[async_trait]
+pub trait WebhookInboxRepository: Send + Sync {
+    async fn ensure_indexes(&self) -> anyhow::Result<()>;
+    /// Returns `None` when the provider already sent this event.
+    async fn save_if_new(&self, entry: &WebhookInboxEntry) -> anyhow::Result<Option<String>>;
+    async fn update(&self, entry: &mut WebhookInboxEntry) -> anyhow::Result<()>;
+    async fn find_one_by_id(&self, id: ObjectId) -> anyhow::Result<Option<WebhookInboxEntry>>;
+    async fn find_due(&self, now: DateTime<Utc>) -> anyhow::Result<Vec<WebhookInboxEntry>>;
+    async fn find_failed(&self) -> anyhow::Result<Vec<WebhookInboxEntry>>;
+}
+
+pub struct MongoDbWebhookInboxRepository {
+    pub client: Client,
+    pub database: String,
+    pub collection: String,
+}
+
+impl MongoDbWebhookInboxRepository {
+    pub fn new(client: Client, database: String) -> Self {
+        MongoDbWebhookInboxRepository {
+            client,
+            database,
+            collection: "WebhookInbox".to_string(),
+        }
+    }
+
+    fn get_collection(&self) -> Collection<WebhookInboxEntry> {
+        let database = self.client.database(&self.database[..]);
+        database.collection::<WebhookInboxEntry>(&self.collection[..])
+    }
+
+    async fn find_many(&self, filter: bson::Document) -> anyhow::Result<Vec<WebhookInboxEntry>> {
+        let options = FindOptions::builder()
+            .sort(doc! {"received_at": 1})
+            .limit(100)
+            .build();
+        let mut result = self.get_collection().find(Some(filter), options).await?;
+        let mut entries = vec![];
+        while result.advance().await? {
+            entries.push(result.deserialize_current()?)
+        }
+        Ok(entries)
+    }
+}
+
+#[async_trait]
+impl WebhookInboxRepository for MongoDbWebhookInboxRepository {
+    async fn ensure_indexes(&self) -> anyhow::Result<()> {
+        let index = IndexModel::builder()
+            .keys(doc! {"provider": 1, "event_id": 1})
+            .options(IndexOptions::builder().unique(true).build())
+            .build();
+        self.get_collection().create_index(index, None).await?;
+        Ok(())
+    }
+
+    async fn save_if_new(&self, entry: &WebhookInboxEntry) -> anyhow::Result<Option<String>> {
+        match self.get_collection().insert_one(entry, None).await {
+            Ok(result) => {
+                let hex = result
+                    .inserted_id
+                    .as_object_id()
+                    .ok_or(anyhow::anyhow!("Failed to get object id"))?
+                    .to_hex();
+                Ok(Some(hex))
+            }
+            Err(err) => match *err.kind {
+                // Duplicate key on the provider and event id index.
+                ErrorKind::Write(WriteFailure::WriteError(ref write_error)) if write_error.code == 11000 => Ok(None),
+                _ => Err(err.into()),
+            },
+        }
+    }
+
+    async fn update(&self, entry: &mut WebhookInboxEntry) -> anyhow::Result<()> {
+        let filter = doc! {"_id": entry.id.ok_or(anyhow::anyhow!("Failed to get object id"))?, "concurrency_stamp": &entry.concurrency_stamp};
+
+        entry.concurrency_stamp = ObjectId::new().to_hex();
+
+        let update = doc! {"$set": bson::to_bson(&entry)? };
+
+        let update_result = self.get_collection().update_one(filter, update, None).await?;
+        if update_result.matched_count == 0 {
+            return Err(anyhow::anyhow!("Failed to update webhook"));
+        }
+        Ok(())
+    }
+
+    async fn find_one_by_id(&self, id: ObjectId) -> anyhow::Result<Option<WebhookInboxEntry>> {
+        let result = self
+            .get_collection()
+            .find_one(Some(doc! {"_id": id}), None)
+            .await?;
+        Ok(result)
+    }
+
+    async fn find_due(&self, now: DateTime<Utc>) -> anyhow::Result<Vec<WebhookInboxEntry>> {
+        // A processing webhook's next attempt is when its lease runs out.
+        self.find_many(doc! {
+            "status": {"$in": [WebhookStatus::Pending.as_str(), WebhookStatus::Processing.as_str()]},
+            "next_attempt_at": {"$lte": now},
+        })
+        .await
+    }
+
+    async fn find_failed(&self) -> anyhow::Result<Vec<WebhookInboxEntry>> {
+        self.find_many(doc! {"status": WebhookStatus::Failed.as_str()}).await
+    }
 }
@@ -0,0 +1,46 @@
+use crate::{baskets::application::BasketService, orders::application::OrderService};
+
+use super::{application::PaymentService, constants::AUTHORIZATION_HOLD_DAYS, data_transfer_objects::{PaymentStatus, ReconciliationReport}};
+
+/// Catches up on webhooks that never arrived. Baskets whose payment turned out paid are purchased
+/// the same way the webhook would have.
+pub async fn reconcile_pending_payments(
+    older_than: chrono::Duration,
+    payment_service: &PaymentService,
+    basket_service: &BasketService,
+    order_service: &OrderService,
+) -> anyhow::Result<ReconciliationReport> {
+    let mut report = payment_service
+        .reconcile_pending_payments(chrono::Utc::now() - older_than)
+        .await?;
+
+    for mismatch in report.mismatches.iter_mut().filter(|mismatch| mismatch.provider_status == PaymentStatus::Paid) {
+        match basket_service
+            .purchase_paid_basket(payment_service, order_service, &mismatch.basket_id)
+            .await
+        {
+            Ok(order_id) => mismatch.order_id = order_id,
+            Err(err) => {
+                tracing::error!("Failed purchasing basket {}: {:?}", mismatch.basket_id, err);
+                report.errors.push(format!("Basket {}: {}", mismatch.basket_id, err));
+            }
+        }
+    }
+
+    // Authorized payments are held like the webhook would have, until staff capture or void them.
+    let held_until = chrono::Utc::now() + chrono::Duration::days(AUTHORIZATION_HOLD_DAYS);
+    for mismatch in report.mismatches.iter().filter(|mismatch| mismatch.provider_status == PaymentStatus::Authorized) {
+        if let Err(err) = basket_service.hold_basket(&mismatch.basket_id, held_until).await {
+            tracing::error!("Failed holding basket {}: {:?}", mismatch.basket_id, err);
+            report.errors.push(format!("Basket {}: {}", mismatch.basket_id, err));
+        }
+    }
+
+    tracing::info!(
+        checked = report.checked,
+        mismatches = report.mismatches.len(),
+        errors = report.errors.len(),
+        "payment reconciliation done"
+    );
+    Ok(report)
+}
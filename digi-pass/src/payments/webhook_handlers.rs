@@ -1,18 +1,211 @@
-use super::{application::PaymentService, data_transfer_objects::maya_webhook::MayaWebhookRequest};
+use crate::{baskets::application::BasketService, money::Money, orders::application::OrderService};
 
-pub async fn handle_maya_checkout_webhook(webhook:MayaWebhookRequest, payment_service: &PaymentService) -> anyhow::Result<()> {
+use super::{
+    application::PaymentService,
+    constants::{AUTHORIZATION_HOLD_DAYS, PAYMENT_PROVIDER_FAKE, PAYMENT_PROVIDER_MAYA, PAYMENT_PROVIDER_STRIPE},
+    data_transfer_objects::maya_webhook::{MayaWebhookEvent, MayaWebhookRequest},
+    data_transfer_objects::PaymentStatus,
+    errors::PaymentErrors,
+    payment_providers::{fake_provider::FakeWebhookRequest, stripe_provider::data_transfer_objects::webhook::StripeEvent, CheckoutStatus},
+};
+
+/// Identifies the event so redeliveries can be dropped.
+pub fn get_event_id(provider: &str, body: &[u8]) -> anyhow::Result<String> {
+    match provider {
+        PAYMENT_PROVIDER_MAYA => {
+            let webhook = MayaWebhookRequest::parse(body).map_err(|_| PaymentErrors::InvalidWebhook)?;
+            Ok(webhook.event_id())
+        }
+        PAYMENT_PROVIDER_STRIPE => {
+            let event: StripeEvent = serde_json::from_slice(body).map_err(|_| PaymentErrors::InvalidWebhook)?;
+            Ok(event.id)
+        }
+        PAYMENT_PROVIDER_FAKE => {
+            let webhook: FakeWebhookRequest = serde_json::from_slice(body).map_err(|_| PaymentErrors::InvalidWebhook)?;
+            Ok(format!("{}:{}", webhook.id, webhook.status))
+        }
+        _ => Err(PaymentErrors::ProviderNotSupported(provider.to_string()).into()),
+    }
+}
+
+pub async fn handle_webhook(
+    provider: &str,
+    body: &[u8],
+    payment_service: &PaymentService,
+    basket_service: &BasketService,
+    order_service: &OrderService,
+) -> anyhow::Result<()> {
+    match provider {
+        PAYMENT_PROVIDER_MAYA => handle_maya_checkout_webhook(body, payment_service, basket_service, order_service).await,
+        PAYMENT_PROVIDER_STRIPE => handle_stripe_checkout_webhook(body, payment_service, basket_service, order_service).await,
+        PAYMENT_PROVIDER_FAKE => {
+            let webhook: FakeWebhookRequest = serde_json::from_slice(body)?;
+            handle_checkout_update(PAYMENT_PROVIDER_FAKE, &webhook.id, Some(&webhook.amount), payment_service, basket_service, order_service).await
+        }
+        _ => Err(PaymentErrors::ProviderNotSupported(provider.to_string()).into()),
+    }
+}
+
+pub async fn handle_maya_checkout_webhook(
+    body: &[u8],
+    payment_service: &PaymentService,
+    basket_service: &BasketService,
+    order_service: &OrderService,
+) -> anyhow::Result<()> {
+    let webhook = MayaWebhookRequest::parse(body)?;
     tracing::debug!("Webhook received: {:?}", webhook);
 
-    //validate event type
-    if webhook.status != "PAYMENT_SUCCESS" {
-        tracing::error!("Payment failed or pneding: {:?}", &webhook.status);
-        return  Ok(());
+    match webhook.event() {
+        MayaWebhookEvent::Other(event) => {
+            tracing::info!("Ignoring Maya event {} for checkout {}", event, webhook.checkout_id());
+            Ok(())
+        }
+        // Failed and expired checkouts are handled too, the status is taken from the provider either way.
+        MayaWebhookEvent::PaymentSuccess
+        | MayaWebhookEvent::PaymentFailed
+        | MayaWebhookEvent::PaymentExpired
+        | MayaWebhookEvent::PaymentCancelled
+        | MayaWebhookEvent::Authorized
+        | MayaWebhookEvent::Captured
+        | MayaWebhookEvent::Voided => {
+            // Only logged, an amount we cannot read must not fail the webhook.
+            let paid_amount = webhook.amount().unwrap_or_else(|err| {
+                tracing::warn!("Ignoring unreadable amount of Maya webhook for checkout {}: {:?}", webhook.checkout_id(), err);
+                None
+            });
+            handle_checkout_update(PAYMENT_PROVIDER_MAYA, webhook.checkout_id(), paid_amount.as_ref(), payment_service, basket_service, order_service).await
+        }
+    }
+}
+
+/// Expects a body whose signature was already verified.
+pub async fn handle_stripe_checkout_webhook(
+    body: &[u8],
+    payment_service: &PaymentService,
+    basket_service: &BasketService,
+    order_service: &OrderService,
+) -> anyhow::Result<()> {
+    let event: StripeEvent = serde_json::from_slice(body)?;
+    tracing::debug!("Webhook received: {:?}", event);
+
+    match event.r#type.as_str() {
+        "checkout.session.completed"
+        | "checkout.session.async_payment_succeeded"
+        | "checkout.session.async_payment_failed"
+        | "checkout.session.expired" => {}
+        _ => {
+            tracing::info!("Ignoring Stripe event {}: {}", event.id, event.r#type);
+            return Ok(());
+        }
+    }
+
+    let session = &event.data.object;
+    let paid_amount = match (session.amount_total, &session.currency) {
+        (Some(amount_total), Some(currency)) => Some(Money::new(amount_total, &currency.to_uppercase())),
+        _ => None,
+    };
+
+    handle_checkout_update(PAYMENT_PROVIDER_STRIPE, &session.id, paid_amount.as_ref(), payment_service, basket_service, order_service).await
+}
+
+/// `webhook_amount` is what the webhook says was paid. It is only logged when it differs,
+/// what we charged is checked against the amount the provider reports.
+async fn handle_checkout_update(
+    provider: &str,
+    checkout_id: &str,
+    webhook_amount: Option<&Money>,
+    payment_service: &PaymentService,
+    basket_service: &BasketService,
+    order_service: &OrderService,
+) -> anyhow::Result<()> {
+    // Confirm with the provider so a forged webhook for a known checkout id cannot change its status or amount.
+    let CheckoutStatus { status, amount } = payment_service.get_status(provider, checkout_id).await?;
+    if let (Some(webhook_amount), Some(amount)) = (webhook_amount, &amount) {
+        if webhook_amount != amount {
+            tracing::warn!("{} webhook for checkout {} says {:?} was paid but the provider reports {:?}", provider, checkout_id, webhook_amount, amount);
+        }
+    }
+    match status {
+        PaymentStatus::Paid => complete_paid_checkout(provider, checkout_id, amount.as_ref(), payment_service, basket_service, order_service).await,
+        PaymentStatus::Authorized => hold_authorized_checkout(provider, checkout_id, amount.as_ref(), payment_service, basket_service).await,
+        PaymentStatus::Failed | PaymentStatus::Expired | PaymentStatus::Cancelled | PaymentStatus::Voided => {
+            let basket_id = payment_service
+                .mark_payment_as_unsuccessful(provider, checkout_id, status)
+                .await?;
+            // Voided authorizations were holding the basket, nobody is going to pay for it anymore.
+            if let (PaymentStatus::Voided, Some(basket_id)) = (status, basket_id) {
+                basket_service.release_basket(&basket_id).await?;
+            }
+            Ok(())
+        }
+        _ => {
+            // Delayed payment methods complete the checkout before the money arrives.
+            tracing::info!("{} checkout {} is {} with the provider", provider, checkout_id, status);
+            Ok(())
+        }
     }
+}
+
+/// The money is held until staff capture or void it, the basket's inventories are held as long.
+async fn hold_authorized_checkout(
+    provider: &str,
+    checkout_id: &str,
+    authorized_amount: Option<&Money>,
+    payment_service: &PaymentService,
+    basket_service: &BasketService,
+) -> anyhow::Result<()> {
+    let authorized_amount = authorized_amount.ok_or(anyhow::anyhow!("{} reported no authorized amount for checkout {}", provider, checkout_id))?;
+    let Some(basket_id) = payment_service
+        .mark_payment_as_authorized(provider, checkout_id, authorized_amount)
+        .await?
+    else {
+        return Ok(());
+    };
 
-    payment_service
-        .mark_payment_as_paid(&webhook.id)
-        .await?;
+    let held_until = chrono::Utc::now() + chrono::Duration::days(AUTHORIZATION_HOLD_DAYS);
+    basket_service.hold_basket(&basket_id, held_until).await
+}
+
+async fn complete_paid_checkout(
+    provider: &str,
+    checkout_id: &str,
+    paid_amount: Option<&Money>,
+    payment_service: &PaymentService,
+    basket_service: &BasketService,
+    order_service: &OrderService,
+) -> anyhow::Result<()> {
+    // Without it there is nothing to check the payment against, the webhook stays in the inbox for someone to look at.
+    let paid_amount = paid_amount.ok_or(anyhow::anyhow!("{} reported no paid amount for checkout {}", provider, checkout_id))?;
+    let Some(basket_id) = payment_service
+        .mark_payment_as_paid(provider, checkout_id, paid_amount)
+        .await?
+    else {
+        // Flagged for review, the basket is only purchased once someone settles the difference.
+        return Ok(());
+    };
+
+    // Purchasing here means the customer gets their order even if they never return from the checkout page.
+    // The purchase endpoint stays available as a fallback, e.g. when attendee details are still missing.
+    match basket_service
+        .purchase_paid_basket(payment_service, order_service, &basket_id)
+        .await?
+    {
+        Some(order_id) => tracing::info!("Basket {} purchased as order {}", basket_id, order_id),
+        None => tracing::info!("Basket {} is not fully paid yet", basket_id),
+    }
 
     Ok(())
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_event_id() {
+        let stripe = br#"{"id": "evt_1", "type": "checkout.session.completed", "data": {"object": {"id": "cs_1"}}}"#;
+        assert_eq!(get_event_id(PAYMENT_PROVIDER_STRIPE, stripe).unwrap(), "evt_1");
+        assert!(get_event_id(PAYMENT_PROVIDER_STRIPE, b"not json").is_err());
+        assert!(get_event_id("PayPal", stripe).is_err());
+    }
+}
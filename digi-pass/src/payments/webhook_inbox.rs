@@ -0,0 +1,80 @@
+use crate::{baskets::application::BasketService, orders::application::OrderService};
+
+use super::{
+    application::{map_webhook_inbox_entry_to_webhook_view, PaymentService},
+    data_models::WebhookInboxEntry,
+    data_transfer_objects::{WebhookRetryReport, WebhookView},
+    webhook_handlers,
+};
+
+/// Stores the webhook and gives it its first attempt. Once stored, failures are left to the retries
+/// so the provider is not asked to send the webhook again.
+pub async fn receive_webhook(
+    provider: &str,
+    body: &[u8],
+    payment_service: &PaymentService,
+    basket_service: &BasketService,
+    order_service: &OrderService,
+) -> anyhow::Result<()> {
+    let event_id = webhook_handlers::get_event_id(provider, body)?;
+    let Some(mut entry) = payment_service.store_webhook(provider, &event_id, body).await? else {
+        return Ok(());
+    };
+
+    if let Err(err) = process_webhook(&mut entry, payment_service, basket_service, order_service).await {
+        tracing::error!("Error handling {} webhook {}: {:?}", provider, event_id, err);
+    }
+    Ok(())
+}
+
+/// Run by the scheduled jobs, processes webhooks whose next attempt or lease is due.
+pub async fn retry_due_webhooks(
+    payment_service: &PaymentService,
+    basket_service: &BasketService,
+    order_service: &OrderService,
+) -> anyhow::Result<WebhookRetryReport> {
+    let mut report = WebhookRetryReport::default();
+    for mut entry in payment_service.get_due_webhooks().await? {
+        match process_webhook(&mut entry, payment_service, basket_service, order_service).await {
+            Ok(()) => report.processed.push(entry.id.map(|id| id.to_hex()).unwrap_or_default()),
+            Err(err) => {
+                tracing::error!("Error retrying {} webhook {}: {:?}", entry.provider, entry.event_id, err);
+                report.failed.push(map_webhook_inbox_entry_to_webhook_view(&entry));
+            }
+        }
+    }
+
+    tracing::info!(
+        processed = report.processed.len(),
+        failed = report.failed.len(),
+        "webhook retries done"
+    );
+    Ok(report)
+}
+
+/// Processes a webhook right away, including ones that ran out of attempts.
+pub async fn replay_webhook(
+    webhook_id: &str,
+    payment_service: &PaymentService,
+    basket_service: &BasketService,
+    order_service: &OrderService,
+) -> anyhow::Result<WebhookView> {
+    let mut entry = payment_service.reset_webhook_for_replay(webhook_id).await?;
+    if let Err(err) = process_webhook(&mut entry, payment_service, basket_service, order_service).await {
+        tracing::error!("Error replaying {} webhook {}: {:?}", entry.provider, entry.event_id, err);
+    }
+    Ok(map_webhook_inbox_entry_to_webhook_view(&entry))
+}
+
+async fn process_webhook(
+    entry: &mut WebhookInboxEntry,
+    payment_service: &PaymentService,
+    basket_service: &BasketService,
+    order_service: &OrderService,
+) -> anyhow::Result<()> {
+    payment_service.claim_webhook(entry).await?;
+
+    let result = webhook_handlers::handle_webhook(&entry.provider, entry.body.as_bytes(), payment_service, basket_service, order_service).await;
+    payment_service.complete_webhook(entry, &result).await?;
+    result
+}
@@ -0,0 +1,44 @@
+use std::{sync::Arc, time::Duration};
+
+use lambda_http::{service_fn, Error, LambdaEvent};
+use serde_json::Value;
+
+use crate::{app_state::AppState, payments::{constants::DEFAULT_RECONCILE_AFTER_MINUTES, reconciliation, webhook_inbox}};
+
+/// Used when `ScheduledJobs__IntervalSeconds` is not set.
+pub const DEFAULT_INTERVAL_SECONDS: u64 = 60;
+
+/// Runs every job once, a failing job is logged and does not stop the others.
+pub async fn run_scheduled_jobs(state: &AppState) {
+    if let Err(err) = webhook_inbox::retry_due_webhooks(&state.payment_service, &state.basket_service, &state.order_service).await {
+        tracing::error!("Error retrying due webhooks: {:?}", err);
+    }
+    let older_than = chrono::Duration::minutes(DEFAULT_RECONCILE_AFTER_MINUTES);
+    if let Err(err) = reconciliation::reconcile_pending_payments(older_than, &state.payment_service, &state.basket_service, &state.order_service).await {
+        tracing::error!("Error reconciling pending payments: {:?}", err);
+    }
+}
+
+/// For local runs, the jobs run in the background of the server.
+pub fn spawn_scheduled_jobs(state: Arc<AppState>, interval: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            run_scheduled_jobs(&state).await;
+        }
+    });
+}
+
+/// In lambda the jobs get their own function, invoked by an EventBridge schedule whose event is ignored.
+pub async fn run_in_lambda(state: Arc<AppState>) -> Result<(), Error> {
+    lambda_http::lambda_runtime::run(service_fn(move |_event: LambdaEvent<Value>| {
+        let state = state.clone();
+        async move {
+            run_scheduled_jobs(&state).await;
+            Ok::<(), Error>(())
+        }
+    }))
+    .await
+}
@@ -1,7 +1,7 @@
 use async_trait::async_trait;
 use axum::{extract::{rejection::JsonRejection, FromRequest, Request}, http::StatusCode, response::{IntoResponse, Response}, Json};
 use serde::de::DeserializeOwned;
-use validator::Validate;
+use validator::{Validate, ValidationError, ValidationErrors};
 use thiserror::Error;
 
 
@@ -24,6 +24,23 @@ where
     }
 }
 
+/// For `#[validate(custom(function = "crate::validation::validate_optional"))]`, validator 0.17 cannot derive `nested` on `Option` fields.
+pub fn validate_optional<T: Validate>(value: &Option<T>) -> Result<(), ValidationError> {
+    match value {
+        Some(value) => value.validate().map_err(to_nested_error),
+        None => Ok(()),
+    }
+}
+
+/// Same as [`validate_optional`] for `Vec` fields.
+pub fn validate_all<T: Validate>(values: &[T]) -> Result<(), ValidationError> {
+    values.iter().try_for_each(|value| value.validate().map_err(to_nested_error))
+}
+
+fn to_nested_error(errors: ValidationErrors) -> ValidationError {
+    ValidationError::new("nested").with_message(errors.to_string().into())
+}
+
 #[derive(Debug, Error)]
 pub enum ServerError {
     #[error(transparent)]